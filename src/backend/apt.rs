@@ -1,4 +1,9 @@
-#[derive(Debug)]
+/// A reader for the dpkg status file and the package indices of apt. Parsing
+/// and choosing candidates are pure functions on text, only the `load_*`
+/// functions touch the file system.
+mod index;
+
+#[derive(Debug, Default)]
 pub struct AptBackend {}
 
 impl AptBackend {
@@ -12,14 +17,14 @@ impl crate::UpmBackend for AptBackend {
         let child = std::process::Command::new("apt-get")
             .arg("--version")
             .output()?;
-        if child.status.success() == false {
+        if !child.status.success() {
             return Ok(crate::BackendSetup::NotInstalled);
         }
 
         let child = std::process::Command::new("apt")
             .arg("--version")
             .output()?;
-        if child.status.success() == false {
+        if !child.status.success() {
             return Err(anyhow::anyhow!("apt is not found."));
         }
 
@@ -27,6 +32,25 @@ impl crate::UpmBackend for AptBackend {
             update: true,
             outdated: false,
             upgrade: true,
+            install: true,
+            uninstall: true,
+            resolve: false,
+            search: false,
+            info: false,
+            list_installed: false,
+            simulate: false,
+            hold: true,
+            holds: false,
+            clean: true,
+            orphans: false,
+            rollback: true,
+            changelog: false,
+            repos: false,
+            repo_edit: true,
+            deps: false,
+            owns: false,
+            files: false,
+            verify: true,
         });
         Ok(setup)
     }
@@ -35,7 +59,7 @@ impl crate::UpmBackend for AptBackend {
         let apt = std::process::Command::new("apt-get")
             .arg("update")
             .output()?;
-        if apt.status.success() == false {
+        if !apt.status.success() {
             let output = String::from_utf8_lossy(&apt.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
@@ -43,55 +67,2258 @@ impl crate::UpmBackend for AptBackend {
     }
 
     fn outdated(&self) -> anyhow::Result<crate::rpc::OutdatedResult> {
-        let apt = std::process::Command::new("apt")
-            .env("LANG", "en_US.UTF-8")
-            .env("LANGUAGE", "en_US")
-            .args(&["list", "--upgradable"])
+        let mut ret = crate::rpc::OutdatedResult { pkgs: Vec::new() };
+        for item in index::load_upgradable()? {
+            // Multi-arch packages are listed once per architecture.
+            if ret.pkgs.iter().any(|x| x.name == item.name) {
+                continue;
+            }
+
+            let mut archives = item.archives;
+            archives.sort();
+            archives.dedup();
+            let vendor = archives.join(",");
+            ret.pkgs.push(crate::rpc::OutdateItem {
+                name: item.name,
+                security: is_security_pocket(&vendor),
+                vendor,
+                current_version: item.current_version,
+                target_version: item.candidate_version,
+                status: crate::rpc::OutdateStatus::Upgradable,
+            });
+        }
+
+        classify_outdated(&mut ret.pkgs)?;
+        Ok(ret)
+    }
+
+    fn upgrade(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::UpgradeResult> {
+        let Some(args) = upgrade_args(self, options)? else {
+            return Ok(crate::rpc::UpgradeResult {
+                pending: Vec::new(),
+            });
+        };
+
+        apt_upgrade(args, options)
+    }
+
+    fn install(&self, pkgs: &[crate::rpc::PackageSpec]) -> anyhow::Result<()> {
+        let mut args = vec!["install".to_string(), "-y".to_string()];
+        // An older version is asked for on purpose.
+        if pkgs.iter().any(|x| x.version.is_some()) {
+            args.push("--allow-downgrades".to_string());
+        }
+        for pkg in pkgs {
+            match &pkg.version {
+                Some(version) => args.push(format!("{}={}", pkg.name, version)),
+                None => args.push(pkg.name.clone()),
+            }
+        }
+
+        let apt = std::process::Command::new("apt-get")
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            .args(&args)
             .output()?;
-        if apt.status.success() == false {
+        if !apt.status.success() {
             let output = String::from_utf8_lossy(&apt.stderr);
-            return Err(anyhow::anyhow!("{}", output.to_string(),));
+            return Err(anyhow::anyhow!("{}", output.to_string()));
         }
+        Ok(())
+    }
 
-        let output = String::from_utf8_lossy(&apt.stdout);
-        let output = output.to_string();
-        println!("{}", output);
+    fn uninstall(
+        &self,
+        pkgs: &[crate::rpc::PackageSpec],
+        options: &crate::rpc::UninstallOptions,
+    ) -> anyhow::Result<crate::rpc::UninstallResult> {
+        let mut args = vec![if options.purge { "purge" } else { "remove" }.to_string()];
+        args.push("-y".to_string());
+        if options.autoremove {
+            args.push("--auto-remove".to_string());
+        }
+        // apt removes the packages that depend on the removed ones along with
+        // them, so there is nothing to force. Essential packages stay protected.
+        for pkg in pkgs {
+            args.push(pkg.name.clone());
+        }
 
-        let lines: Vec<&str> = output.lines().collect();
-        let re =
-            regex::Regex::new(r"(\S+)/(\S+)\s+(\S+)\s+\(\S+)\s+[upgradable from: (\S+)\]").unwrap();
+        // Simulate first so we know exactly which packages go away.
+        let apt = std::process::Command::new("apt-get")
+            .env("LANG", "C")
+            .arg("-s")
+            .args(&args)
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        let ret = crate::rpc::UninstallResult {
+            pkgs: parse_simulated_removals(&output),
+        };
 
-        let mut ret = crate::rpc::OutdatedResult { pkgs: Vec::new() };
-        for line in lines {
-            let Some(caps) = re.captures(line) else {
+        if options.dry_run {
+            return Ok(ret);
+        }
+
+        let apt = std::process::Command::new("apt-get")
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            .args(&args)
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        Ok(ret)
+    }
+
+    fn resolve(&self, name: &str) -> anyhow::Result<Vec<crate::rpc::PackageSpec>> {
+        // Unknown packages produce no output at all.
+        let policy = apt_cache_policy(&[name])?;
+        let Some(version) = policy.get(name).and_then(|x| x.candidate.clone()) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(vec![crate::rpc::PackageSpec {
+            name: name.to_string(),
+            version: Some(version),
+            vendor: None,
+        }])
+    }
+
+    fn search(&self, query: &str) -> anyhow::Result<Vec<crate::rpc::SearchItem>> {
+        let apt = std::process::Command::new("apt-cache")
+            .env("LANG", "C")
+            .args(["search", query])
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        let mut ret = Vec::new();
+        for line in output.lines() {
+            let Some((name, summary)) = line.split_once(" - ") else {
                 continue;
             };
+            ret.push(crate::rpc::SearchItem {
+                name: name.to_string(),
+                backend: "apt".to_string(),
+                vendor: String::new(),
+                version: String::new(),
+                summary: summary.to_string(),
+                installed: false,
+            });
+        }
+        if ret.is_empty() {
+            return Ok(ret);
+        }
+
+        // `apt-cache search` only gives names, fill in the versions and the
+        // suites the candidate comes from.
+        let names: Vec<&str> = ret.iter().map(|x| x.name.as_str()).collect();
+        let policy = apt_cache_policy(&names)?;
+        for item in ret.iter_mut() {
+            let Some(policy) = policy.get(&item.name) else {
+                continue;
+            };
+            item.version = policy.candidate.clone().unwrap_or_default();
+            item.installed = policy.installed.is_some();
+
+            let mut suites: Vec<&str> = Vec::new();
+            let origins = policy.version(&item.version).map(|x| x.origins.as_slice());
+            for origin in origins.unwrap_or_default() {
+                let suite = origin.split('/').next().unwrap_or(origin);
+                if suite != "now" && !suites.contains(&suite) {
+                    suites.push(suite);
+                }
+            }
+            item.vendor = suites.join(",");
+        }
+
+        Ok(ret)
+    }
+
+    fn info(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageInfo>> {
+        let apt = std::process::Command::new("apt-cache")
+            .env("LANG", "C")
+            .args(["show", "--no-all-versions", name])
+            .output()?;
+        if !apt.status.success() {
+            // apt-cache fails for names it does not know.
+            return Ok(None);
+        }
+
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        let fields = parse_control(&output);
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        let policy = apt_cache_policy(&[name])?;
+        let policy = policy.get(name);
+        let candidate = policy.and_then(|x| x.candidate.clone());
+        let origin = policy
+            .zip(candidate.as_deref())
+            .and_then(|(x, v)| x.version(v))
+            .and_then(|x| x.origins.first().cloned());
+
+        // Only the package names, without version constraints or alternatives.
+        let mut dependencies = Vec::new();
+        for dep in field("Depends").unwrap_or_default().split(',') {
+            if let Some(dep) = dep
+                .split('|')
+                .next()
+                .and_then(|v| v.split_whitespace().next())
+            {
+                dependencies.push(dep.to_string());
+            }
+        }
+
+        let info = crate::rpc::PackageInfo {
+            name: name.to_string(),
+            backend: "apt".to_string(),
+            description: field("Description")
+                .or_else(|| field("Description-en"))
+                .unwrap_or_default(),
+            homepage: field("Homepage"),
+            license: copyright_license(name),
+            installed_version: policy.and_then(|x| x.installed.clone()),
+            candidate_version: candidate,
+            download_size: field("Size").and_then(|v| v.parse().ok()),
+            installed_size: field("Installed-Size")
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|v| v * 1024),
+            origin,
+            dependencies,
+        };
+        Ok(Some(info))
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<crate::rpc::InstalledItem>> {
+        let dpkg = std::process::Command::new("dpkg-query")
+            .args([
+                "-W",
+                "-f=${Package}\t${Version}\t${Architecture}\t${db:Status-Abbrev}\n",
+            ])
+            .output()?;
+        if !dpkg.status.success() {
+            let output = String::from_utf8_lossy(&dpkg.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        let manual = apt_mark("showmanual", &[])?;
+        let manual: std::collections::HashSet<&str> = manual.lines().map(|v| v.trim()).collect();
+
+        let output = String::from_utf8_lossy(&dpkg.stdout).to_string();
+        let mut ret = Vec::new();
+        for line in output.lines() {
+            let cols: Vec<&str> = line.split('\t').collect();
+            // Only fully installed packages, not removed ones with config left.
+            if cols.len() != 4 || !cols[3].starts_with("ii") {
+                continue;
+            }
+
+            ret.push(crate::rpc::InstalledItem {
+                name: cols[0].to_string(),
+                version: cols[1].to_string(),
+                arch: cols[2].to_string(),
+                backend: "apt".to_string(),
+                origin: String::new(),
+                manual: manual.contains(cols[0]),
+                revision: String::new(),
+            });
+        }
+
+        // The origin is the first repository still providing the installed version.
+        let names: Vec<&str> = ret.iter().map(|x| x.name.as_str()).collect();
+        let policy = apt_cache_policy(&names)?;
+        for item in ret.iter_mut() {
+            let origin = policy
+                .get(&item.name)
+                .and_then(|x| x.version(&item.version))
+                .and_then(|x| x.origins.iter().find(|v| *v != "now"));
+            item.origin = origin.cloned().unwrap_or("local".to_string());
+        }
+
+        Ok(ret)
+    }
+
+    fn simulate(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::SimulateResult> {
+        let Some(args) = upgrade_args(self, options)? else {
+            return Ok(crate::rpc::SimulateResult::default());
+        };
+
+        let apt = std::process::Command::new("apt-get")
+            .env("LANG", "C")
+            .arg("-s")
+            .args(&args)
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        Ok(parse_simulated_upgrade(&output))
+    }
 
-            let package_name = caps.get(1).unwrap().as_str();
-            let ventor = caps.get(2).unwrap().as_str();
-            let current_version = caps.get(5).unwrap().as_str();
-            let target_version = caps.get(3).unwrap().as_str();
+    fn hold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        apt_mark("hold", pkgs)?;
+        Ok(())
+    }
+
+    fn unhold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        apt_mark("unhold", pkgs)?;
+        Ok(())
+    }
+
+    fn holds(&self) -> anyhow::Result<Vec<String>> {
+        let output = apt_mark("showhold", &[])?;
+        Ok(output.lines().map(|v| v.trim().to_string()).collect())
+    }
 
-            let item = crate::rpc::OutdateItem {
-                name: package_name.to_string(),
-                vendor: ventor.to_string(),
-                current_version: current_version.to_string(),
-                target_version: target_version.to_string(),
+    fn clean(&self, options: &crate::rpc::CleanOptions) -> anyhow::Result<crate::rpc::CleanResult> {
+        let command = if options.all { "clean" } else { "autoclean" };
+
+        if options.dry_run {
+            let files = if options.all {
+                cached_files(|_| true)
+            } else {
+                let apt = std::process::Command::new("apt-get")
+                    .args(["-s", command])
+                    .output()?;
+                if !apt.status.success() {
+                    let output = String::from_utf8_lossy(&apt.stderr);
+                    return Err(anyhow::anyhow!("{}", output.to_string()));
+                }
+                let output = String::from_utf8_lossy(&apt.stdout).to_string();
+                let prefixes = parse_simulated_autoclean(&output);
+                cached_files(|name| prefixes.iter().any(|v| name.starts_with(v)))
             };
-            ret.pkgs.push(item);
+            return Ok(crate::rpc::CleanResult {
+                reclaimed: super::disk_usage(&files),
+            });
+        }
+
+        let before = super::disk_usage(&[APT_CACHE_DIR]);
+        let apt = std::process::Command::new("apt-get")
+            .arg(command)
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
         }
+        let after = super::disk_usage(&[APT_CACHE_DIR]);
 
+        Ok(crate::rpc::CleanResult {
+            reclaimed: before.saturating_sub(after),
+        })
+    }
+
+    fn orphans(&self) -> anyhow::Result<Vec<crate::rpc::RemoveItem>> {
+        let apt = std::process::Command::new("apt-get")
+            .env("LANG", "C")
+            .args(["-s", "autoremove"])
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+
+        let holds = self.holds()?;
+        let mut ret = parse_simulated_removals(&output);
+        ret.retain(|x| !holds.contains(&x.name));
         Ok(ret)
     }
 
-    fn upgrade(&self) -> anyhow::Result<()> {
+    fn changelog(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageChangelog>> {
+        let policy = apt_cache_policy(&[name])?;
+        let Some(policy) = policy.get(name) else {
+            return Ok(None);
+        };
+        let Some(target) = policy.candidate.clone() else {
+            return Ok(None);
+        };
+
+        let apt = std::process::Command::new("apt-get")
+            .env("LANG", "C")
+            .args(["changelog", name])
+            .output()?;
+        let text = if apt.status.success() {
+            String::from_utf8_lossy(&apt.stdout).to_string()
+        } else {
+            // Offline, the installed version still has its changelog.
+            let path = std::path::Path::new("/usr/share/doc")
+                .join(name)
+                .join("changelog.Debian.gz");
+            match super::read_text(&path) {
+                Ok(v) => v,
+                Err(_) => {
+                    let output = String::from_utf8_lossy(&apt.stderr);
+                    return Err(anyhow::anyhow!("{}", output.to_string()));
+                }
+            }
+        };
+
+        let entries = parse_debian_changelog(&text);
+        Ok(Some(crate::rpc::PackageChangelog {
+            name: name.to_string(),
+            current_version: policy.installed.clone(),
+            entries: super::trim_changelog(entries, policy.installed.as_deref(), &target),
+            target_version: target,
+        }))
+    }
+
+    fn rollback(&self, changes: &[crate::history::Change]) -> anyhow::Result<()> {
+        // Older records carry no architecture, they mean the native one.
+        let specs: Vec<String> = changes
+            .iter()
+            .map(|x| match x.arch.as_str() {
+                "" => x.name.clone(),
+                arch => format!("{}:{}", x.name, arch),
+            })
+            .collect();
+        let names: Vec<&str> = specs.iter().map(|x| x.as_str()).collect();
+        let policy = apt_cache_policy(&names)?;
+
+        // `pkg=version` needs a repository that still has the version,
+        // otherwise fall back to the downloaded package.
+        let mut args = vec![
+            "install".to_string(),
+            "-y".to_string(),
+            "--allow-downgrades".to_string(),
+        ];
+        for (change, spec) in changes.iter().zip(specs.iter()) {
+            let Some(version) = &change.old_version else {
+                args.push(format!("{}-", spec));
+                continue;
+            };
+
+            // apt-cache leaves out the native architecture.
+            let available = policy
+                .get(spec)
+                .or_else(|| policy.get(&change.name))
+                .is_some_and(|x| {
+                    x.version(version)
+                        .is_some_and(|v| v.origins.iter().any(|o| o != "now"))
+                });
+            if available {
+                args.push(format!("{}={}", spec, version));
+            } else if let Some(path) = cached_deb(&change.name, version, &change.arch) {
+                args.push(path.to_string_lossy().to_string());
+            } else {
+                return Err(anyhow::anyhow!(
+                    "version {} of '{}' is neither cached nor available from a repository.",
+                    version,
+                    change.name
+                ));
+            }
+        }
+
         let apt = std::process::Command::new("apt-get")
-            .args(&["upgrade", "-y"])
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            .args(&args)
             .output()?;
-        if apt.status.success() == false {
+        if !apt.status.success() {
             let output = String::from_utf8_lossy(&apt.stderr);
-            return Err(anyhow::anyhow!("{}", output.to_string(),));
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn repos(&self) -> anyhow::Result<Vec<crate::rpc::Repository>> {
+        let mut paths = vec![std::path::PathBuf::from(APT_SOURCES_LIST)];
+        if let Ok(entries) = std::fs::read_dir(APT_SOURCES_DIR) {
+            let mut entries: Vec<std::path::PathBuf> =
+                entries.filter_map(|v| v.ok()).map(|v| v.path()).collect();
+            entries.sort();
+            paths.extend(entries);
+        }
+
+        let mut ret = Vec::new();
+        for path in paths {
+            let Some(name) = path.file_stem().map(|v| v.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            match path.extension().and_then(|v| v.to_str()) {
+                Some("sources") => {
+                    for stanza in parse_sources(&text) {
+                        let Some(url) = control_field(&stanza, "URIs") else {
+                            continue;
+                        };
+                        let words = |k: &str| -> Vec<String> {
+                            control_field(&stanza, k)
+                                .unwrap_or_default()
+                                .split_whitespace()
+                                .map(|v| v.to_string())
+                                .collect()
+                        };
+                        ret.push(crate::rpc::Repository {
+                            name: name.clone(),
+                            url: url.to_string(),
+                            enabled: control_field(&stanza, "Enabled")
+                                .is_none_or(|v| !v.eq_ignore_ascii_case("no")),
+                            backend: "apt".to_string(),
+                            suites: words("Suites"),
+                            components: words("Components"),
+                        });
+                    }
+                }
+                Some("list") => {
+                    for (url, suite, components) in parse_one_line_sources(&text) {
+                        ret.push(crate::rpc::Repository {
+                            name: name.clone(),
+                            url,
+                            enabled: true,
+                            backend: "apt".to_string(),
+                            suites: vec![suite],
+                            components,
+                        });
+                    }
+                }
+                _ => {}
+            }
         }
+
+        Ok(ret)
+    }
+
+    fn repo_add(
+        &self,
+        repo: &crate::rpc::Repository,
+        options: &crate::rpc::RepoAddOptions,
+    ) -> anyhow::Result<()> {
+        let path = sources_path(&repo.name)?;
+        if path.exists() {
+            return Err(anyhow::anyhow!(
+                "repository '{}' already exists.",
+                repo.name
+            ));
+        }
+        if repo.suites.is_empty() {
+            return Err(anyhow::anyhow!("apt repositories need at least one suite."));
+        }
+        check_source_values(repo)?;
+
+        let mut text = format!(
+            "Types: deb\nURIs: {}\nSuites: {}\n",
+            repo.url,
+            repo.suites.join(" ")
+        );
+        if !repo.components.is_empty() {
+            text.push_str(&format!("Components: {}\n", repo.components.join(" ")));
+        }
+        if let Some(key) = &options.key {
+            // apt reads armored keys only from files named `.asc`.
+            let data = super::read_key(key)?;
+            let ext = if data.starts_with(b"-----BEGIN PGP") {
+                "asc"
+            } else {
+                "gpg"
+            };
+            let keyring =
+                std::path::Path::new(APT_KEYRINGS_DIR).join(format!("{}.{}", repo.name, ext));
+            std::fs::create_dir_all(APT_KEYRINGS_DIR)?;
+            std::fs::write(&keyring, data)?;
+            text.push_str(&format!("Signed-By: {}\n", keyring.display()));
+        }
+        if !repo.enabled {
+            text.push_str("Enabled: no\n");
+        }
+
+        std::fs::write(&path, text)?;
         Ok(())
     }
+
+    fn repo_remove(&self, name: &str) -> anyhow::Result<()> {
+        let path = sources_path(name)?;
+        let text = match std::fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!(
+                    "repository '{}' is not a deb822 source in {}.",
+                    name,
+                    APT_SOURCES_DIR
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Only remove the keyrings `repo_add` wrote, others may be shared.
+        let keyrings: Vec<std::path::PathBuf> = parse_sources(&text)
+            .iter()
+            .filter_map(|x| control_field(x, "Signed-By"))
+            .map(std::path::PathBuf::from)
+            .filter(|x| {
+                x.parent() == Some(std::path::Path::new(APT_KEYRINGS_DIR))
+                    && x.file_stem().is_some_and(|v| v == name)
+            })
+            .collect();
+
+        std::fs::remove_file(&path)?;
+        for keyring in keyrings {
+            if let Err(e) = std::fs::remove_file(&keyring) {
+                log::warn!("cannot remove keyring '{}': {}", keyring.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repo_enable(&self, name: &str, enabled: bool) -> anyhow::Result<()> {
+        let path = sources_path(name)?;
+        let text = match std::fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow::anyhow!(
+                    "repository '{}' is not a deb822 source in {}.",
+                    name,
+                    APT_SOURCES_DIR
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        std::fs::write(&path, set_sources_enabled(&text, enabled))?;
+        Ok(())
+    }
+
+    fn deps(
+        &self,
+        name: &str,
+        options: &crate::rpc::DepsOptions,
+    ) -> anyhow::Result<Option<Vec<crate::rpc::Dependency>>> {
+        let command = if options.reverse {
+            "rdepends"
+        } else {
+            "depends"
+        };
+        let mut args = vec![
+            command,
+            "--recurse",
+            "--no-suggests",
+            "--no-conflicts",
+            "--no-breaks",
+            "--no-replaces",
+            "--no-enhances",
+        ];
+        if options.installed {
+            args.push("--installed");
+        }
+        let apt = std::process::Command::new("apt-cache")
+            .env("LANG", "C")
+            .args(&args)
+            .arg(name)
+            .output()?;
+        if !apt.status.success() {
+            // apt-cache fails for names it does not know.
+            return Ok(None);
+        }
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        let graph = parse_apt_depends(&output);
+
+        // apt-cache only prints names, the constraints come from the
+        // package that declares the dependency.
+        let names: Vec<&str> = graph.keys().map(|v| v.as_str()).collect();
+        let relations = apt_relations(&names)?;
+        let installed: std::collections::HashSet<String> =
+            self.list_installed()?.into_iter().map(|x| x.name).collect();
+
+        let mut direct = std::collections::HashMap::new();
+        for (parent, children) in graph.iter() {
+            let mut deps = Vec::new();
+            for (child, kind) in children {
+                // A reverse dependency declares the relation itself.
+                let (owner, target) = if options.reverse {
+                    (child, parent)
+                } else {
+                    (parent, child)
+                };
+                let relation = relations
+                    .get(owner)
+                    .and_then(|x| x.iter().find(|x| x.name == *target));
+                deps.push(crate::rpc::Dependency {
+                    name: child.clone(),
+                    constraint: relation.map(|x| x.constraint.clone()).unwrap_or_default(),
+                    kind: relation
+                        .map(|x| x.kind)
+                        .or(*kind)
+                        .unwrap_or(crate::rpc::DependencyKind::Depends),
+                    installed: installed.contains(child),
+                    deps: Vec::new(),
+                });
+            }
+            direct.insert(parent.clone(), deps);
+        }
+
+        Ok(Some(super::dependency_tree(name, &direct)))
+    }
+
+    fn owns(&self, path: &std::path::Path) -> anyhow::Result<Vec<crate::rpc::FileOwner>> {
+        let mut names: Vec<String> = Vec::new();
+        for path in usr_merge_aliases(path) {
+            let dpkg = std::process::Command::new("dpkg-query")
+                .env("LANG", "C")
+                .arg("-S")
+                .arg(&path)
+                .output()?;
+            // dpkg-query fails for files no package owns.
+            if !dpkg.status.success() {
+                continue;
+            }
+            let output = String::from_utf8_lossy(&dpkg.stdout).to_string();
+            for name in parse_dpkg_search(&output) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let dpkg = std::process::Command::new("dpkg-query")
+            .args(["-W", "-f", "${binary:Package}\t${Version}\n"])
+            .args(&names)
+            .output()?;
+        if !dpkg.status.success() {
+            let output = String::from_utf8_lossy(&dpkg.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+        let output = String::from_utf8_lossy(&dpkg.stdout).to_string();
+        let versions: std::collections::HashMap<&str, &str> =
+            output.lines().filter_map(|v| v.split_once('\t')).collect();
+
+        Ok(names
+            .into_iter()
+            .map(|name| crate::rpc::FileOwner {
+                version: versions.get(name.as_str()).unwrap_or(&"").to_string(),
+                name,
+                backend: "apt".to_string(),
+            })
+            .collect())
+    }
+
+    fn files(&self, name: &str) -> anyhow::Result<Option<Vec<std::path::PathBuf>>> {
+        let dpkg = std::process::Command::new("dpkg-query")
+            .env("LANG", "C")
+            .args(["-L", name])
+            .output()?;
+        // dpkg-query fails for packages that are not installed.
+        if !dpkg.status.success() {
+            return Ok(None);
+        }
+
+        // The list includes directories and diversion notes.
+        let output = String::from_utf8_lossy(&dpkg.stdout).to_string();
+        let files = output
+            .lines()
+            .filter(|v| v.starts_with('/'))
+            .map(std::path::PathBuf::from)
+            .filter(|v| std::fs::symlink_metadata(v).map_or(true, |m| !m.is_dir()))
+            .collect();
+        Ok(Some(files))
+    }
+
+    fn verify(&self, pkgs: &[String]) -> anyhow::Result<Vec<crate::rpc::FileIssue>> {
+        // The control files of a package are `<pkg>[:<arch>].<kind>`.
+        let dir = std::path::Path::new(DPKG_INFO_DIR);
+        let matches = |name: &str, pkg: &str| name == pkg || name.split(':').next() == Some(pkg);
+        let mut names: Vec<String> = std::fs::read_dir(dir)?
+            .filter_map(|v| v.ok())
+            .filter_map(|v| {
+                let name = v.file_name().to_string_lossy().to_string();
+                name.strip_suffix(".list").map(|v| v.to_string())
+            })
+            .filter(|v| pkgs.is_empty() || pkgs.iter().any(|p| matches(v, p)))
+            .collect();
+        names.sort();
+        if let Some(pkg) = pkgs.iter().find(|p| !names.iter().any(|v| matches(v, p))) {
+            return Err(anyhow::anyhow!("package '{}' is not installed.", pkg));
+        }
+
+        let diversions = dpkg_diversions();
+        let mut ret = Vec::new();
+        for name in names {
+            // Not every package ships checksums.
+            let Ok(text) = std::fs::read_to_string(dir.join(format!("{}.md5sums", name))) else {
+                continue;
+            };
+            let pkg = name.split(':').next().unwrap_or(&name);
+
+            for line in text.lines() {
+                let Some((sum, file)) = line.split_once("  ") else {
+                    continue;
+                };
+                let mut path = std::path::Path::new("/").join(file);
+                // Another package or the admin may have moved the file away.
+                if let Some((to, by)) = diversions.get(&path) {
+                    // What the admin put in place of a diverted file belongs
+                    // to no package.
+                    if by == ":" && path.symlink_metadata().is_ok() {
+                        ret.push(crate::rpc::FileIssue {
+                            name: pkg.to_string(),
+                            path: path.to_string_lossy().to_string(),
+                            status: crate::rpc::FileStatus::Unexpected,
+                        });
+                    }
+                    if by != pkg {
+                        path = to.clone();
+                    }
+                }
+
+                // The package installed a regular file, not a link or a
+                // directory.
+                if path.symlink_metadata().is_ok_and(|v| !v.is_file()) {
+                    ret.push(crate::rpc::FileIssue {
+                        name: pkg.to_string(),
+                        path: path.to_string_lossy().to_string(),
+                        status: crate::rpc::FileStatus::Unexpected,
+                    });
+                    continue;
+                }
+
+                let status = match file_md5(&path) {
+                    Ok(v) if v == sum => continue,
+                    Ok(_) => crate::rpc::FileStatus::Modified,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        crate::rpc::FileStatus::Missing
+                    }
+                    Err(e) => {
+                        log::warn!("cannot read '{}': {}", path.display(), e);
+                        continue;
+                    }
+                };
+                ret.push(crate::rpc::FileIssue {
+                    name: pkg.to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    status,
+                });
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// The directory dpkg keeps the control files of installed packages in.
+const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+
+/// Read the diversions dpkg knows about.
+///
+/// `/var/lib/dpkg/diversions` holds three lines per diversion: the original
+/// path, where it was moved to and the diverting package, `:` for the admin.
+///
+/// # Returns
+/// Where each diverted path was moved to and by which package.
+fn dpkg_diversions() -> std::collections::HashMap<std::path::PathBuf, (std::path::PathBuf, String)>
+{
+    let text = std::fs::read_to_string("/var/lib/dpkg/diversions").unwrap_or_default();
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .chunks_exact(3)
+        .map(|v| (v[0].into(), (v[1].into(), v[2].to_string())))
+        .collect()
+}
+
+/// Compute the MD5 checksum of a file.
+///
+/// # Arguments
+/// + `path` - The path of the file.
+///
+/// # Returns
+/// The checksum as lowercase hex, the way `md5sums` files list it.
+fn file_md5(path: &std::path::Path) -> std::io::Result<String> {
+    use md5::Digest;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = md5::Md5::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Get the paths a file may be known to dpkg by.
+///
+/// With merged `/usr`, `/bin` links to `/usr/bin`, but dpkg only knows the
+/// path a file was packaged with.
+///
+/// # Arguments
+/// + `path` - The absolute path of the file.
+///
+/// # Returns
+/// The path, its canonical form and both with and without `/usr`.
+fn usr_merge_aliases(path: &std::path::Path) -> Vec<std::path::PathBuf> {
+    const MERGED: [&str; 6] = ["bin", "sbin", "lib", "lib32", "lib64", "libx32"];
+
+    let mut paths = vec![path.to_path_buf()];
+    if let Ok(v) = std::fs::canonicalize(path) {
+        paths.push(v);
+    }
+
+    let mut ret: Vec<std::path::PathBuf> = Vec::new();
+    for path in paths {
+        let alias = MERGED.iter().find_map(|dir| {
+            let usr = std::path::Path::new("/usr").join(dir);
+            let root = std::path::Path::new("/").join(dir);
+            if let Ok(rest) = path.strip_prefix(&usr) {
+                Some(root.join(rest))
+            } else {
+                path.strip_prefix(&root).ok().map(|rest| usr.join(rest))
+            }
+        });
+        for path in std::iter::once(path).chain(alias) {
+            if !ret.contains(&path) {
+                ret.push(path);
+            }
+        }
+    }
+
+    ret
+}
+
+/// Parse the output of `dpkg-query -S`.
+///
+/// Each match is printed as `pkg1, pkg2: /path`, diversions as
+/// `diversion by pkg from: /path`.
+///
+/// # Arguments
+/// + `output` - The output of dpkg-query.
+///
+/// # Returns
+/// The names of the packages that own the file.
+fn parse_dpkg_search(output: &str) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("diversion by ") {
+            continue;
+        }
+        let Some((names, _)) = line.split_once(": ") else {
+            continue;
+        };
+        for name in names.split(", ") {
+            if !ret.iter().any(|v| v == name) {
+                ret.push(name.to_string());
+            }
+        }
+    }
+
+    ret
+}
+
+/// Parse the output of `apt-cache depends --recurse` or `apt-cache rdepends
+/// --recurse`.
+///
+/// Each package starts a block of indented dependencies, `Depends: name` for
+/// depends and a plain name for rdepends. Alternatives are marked with a
+/// leading `|`, only the first one of a group is kept. The providers listed
+/// below a virtual package are left out.
+///
+/// # Arguments
+/// + `output` - The output of apt-cache.
+///
+/// # Returns
+/// The direct dependencies of each package, with their kind if apt-cache
+/// printed it.
+fn parse_apt_depends(
+    output: &str,
+) -> std::collections::HashMap<String, Vec<(String, Option<crate::rpc::DependencyKind>)>> {
+    let mut ret: std::collections::HashMap<
+        String,
+        Vec<(String, Option<crate::rpc::DependencyKind>)>,
+    > = std::collections::HashMap::new();
+    let mut current: Option<String> = None;
+    let mut alternative = false;
+    for line in output.lines() {
+        if line.starts_with("    ") || line.ends_with(':') {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            let name = line.trim().trim_start_matches('<').trim_end_matches('>');
+            ret.entry(name.to_string()).or_default();
+            current = Some(name.to_string());
+            alternative = false;
+            continue;
+        }
+        let Some(parent) = &current else {
+            continue;
+        };
+
+        let entry = line.trim_start();
+        let first = !alternative;
+        alternative = entry.starts_with('|');
+        if !first {
+            continue;
+        }
+        let entry = entry.trim_start_matches('|');
+
+        let (kind, name) = match entry.split_once(": ") {
+            Some(("Depends" | "PreDepends", v)) => (Some(crate::rpc::DependencyKind::Depends), v),
+            Some(("Recommends", v)) => (Some(crate::rpc::DependencyKind::Recommends), v),
+            Some((_, _)) => continue,
+            None => (None, entry),
+        };
+        let name = name.trim().trim_start_matches('<').trim_end_matches('>');
+
+        let deps = ret.entry(parent.clone()).or_default();
+        if !deps.iter().any(|(k, _)| k == name) {
+            deps.push((name.to_string(), kind));
+        }
+    }
+
+    ret
+}
+
+/// Get the relations packages declare in their control data.
+///
+/// # Arguments
+/// + `names` - The names of packages.
+///
+/// # Returns
+/// Each `Pre-Depends`, `Depends` and `Recommends` entry of each package,
+/// without their own dependencies. Packages apt does not know, such as
+/// virtual packages, are left out.
+fn apt_relations(
+    names: &[&str],
+) -> anyhow::Result<std::collections::HashMap<String, Vec<crate::rpc::Dependency>>> {
+    let mut ret = std::collections::HashMap::new();
+    if names.is_empty() {
+        return Ok(ret);
+    }
+
+    let apt = std::process::Command::new("apt-cache")
+        .env("LANG", "C")
+        .args(["show", "--no-all-versions"])
+        .args(names)
+        .output()?;
+    let output = String::from_utf8_lossy(&apt.stdout).to_string();
+
+    let re = regex::Regex::new(r"^([^\s:(\[]+)(?::\S+)?\s*(?:\(([^)]*)\))?").unwrap();
+    for stanza in output.split("\n\n") {
+        let fields = parse_control(stanza);
+        let Some((_, name)) = fields.iter().find(|(k, _)| k == "Package") else {
+            continue;
+        };
+
+        let mut relations = Vec::new();
+        for (key, value) in fields.iter() {
+            let kind = match key.as_str() {
+                "Pre-Depends" | "Depends" => crate::rpc::DependencyKind::Depends,
+                "Recommends" => crate::rpc::DependencyKind::Recommends,
+                _ => continue,
+            };
+            for entry in value.split([',', '|']) {
+                let Some(caps) = re.captures(entry.trim()) else {
+                    continue;
+                };
+                let constraint = caps.get(2).map_or("", |v| v.as_str());
+                relations.push(crate::rpc::Dependency {
+                    name: caps.get(1).unwrap().as_str().to_string(),
+                    constraint: constraint.split_whitespace().collect::<Vec<_>>().join(" "),
+                    kind,
+                    installed: false,
+                    deps: Vec::new(),
+                });
+            }
+        }
+        ret.insert(name.clone(), relations);
+    }
+
+    Ok(ret)
+}
+
+/// The legacy one-line sources file.
+const APT_SOURCES_LIST: &str = "/etc/apt/sources.list";
+
+/// The directory of apt source files.
+const APT_SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+
+/// The directory of keyrings that sources refer to with `Signed-By`.
+const APT_KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+
+/// Get the deb822 source file of a repository.
+///
+/// # Arguments
+/// + `name` - The name of repository.
+///
+/// # Returns
+/// The path of the file. An error if apt would ignore a file of this name.
+fn sources_path(name: &str) -> anyhow::Result<std::path::PathBuf> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if name.is_empty() || !valid {
+        return Err(anyhow::anyhow!(
+            "invalid repository name '{}', use letters, digits, '_', '-' and '.'.",
+            name
+        ));
+    }
+
+    Ok(std::path::Path::new(APT_SOURCES_DIR).join(format!("{}.sources", name)))
+}
+
+/// The URL schemes apt has transports for.
+const APT_URL_SCHEMES: [&str; 9] = [
+    "http",
+    "https",
+    "ftp",
+    "file",
+    "mirror",
+    "mirror+http",
+    "mirror+https",
+    "tor+http",
+    "tor+https",
+];
+
+/// Check the values written into a deb822 source.
+///
+/// Root writes the file, so a value must not be able to start a new field.
+///
+/// # Arguments
+/// + `repo` - The repository to add.
+///
+/// # Returns
+/// `Ok(())` if the URL has a known scheme and no value is empty or contains
+/// whitespace or control characters.
+fn check_source_values(repo: &crate::rpc::Repository) -> anyhow::Result<()> {
+    let values = std::iter::once(&repo.url)
+        .chain(repo.suites.iter())
+        .chain(repo.components.iter());
+    for value in values {
+        if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(anyhow::anyhow!(
+                "invalid value {:?}, it must not be empty or contain whitespace.",
+                value
+            ));
+        }
+    }
+
+    let scheme = repo.url.split_once("://").map(|(v, _)| v);
+    if !scheme.is_some_and(|v| APT_URL_SCHEMES.contains(&v)) {
+        return Err(anyhow::anyhow!(
+            "unsupported URL '{}', use one of {}.",
+            repo.url,
+            APT_URL_SCHEMES
+                .iter()
+                .map(|v| format!("{}://", v))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parse a deb822 sources file.
+///
+/// # Arguments
+/// + `text` - The content of the file.
+///
+/// # Returns
+/// The fields of each stanza, comments left out.
+fn parse_sources(text: &str) -> Vec<Vec<(String, String)>> {
+    let text: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    text.join("\n")
+        .split("\n\n")
+        .map(parse_control)
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Get a field of a deb822 stanza, whose names are case insensitive.
+///
+/// # Arguments
+/// + `stanza` - The fields of the stanza.
+/// + `key` - The name of the field.
+///
+/// # Returns
+/// The value, `None` if the field is missing.
+fn control_field<'a>(stanza: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    stanza
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Parse a one-line sources file such as `/etc/apt/sources.list`.
+///
+/// Each entry looks like `deb [options] uri suite [component...]`.
+///
+/// # Arguments
+/// + `text` - The content of the file.
+///
+/// # Returns
+/// The URI, suite and components of the entries that are not commented out.
+fn parse_one_line_sources(text: &str) -> Vec<(String, String, Vec<String>)> {
+    let re = regex::Regex::new(r"^\s*deb(?:-src)?\s+(?:\[[^\]]*\]\s+)?(\S+)\s+(\S+)(.*)").unwrap();
+    text.lines()
+        .filter_map(|line| re.captures(line))
+        .map(|caps| {
+            let components = caps[3]
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|v| v.to_string())
+                .collect();
+            (caps[1].to_string(), caps[2].to_string(), components)
+        })
+        .collect()
+}
+
+/// Enable or disable every stanza of a deb822 sources file.
+///
+/// Comments and other fields are kept as they are.
+///
+/// # Arguments
+/// + `text` - The content of the file.
+/// + `enabled` - Whether apt should use the stanzas.
+///
+/// # Returns
+/// The new content of the file.
+fn set_sources_enabled(text: &str, enabled: bool) -> String {
+    let mut ret: Vec<String> = Vec::new();
+    let mut stanza: Vec<&str> = Vec::new();
+    let flush = |stanza: &mut Vec<&str>, ret: &mut Vec<String>| {
+        let fields = stanza.iter().any(|v| !v.starts_with('#'));
+        ret.extend(
+            stanza
+                .drain(..)
+                .filter(|v| {
+                    !v.split_once(':')
+                        .is_some_and(|(k, _)| k.eq_ignore_ascii_case("Enabled"))
+                })
+                .map(|v| v.to_string()),
+        );
+        if fields && !enabled {
+            ret.push("Enabled: no".to_string());
+        }
+    };
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            flush(&mut stanza, &mut ret);
+            ret.push(String::new());
+        } else {
+            stanza.push(line);
+        }
+    }
+    flush(&mut stanza, &mut ret);
+
+    let mut ret = ret.join("\n");
+    ret.push('\n');
+    ret
+}
+
+/// Parse a Debian changelog.
+///
+/// Each entry starts with `name (version) suites; urgency=...`, followed by
+/// the indented changes and a ` -- maintainer  date` trailer.
+///
+/// # Arguments
+/// + `text` - The changelog.
+///
+/// # Returns
+/// The entries, newest first.
+fn parse_debian_changelog(text: &str) -> Vec<crate::rpc::ChangelogEntry> {
+    let header = regex::Regex::new(r"^\S+ \(([^)]+)\) [^;]*;").unwrap();
+    let trailer = regex::Regex::new(r"^ -- .*?  (.+)$").unwrap();
+
+    let mut ret: Vec<crate::rpc::ChangelogEntry> = Vec::new();
+    for line in text.lines() {
+        if let Some(caps) = header.captures(line) {
+            ret.push(crate::rpc::ChangelogEntry {
+                version: caps.get(1).unwrap().as_str().to_string(),
+                date: String::new(),
+                text: String::new(),
+            });
+            continue;
+        }
+
+        let Some(entry) = ret.last_mut() else {
+            continue;
+        };
+        if let Some(caps) = trailer.captures(line) {
+            entry.date = caps.get(1).unwrap().as_str().trim().to_string();
+            entry.text = entry.text.trim_matches('\n').to_string();
+        } else if entry.date.is_empty() {
+            entry.text.push_str(line.strip_prefix("  ").unwrap_or(line));
+            entry.text.push('\n');
+        }
+    }
+
+    ret
+}
+
+/// The directory apt keeps downloaded packages and its binary caches in.
+const APT_CACHE_DIR: &str = "/var/cache/apt";
+
+/// List the files `apt-get clean` would remove.
+///
+/// # Arguments
+/// + `filter` - Keep only the downloaded packages whose file name matches.
+///
+/// # Returns
+/// The downloaded packages that match and, if every package matches, the
+/// binary caches as well.
+fn cached_files(filter: impl Fn(&str) -> bool) -> Vec<std::path::PathBuf> {
+    let dir = std::path::Path::new(APT_CACHE_DIR);
+
+    let mut ret = Vec::new();
+    let mut all = true;
+    for sub in ["archives", "archives/partial"] {
+        let Ok(entries) = std::fs::read_dir(dir.join(sub)) else {
+            continue;
+        };
+        for entry in entries.filter_map(|v| v.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.ends_with(".deb") {
+                continue;
+            }
+            if filter(&name) {
+                ret.push(entry.path());
+            } else {
+                all = false;
+            }
+        }
+    }
+
+    if all {
+        ret.push(dir.join("pkgcache.bin"));
+        ret.push(dir.join("srcpkgcache.bin"));
+    }
+    ret
+}
+
+/// Parse the output of `apt-get -s autoclean`.
+///
+/// Each package that would be removed is listed as `Del name version [size]`.
+///
+/// # Arguments
+/// + `output` - The output of apt-get.
+///
+/// # Returns
+/// The file name prefixes of the downloaded packages, `name_version_`, with
+/// the version escaped the way apt names the files.
+fn parse_simulated_autoclean(output: &str) -> Vec<String> {
+    let re = regex::Regex::new(r"^Del (\S+) (\S+)").unwrap();
+    output
+        .lines()
+        .filter_map(|line| re.captures(line))
+        .map(|caps| deb_prefix(caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str()))
+        .collect()
+}
+
+/// Get the file name prefix apt uses for a downloaded package.
+///
+/// # Arguments
+/// + `name` - The name of package.
+/// + `version` - The version of package.
+///
+/// # Returns
+/// The prefix, `name_version_` with the epoch colon escaped.
+fn deb_prefix(name: &str, version: &str) -> String {
+    format!("{}_{}_", name, version.replace(':', "%3a"))
+}
+
+/// Find a downloaded package in the apt cache.
+///
+/// # Arguments
+/// + `name` - The name of package.
+/// + `version` - The version of package.
+/// + `arch` - The architecture of package, empty for any.
+///
+/// # Returns
+/// The path of the package, `None` if it is not cached.
+fn cached_deb(name: &str, version: &str, arch: &str) -> Option<std::path::PathBuf> {
+    let prefix = deb_prefix(name, version);
+    let dir = std::path::Path::new(APT_CACHE_DIR).join("archives");
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .find(|v| {
+            let name = v.file_name().to_string_lossy().to_string();
+            name.starts_with(&prefix)
+                && name.ends_with(".deb")
+                && (arch.is_empty()
+                    || name.ends_with(&format!("_{}.deb", arch))
+                    || name.ends_with("_all.deb"))
+        })
+        .map(|v| v.path())
+}
+
+/// Check whether a package comes from a security pocket.
+///
+/// # Arguments
+/// + `vendor` - The comma separated suites of the candidate, e.g.
+///   `jammy-updates,jammy-security`.
+///
+/// # Returns
+/// `true` if any of the suites is a `*-security` pocket.
+fn is_security_pocket(vendor: &str) -> bool {
+    vendor.split(',').any(|v| v.ends_with("-security"))
+}
+
+/// Run `apt-mark`.
+///
+/// # Arguments
+/// + `command` - The apt-mark command.
+/// + `pkgs` - The names of packages.
+///
+/// # Returns
+/// The output of apt-mark.
+fn apt_mark(command: &str, pkgs: &[String]) -> anyhow::Result<String> {
+    let apt = std::process::Command::new("apt-mark")
+        .arg(command)
+        .args(pkgs)
+        .output()?;
+    if !apt.status.success() {
+        let output = String::from_utf8_lossy(&apt.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&apt.stdout).to_string())
+}
+
+/// Run an upgrade.
+///
+/// # Arguments
+/// + `args` - The `apt-get` arguments of the upgrade.
+/// + `options` - How to upgrade.
+///
+/// # Returns
+/// The packages left for later.
+fn apt_upgrade(
+    mut args: Vec<String>,
+    options: &crate::rpc::UpgradeOptions,
+) -> anyhow::Result<crate::rpc::UpgradeResult> {
+    let mut ret = crate::rpc::UpgradeResult {
+        pending: Vec::new(),
+    };
+
+    if options.download_only {
+        args.push("--download-only".to_string());
+    }
+    if options.offline {
+        // apt holds back what is not downloaded yet, find out what.
+        let apt = std::process::Command::new("apt-get")
+            .env("LANG", "C")
+            .arg("-s")
+            .args(&args)
+            .output()?;
+        if !apt.status.success() {
+            let output = String::from_utf8_lossy(&apt.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+        let output = String::from_utf8_lossy(&apt.stdout).to_string();
+        let plan = parse_simulated_upgrade(&output);
+        ret.pending = plan
+            .upgrades
+            .iter()
+            .chain(plan.installs.iter())
+            .filter(|x| cached_deb(&x.name, &x.target_version, "").is_none())
+            .map(|x| x.name.clone())
+            .collect();
+
+        args.push("--no-download".to_string());
+        args.push("--ignore-missing".to_string());
+    }
+
+    let apt = std::process::Command::new("apt-get").args(&args).output()?;
+    if !apt.status.success() {
+        let output = String::from_utf8_lossy(&apt.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string(),));
+    }
+    Ok(ret)
+}
+
+/// Build the `apt-get` arguments of an upgrade.
+///
+/// Without named packages, exclusions or a security filter, the configured
+/// strategy upgrades everything. Otherwise the packages are upgraded with
+/// `apt-get install --only-upgrade`, without named packages the ones the
+/// strategy would upgrade, so nothing has to be held meanwhile.
+///
+/// # Arguments
+/// + `backend` - The apt backend.
+/// + `options` - Which packages to upgrade.
+///
+/// # Returns
+/// The arguments, `None` if there is nothing to upgrade.
+fn upgrade_args(
+    backend: &AptBackend,
+    options: &crate::rpc::UpgradeOptions,
+) -> anyhow::Result<Option<Vec<String>>> {
+    use crate::rpc::{AptStrategy, OutdateStatus};
+
+    let extra_args = options.apt.args.iter().chain(options.extra_args.iter());
+    if options.pkgs.is_empty() && options.exclude.is_empty() && !options.security_only {
+        let command = match options.apt.strategy {
+            AptStrategy::Upgrade => "upgrade",
+            AptStrategy::FullUpgrade => "full-upgrade",
+            AptStrategy::DistUpgrade => "dist-upgrade",
+        };
+        let mut args = vec![command.to_string(), "-y".to_string()];
+        // The other commands install new dependencies anyway.
+        if options.apt.with_new_pkgs && options.apt.strategy == AptStrategy::Upgrade {
+            args.push("--with-new-pkgs".to_string());
+        }
+        args.extend(extra_args.cloned());
+        return Ok(Some(args));
+    }
+
+    let mut args = vec![
+        "install".to_string(),
+        "--only-upgrade".to_string(),
+        "-y".to_string(),
+    ];
+    let pkgs = if options.pkgs.is_empty() {
+        // Held and phased updates stay where they are, kept back ones need a
+        // strategy that may install new packages.
+        let plain = options.apt.strategy == AptStrategy::Upgrade;
+        let kept_back = !plain || options.apt.with_new_pkgs;
+        if plain {
+            args.push("--no-remove".to_string());
+        }
+        crate::UpmBackend::outdated(backend)?
+            .pkgs
+            .into_iter()
+            .filter(|x| match x.status {
+                OutdateStatus::Upgradable => true,
+                OutdateStatus::KeptBack => kept_back,
+                _ => false,
+            })
+            .filter(|x| !options.security_only || x.security)
+            .filter(|x| !options.exclude.contains(&x.name))
+            .map(|x| x.name)
+            .collect()
+    } else {
+        crate::upgrade_targets(backend, options)?.unwrap_or_default()
+    };
+    if pkgs.is_empty() {
+        return Ok(None);
+    }
+
+    args.extend(extra_args.cloned());
+    args.extend(pkgs);
+    Ok(Some(args))
+}
+
+/// Find out which outdated packages `apt-get upgrade` would skip, and why.
+///
+/// # Arguments
+/// + `pkgs` - The outdated packages, updated in place.
+fn classify_outdated(pkgs: &mut [crate::rpc::OutdateItem]) -> anyhow::Result<()> {
+    if pkgs.is_empty() {
+        return Ok(());
+    }
+
+    let holds = apt_mark("showhold", &[])?;
+    let holds: std::collections::HashSet<&str> = holds.lines().map(|v| v.trim()).collect();
+
+    let apt = std::process::Command::new("apt-get")
+        .env("LANG", "C")
+        .args(["-s", "upgrade"])
+        .output()?;
+    if !apt.status.success() {
+        let output = String::from_utf8_lossy(&apt.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+    let output = String::from_utf8_lossy(&apt.stdout).to_string();
+    let plan = parse_simulated_upgrade(&output);
+
+    let names: Vec<&str> = pkgs.iter().map(|x| x.name.as_str()).collect();
+    let policy = apt_cache_policy(&names)?;
+
+    for item in pkgs.iter_mut() {
+        if holds.contains(item.name.as_str()) {
+            item.status = crate::rpc::OutdateStatus::Held;
+            continue;
+        }
+        if !plan.held_back.iter().any(|x| x.name == item.name) {
+            continue;
+        }
+
+        let phased = policy
+            .get(&item.name)
+            .and_then(|x| x.version(&item.target_version))
+            .and_then(|x| x.phased);
+        item.status = match phased {
+            Some(percentage) => crate::rpc::OutdateStatus::Phased { percentage },
+            None => crate::rpc::OutdateStatus::KeptBack,
+        };
+    }
+
+    Ok(())
+}
+
+/// Parse the output of `apt-get -s upgrade`.
+///
+/// `Inst` lines with an installed version are upgrades, without one they are
+/// new installs. Held back packages, including updates deferred due to
+/// phasing, are listed after a header line.
+///
+/// # Arguments
+/// + `output` - The output of the simulation.
+///
+/// # Returns
+/// The planned transaction.
+fn parse_simulated_upgrade(output: &str) -> crate::rpc::SimulateResult {
+    let inst_re = regex::Regex::new(r"^Inst (\S+) (?:\[(\S+)\] )?\((\S+)").unwrap();
+
+    let mut ret = crate::rpc::SimulateResult::default();
+    let mut in_held = false;
+    for line in output.lines() {
+        if line.starts_with("The following packages have been kept back")
+            || line.starts_with("The following upgrades have been deferred due to phasing")
+        {
+            in_held = true;
+            continue;
+        }
+        if in_held {
+            if line.starts_with(' ') {
+                for name in line.split_whitespace() {
+                    ret.held_back.push(crate::rpc::PlanItem {
+                        name: name.to_string(),
+                        current_version: String::new(),
+                        target_version: String::new(),
+                    });
+                }
+                continue;
+            }
+            in_held = false;
+        }
+
+        if let Some(caps) = inst_re.captures(line) {
+            let item = crate::rpc::PlanItem {
+                name: caps.get(1).unwrap().as_str().to_string(),
+                current_version: caps.get(2).map_or("", |v| v.as_str()).to_string(),
+                target_version: caps.get(3).unwrap().as_str().to_string(),
+            };
+            if item.current_version.is_empty() {
+                ret.installs.push(item);
+            } else {
+                ret.upgrades.push(item);
+            }
+        }
+    }
+
+    for item in parse_simulated_removals(output) {
+        ret.removals.push(crate::rpc::PlanItem {
+            name: item.name,
+            current_version: item.version,
+            target_version: String::new(),
+        });
+    }
+
+    ret
+}
+
+/// The versions of a package as reported by `apt-cache policy`.
+#[derive(Debug, Default)]
+struct AptPolicy {
+    installed: Option<String>,
+    candidate: Option<String>,
+    versions: Vec<AptPolicyVersion>,
+}
+
+/// An entry of the version table of `apt-cache policy`.
+#[derive(Debug, Default)]
+struct AptPolicyVersion {
+    version: String,
+    /// The rollout percentage if this version is a phased update.
+    phased: Option<u8>,
+    /// The `suite/component` of each repository providing this version, or
+    /// `now` for the dpkg status file.
+    origins: Vec<String>,
+}
+
+impl AptPolicy {
+    /// Get an entry of the version table.
+    fn version(&self, version: &str) -> Option<&AptPolicyVersion> {
+        self.versions.iter().find(|x| x.version == version)
+    }
+}
+
+/// Query the installed and candidate versions of packages.
+///
+/// # Arguments
+/// + `names` - The names of packages.
+///
+/// # Returns
+/// The versions of each known package.
+fn apt_cache_policy(
+    names: &[&str],
+) -> anyhow::Result<std::collections::HashMap<String, AptPolicy>> {
+    let apt = std::process::Command::new("apt-cache")
+        .env("LANG", "C")
+        .arg("policy")
+        .args(names)
+        .output()?;
+    if !apt.status.success() {
+        let output = String::from_utf8_lossy(&apt.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    let output = String::from_utf8_lossy(&apt.stdout).to_string();
+    Ok(parse_policy(&output))
+}
+
+/// Parse the output of `apt-cache policy`.
+///
+/// # Arguments
+/// + `output` - The output of `apt-cache policy`.
+///
+/// # Returns
+/// The versions of each package, `(none)` is mapped to `None`.
+fn parse_policy(output: &str) -> std::collections::HashMap<String, AptPolicy> {
+    let version_re =
+        regex::Regex::new(r"^ (?:\*\*\*|   ) (\S+) -?\d+(?: \(phased (\d+)%\))?").unwrap();
+    let origin_re = regex::Regex::new(r"^ {8}-?\d+ (\S+)(?: (\S+) \S+ Packages)?$").unwrap();
+
+    let mut ret = std::collections::HashMap::new();
+    let mut current: Option<String> = None;
+    for line in output.lines() {
+        if !line.starts_with(' ') {
+            current = line.strip_suffix(':').map(|v| v.to_string());
+            if let Some(name) = &current {
+                ret.insert(name.clone(), AptPolicy::default());
+            }
+            continue;
+        }
+
+        let Some(name) = &current else {
+            continue;
+        };
+        let policy: &mut AptPolicy = ret.get_mut(name).unwrap();
+
+        if let Some(caps) = origin_re.captures(line) {
+            let origin = match caps.get(2) {
+                Some(v) => v.as_str(),
+                None => "now",
+            };
+            if let Some(version) = policy.versions.last_mut() {
+                version.origins.push(origin.to_string());
+            }
+            continue;
+        }
+        if let Some(caps) = version_re.captures(line) {
+            policy.versions.push(AptPolicyVersion {
+                version: caps.get(1).unwrap().as_str().to_string(),
+                phased: caps.get(2).and_then(|v| v.as_str().parse().ok()),
+                origins: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = Some(value.trim().to_string()).filter(|v| v != "(none)");
+        match key {
+            "Installed" => policy.installed = value,
+            "Candidate" => policy.candidate = value,
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+/// Parse a deb822 control stanza such as the output of `apt-cache show`.
+///
+/// Continuation lines are joined to their field with a newline, a lone `.`
+/// stands for an empty line.
+///
+/// # Arguments
+/// + `stanza` - The text of one stanza.
+///
+/// # Returns
+/// The fields in order of appearance.
+fn parse_control(stanza: &str) -> Vec<(String, String)> {
+    let mut ret: Vec<(String, String)> = Vec::new();
+    for line in stanza.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = ret.last_mut() {
+                let line = line.trim();
+                value.push('\n');
+                value.push_str(if line == "." { "" } else { line });
+            }
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            ret.push((key.to_string(), value.trim().to_string()));
+        }
+    }
+
+    ret
+}
+
+/// Get the first license named in a machine readable copyright file.
+///
+/// # Arguments
+/// + `name` - The name of package.
+///
+/// # Returns
+/// The license, `None` if unknown.
+fn copyright_license(name: &str) -> Option<String> {
+    let path = format!("/usr/share/doc/{}/copyright", name);
+    let data = std::fs::read_to_string(path).ok()?;
+    data.lines()
+        .find_map(|v| v.strip_prefix("License:"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse the `Remv`/`Purg` lines of `apt-get -s` output.
+///
+/// # Arguments
+/// + `output` - The output of the simulation.
+///
+/// # Returns
+/// The packages that would be removed.
+fn parse_simulated_removals(output: &str) -> Vec<crate::rpc::RemoveItem> {
+    let re = regex::Regex::new(r"^(?:Remv|Purg) (\S+) \[(\S+)\]").unwrap();
+
+    let mut ret = Vec::new();
+    for line in output.lines() {
+        let Some(caps) = re.captures(line) else {
+            continue;
+        };
+
+        ret.push(crate::rpc::RemoveItem {
+            name: caps.get(1).unwrap().as_str().to_string(),
+            version: caps.get(2).unwrap().as_str().to_string(),
+        });
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(items: &[crate::rpc::PlanItem]) -> Vec<(&str, &str, &str)> {
+        items
+            .iter()
+            .map(|x| {
+                (
+                    x.name.as_str(),
+                    x.current_version.as_str(),
+                    x.target_version.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn repository(url: &str, suites: &[&str], components: &[&str]) -> crate::rpc::Repository {
+        crate::rpc::Repository {
+            name: "example".to_string(),
+            url: url.to_string(),
+            enabled: true,
+            backend: "apt".to_string(),
+            suites: suites.iter().map(|v| v.to_string()).collect(),
+            components: components.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_dpkg_search() {
+        let output = "\
+diversion by dash from: /bin/sh
+diversion by dash to: /bin/sh.distrib
+dash: /bin/sh
+bash, dash: /usr/share/man/man1
+";
+        assert_eq!(parse_dpkg_search(output), ["dash", "bash"]);
+    }
+
+    #[test]
+    fn test_parse_apt_depends() {
+        use crate::rpc::DependencyKind::{Depends, Recommends};
+
+        let output = "\
+curl
+  Depends: libc6
+  PreDepends: libcurl4
+ |Depends: <awk>
+    mawk
+  Depends: gawk
+  Recommends: ca-certificates
+  Breaks: curl-old
+libcurl4
+  Depends: libc6
+<awk>
+libc6
+";
+        let deps = parse_apt_depends(output);
+        assert_eq!(
+            deps["curl"],
+            [
+                ("libc6".to_string(), Some(Depends)),
+                ("libcurl4".to_string(), Some(Depends)),
+                ("awk".to_string(), Some(Depends)),
+                ("ca-certificates".to_string(), Some(Recommends)),
+            ]
+        );
+        assert_eq!(deps["libcurl4"], [("libc6".to_string(), Some(Depends))]);
+        assert!(deps["awk"].is_empty());
+        assert!(deps["libc6"].is_empty());
+
+        let output = "\
+libcurl4
+Reverse Depends:
+  curl
+ |libcurl4-openssl-dev
+  libcurl4-gnutls-dev
+  curl
+";
+        assert_eq!(
+            parse_apt_depends(output)["libcurl4"],
+            [
+                ("curl".to_string(), None),
+                ("libcurl4-openssl-dev".to_string(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_source_values() {
+        let ok = repository("https://example.com/apt", &["stable"], &["main"]);
+        assert!(check_source_values(&ok).is_ok());
+        let ok = repository("tor+https://example.onion/apt", &["./"], &[]);
+        assert!(check_source_values(&ok).is_ok());
+
+        for repo in [
+            repository("javascript:alert(1)", &["stable"], &["main"]),
+            repository("mirror+file:///etc/apt/mirrors.txt", &["stable"], &["main"]),
+            repository("example.com/apt", &["stable"], &["main"]),
+            repository(
+                "https://example.com/apt",
+                &["stable\nTrusted: yes"],
+                &["main"],
+            ),
+            repository("https://example.com/apt\n", &["stable"], &["main"]),
+            repository("https://example.com/apt", &["stable"], &["main contrib"]),
+            repository("https://example.com/apt", &[""], &["main"]),
+        ] {
+            assert!(check_source_values(&repo).is_err(), "{:?}", repo);
+        }
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let text = "\
+# Added by the installer.
+Types: deb
+URIs: http://deb.debian.org/debian
+Suites: bookworm bookworm-updates
+Components: main
+Signed-By:
+ -----BEGIN PGP PUBLIC KEY BLOCK-----
+ .
+ mQINBGPL
+ -----END PGP PUBLIC KEY BLOCK-----
+
+# Disabled.
+types: deb
+uris: https://example.com/apt
+suites: stable
+enabled: no
+";
+        let stanzas = parse_sources(text);
+        assert_eq!(stanzas.len(), 2);
+        assert_eq!(
+            control_field(&stanzas[0], "Suites"),
+            Some("bookworm bookworm-updates")
+        );
+        assert_eq!(
+            control_field(&stanzas[0], "Signed-By"),
+            Some(
+                "\n-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nmQINBGPL\n-----END PGP PUBLIC KEY BLOCK-----"
+            )
+        );
+        assert_eq!(
+            control_field(&stanzas[1], "URIs"),
+            Some("https://example.com/apt")
+        );
+        assert_eq!(control_field(&stanzas[1], "Enabled"), Some("no"));
+        assert_eq!(control_field(&stanzas[1], "Components"), None);
+    }
+
+    #[test]
+    fn test_parse_one_line_sources() {
+        let text = "\
+# See sources.list(5).
+deb http://deb.debian.org/debian bookworm main contrib # non-free
+deb [arch=amd64 signed-by=/etc/apt/keyrings/example.gpg] https://example.com/apt stable main
+  deb-src http://deb.debian.org/debian bookworm main
+# deb http://deb.debian.org/debian bookworm-backports main
+deb file:/srv/repo ./
+";
+        assert_eq!(
+            parse_one_line_sources(text),
+            [
+                (
+                    "http://deb.debian.org/debian".to_string(),
+                    "bookworm".to_string(),
+                    vec!["main".to_string(), "contrib".to_string()]
+                ),
+                (
+                    "https://example.com/apt".to_string(),
+                    "stable".to_string(),
+                    vec!["main".to_string()]
+                ),
+                (
+                    "http://deb.debian.org/debian".to_string(),
+                    "bookworm".to_string(),
+                    vec!["main".to_string()]
+                ),
+                ("file:/srv/repo".to_string(), "./".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_sources_enabled() {
+        let text = "\
+# Debian
+Types: deb
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+
+Types: deb-src
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+Enabled: yes
+";
+        let disabled = "\
+# Debian
+Types: deb
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+Enabled: no
+
+Types: deb-src
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+Enabled: no
+";
+        let enabled = "\
+# Debian
+Types: deb
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+
+Types: deb-src
+URIs: http://deb.debian.org/debian
+Suites: bookworm
+Components: main
+";
+        assert_eq!(set_sources_enabled(text, false), disabled);
+        assert_eq!(set_sources_enabled(disabled, true), enabled);
+        assert_eq!(set_sources_enabled(disabled, false), disabled);
+        // A stanza made only of comments stays as it is.
+        assert_eq!(set_sources_enabled("# Empty\n", false), "# Empty\n");
+    }
+
+    #[test]
+    fn test_parse_debian_changelog() {
+        let text = "\
+curl (7.88.1-10+deb12u8) bookworm-security; urgency=medium
+
+  * Fix CVE-2024-9681.
+  * Fix CVE-2024-11053.
+
+ -- Samuel Henrique <samueloph@debian.org>  Sat, 30 Nov 2024 20:38:37 +0000
+
+curl (7.88.1-10+deb12u7) bookworm; urgency=medium
+
+  * Fix CVE-2024-7264.
+
+ -- Samuel Henrique <samueloph@debian.org>  Tue, 17 Sep 2024 22:05:12 +0100
+";
+        let entries = parse_debian_changelog(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].version, "7.88.1-10+deb12u8");
+        assert_eq!(entries[0].date, "Sat, 30 Nov 2024 20:38:37 +0000");
+        assert_eq!(
+            entries[0].text,
+            "* Fix CVE-2024-9681.\n* Fix CVE-2024-11053."
+        );
+        assert_eq!(entries[1].version, "7.88.1-10+deb12u7");
+        assert_eq!(entries[1].date, "Tue, 17 Sep 2024 22:05:12 +0100");
+        assert_eq!(entries[1].text, "* Fix CVE-2024-7264.");
+    }
+
+    #[test]
+    fn test_parse_simulated_autoclean() {
+        let output = "\
+Reading package lists... Done
+Building dependency tree... Done
+Del libssl3 3.0.11-1~deb12u2 [2,019 kB]
+Del python3 1:3.11.2-1+b1 [26.3 kB]
+";
+        assert_eq!(
+            parse_simulated_autoclean(output),
+            ["libssl3_3.0.11-1~deb12u2_", "python3_1%3a3.11.2-1+b1_"]
+        );
+    }
+
+    #[test]
+    fn test_is_security_pocket() {
+        assert!(is_security_pocket("jammy-updates,jammy-security"));
+        assert!(is_security_pocket("bookworm-security"));
+        assert!(!is_security_pocket("jammy-updates"));
+        assert!(!is_security_pocket(""));
+    }
+
+    #[test]
+    fn test_parse_simulated_upgrade() {
+        let output = "\
+Reading package lists...
+Building dependency tree...
+Calculating upgrade...
+The following packages have been kept back:
+  linux-image-amd64 openssl
+The following upgrades have been deferred due to phasing:
+  systemd
+The following packages will be upgraded:
+  curl libcurl4
+2 upgraded, 1 newly installed, 1 to remove and 3 not upgraded.
+Remv libfoo1 [1.0-1]
+Inst libcurl4 [7.88.1-10+deb12u5] (7.88.1-10+deb12u8 Debian-Security:12/stable-security [amd64])
+Inst curl [7.88.1-10+deb12u5] (7.88.1-10+deb12u8 Debian-Security:12/stable-security [amd64])
+Inst libfoo2 (2.0-1 Debian:12.5/stable [amd64])
+Conf libcurl4 (7.88.1-10+deb12u8 Debian-Security:12/stable-security [amd64])
+Conf curl (7.88.1-10+deb12u8 Debian-Security:12/stable-security [amd64])
+";
+        let ret = parse_simulated_upgrade(output);
+        assert_eq!(
+            plan(&ret.upgrades),
+            [
+                ("libcurl4", "7.88.1-10+deb12u5", "7.88.1-10+deb12u8"),
+                ("curl", "7.88.1-10+deb12u5", "7.88.1-10+deb12u8"),
+            ]
+        );
+        assert_eq!(plan(&ret.installs), [("libfoo2", "", "2.0-1")]);
+        assert_eq!(plan(&ret.removals), [("libfoo1", "1.0-1", "")]);
+        assert_eq!(
+            plan(&ret.held_back),
+            [
+                ("linux-image-amd64", "", ""),
+                ("openssl", "", ""),
+                ("systemd", "", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let output = "\
+curl:
+  Installed: 7.88.1-10+deb12u5
+  Candidate: 7.88.1-10+deb12u8
+  Version table:
+     7.88.1-10+deb12u8 500
+        500 http://security.debian.org/debian-security bookworm-security/main amd64 Packages
+ *** 7.88.1-10+deb12u5 100
+        100 /var/lib/dpkg/status
+nano:
+  Installed: (none)
+  Candidate: 7.2-1+deb12u1
+  Version table:
+     7.2-1+deb12u1 500 (phased 40%)
+        500 http://deb.debian.org/debian bookworm-updates/main amd64 Packages
+        500 http://deb.debian.org/debian bookworm/main amd64 Packages
+";
+        let policy = parse_policy(output);
+
+        let curl = &policy["curl"];
+        assert_eq!(curl.installed.as_deref(), Some("7.88.1-10+deb12u5"));
+        assert_eq!(curl.candidate.as_deref(), Some("7.88.1-10+deb12u8"));
+        assert_eq!(
+            curl.version("7.88.1-10+deb12u8").unwrap().origins,
+            ["bookworm-security/main"]
+        );
+        assert_eq!(curl.version("7.88.1-10+deb12u5").unwrap().origins, ["now"]);
+        assert_eq!(curl.version("7.88.1-10+deb12u8").unwrap().phased, None);
+
+        let nano = &policy["nano"];
+        assert_eq!(nano.installed, None);
+        let version = nano.version("7.2-1+deb12u1").unwrap();
+        assert_eq!(version.phased, Some(40));
+        assert_eq!(version.origins, ["bookworm-updates/main", "bookworm/main"]);
+    }
+
+    #[test]
+    fn test_parse_control() {
+        let stanza = "\
+Package: curl
+Version: 7.88.1-10+deb12u8
+Description: command line tool for transferring data with URL syntax
+ curl is a command line tool.
+ .
+ It supports many protocols.
+";
+        assert_eq!(
+            parse_control(stanza),
+            [
+                ("Package".to_string(), "curl".to_string()),
+                ("Version".to_string(), "7.88.1-10+deb12u8".to_string()),
+                (
+                    "Description".to_string(),
+                    "command line tool for transferring data with URL syntax\n\
+                     curl is a command line tool.\n\nIt supports many protocols."
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_simulated_removals() {
+        let output = "\
+The following packages will be REMOVED:
+  curl* libcurl4
+Remv curl [7.88.1-10+deb12u5]
+Purg libcurl4 [7.88.1-10+deb12u5]
+Remv broken
+";
+        let ret: Vec<(String, String)> = parse_simulated_removals(output)
+            .into_iter()
+            .map(|x| (x.name, x.version))
+            .collect();
+        assert_eq!(
+            ret,
+            [
+                ("curl".to_string(), "7.88.1-10+deb12u5".to_string()),
+                ("libcurl4".to_string(), "7.88.1-10+deb12u5".to_string()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,943 @@
+/// The dpkg status file.
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+
+/// The directory apt downloads the package indices to.
+const APT_LISTS_DIR: &str = "/var/lib/apt/lists";
+
+/// The main preferences file of apt.
+const APT_PREFERENCES: &str = "/etc/apt/preferences";
+
+/// The directory of additional preferences files.
+const APT_PREFERENCES_DIR: &str = "/etc/apt/preferences.d";
+
+/// The helper that decompresses indices with whatever apt supports.
+const APT_HELPER: &str = "/usr/lib/apt/apt-helper";
+
+/// A package dpkg has installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct InstalledPackage {
+    pub(super) name: String,
+    pub(super) arch: String,
+    pub(super) version: String,
+}
+
+/// A package version listed in a `Packages` index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AvailablePackage {
+    pub(super) name: String,
+    pub(super) arch: String,
+    pub(super) version: String,
+}
+
+/// The archive a `Packages` index belongs to, described by its `Release`
+/// file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Archive {
+    /// The host the archive is downloaded from, for `Pin: origin`.
+    pub(super) host: String,
+    /// The component of the index, e.g. `main`.
+    pub(super) component: String,
+    pub(super) origin: String,
+    pub(super) label: String,
+    pub(super) suite: String,
+    pub(super) codename: String,
+    pub(super) version: String,
+    /// Versions are only installed on request.
+    pub(super) not_automatic: bool,
+    /// Installed versions are still upgraded from a `not_automatic` archive.
+    pub(super) but_automatic_upgrades: bool,
+}
+
+impl Archive {
+    /// The priority of the versions of the archive without pinning.
+    ///
+    /// # Returns
+    /// 1 for `NotAutomatic` archives, 100 if they also have
+    /// `ButAutomaticUpgrades`, otherwise 500.
+    pub(super) fn default_priority(&self) -> i32 {
+        match (self.not_automatic, self.but_automatic_upgrades) {
+            (true, true) => 100,
+            (true, false) => 1,
+            _ => 500,
+        }
+    }
+
+    /// The name apt shows for the archive, e.g. `bookworm-security`.
+    pub(super) fn name(&self) -> &str {
+        if self.suite.is_empty() {
+            &self.codename
+        } else {
+            &self.suite
+        }
+    }
+}
+
+/// A pattern of the preferences, compiled when the preferences are read.
+#[derive(Debug, Clone)]
+pub(super) enum Pattern {
+    /// A value compared as it is.
+    Literal(String),
+    /// A glob with `*` and `?` or a `/regex/`, and the text it was read from.
+    Regex(String, regex::Regex),
+}
+
+impl Pattern {
+    /// Compile a pattern.
+    ///
+    /// # Arguments
+    /// + `pattern` - A literal, a glob with `*` and `?`, or `/regex/`.
+    ///
+    /// # Returns
+    /// The pattern. An invalid regex is kept as a literal, which matches no
+    /// package.
+    pub(super) fn new(pattern: &str) -> Self {
+        let re = match pattern
+            .strip_prefix('/')
+            .and_then(|v| v.strip_suffix('/'))
+            .filter(|v| !v.is_empty())
+        {
+            Some(v) => v.to_string(),
+            None if pattern.contains(['*', '?']) => {
+                let re = regex::escape(pattern)
+                    .replace(r"\*", ".*")
+                    .replace(r"\?", ".");
+                format!("^{}$", re)
+            }
+            None => return Pattern::Literal(pattern.to_string()),
+        };
+        match regex::Regex::new(&re) {
+            Ok(v) => Pattern::Regex(pattern.to_string(), v),
+            Err(_) => Pattern::Literal(pattern.to_string()),
+        }
+    }
+
+    /// The text the pattern was read from.
+    fn as_str(&self) -> &str {
+        match self {
+            Pattern::Literal(v) | Pattern::Regex(v, _) => v,
+        }
+    }
+
+    /// Check whether a value matches the pattern.
+    ///
+    /// # Arguments
+    /// + `value` - The value.
+    ///
+    /// # Returns
+    /// `true` if the value matches.
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Literal(v) => v == value,
+            Pattern::Regex(_, re) => re.is_match(value),
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+/// What a pin of the preferences applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum PinTarget {
+    /// `Pin: release a=...,n=...`, the fields of the `Release` file.
+    Release(Vec<(String, Pattern)>),
+    /// `Pin: origin host`, the host of the archive, `""` for the dpkg status.
+    Origin(Pattern),
+    /// `Pin: version 1.2*`.
+    Version(Pattern),
+}
+
+/// A record of `apt_preferences(5)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Pin {
+    /// The package names, globs or `/regex/`, `*` for every package.
+    pub(super) packages: Vec<Pattern>,
+    pub(super) target: PinTarget,
+    pub(super) priority: i32,
+}
+
+impl Pin {
+    /// Whether the pin applies to every package, apt prefers specific pins.
+    fn is_general(&self) -> bool {
+        self.packages.iter().all(|v| v.as_str() == "*")
+    }
+
+    /// Check whether the pin applies to a package version.
+    ///
+    /// # Arguments
+    /// + `name` - The name of package.
+    /// + `version` - The version.
+    /// + `archive` - The archive providing it, `None` for the dpkg status.
+    ///
+    /// # Returns
+    /// `true` if the pin applies.
+    fn matches(&self, name: &str, version: &str, archive: Option<&Archive>) -> bool {
+        if !self.packages.iter().any(|v| v.matches(name)) {
+            return false;
+        }
+
+        match &self.target {
+            PinTarget::Version(v) => v.matches(version),
+            PinTarget::Origin(v) => match archive {
+                Some(archive) => v.matches(&archive.host),
+                None => v.as_str().is_empty(),
+            },
+            PinTarget::Release(fields) => {
+                let Some(archive) = archive else {
+                    return false;
+                };
+                fields.iter().all(|(k, v)| {
+                    let value = match k.as_str() {
+                        "a" => &archive.suite,
+                        "n" => &archive.codename,
+                        "o" => &archive.origin,
+                        "l" => &archive.label,
+                        "c" => &archive.component,
+                        "v" => &archive.version,
+                        _ => return false,
+                    };
+                    v.matches(value)
+                })
+            }
+        }
+    }
+}
+
+/// The upgrade apt would pick for an installed package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Upgrade {
+    pub(super) name: String,
+    pub(super) arch: String,
+    pub(super) current_version: String,
+    pub(super) candidate_version: String,
+    /// The names of the archives providing the candidate.
+    pub(super) archives: Vec<String>,
+}
+
+/// Split deb822 text into the fields of its stanzas.
+///
+/// Only the first line of multi-line fields is kept, the fields read here
+/// are all single-line.
+///
+/// # Arguments
+/// + `text` - The text.
+///
+/// # Returns
+/// The fields of each stanza, in order of appearance.
+fn stanzas(text: &str) -> impl Iterator<Item = Vec<(&str, &str)>> {
+    text.split("\n\n").filter_map(|stanza| {
+        let fields: Vec<(&str, &str)> = stanza
+            .lines()
+            .filter(|v| !v.starts_with([' ', '\t', '#']))
+            .filter_map(|v| v.split_once(':'))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        Some(fields).filter(|v| !v.is_empty())
+    })
+}
+
+/// Get a field of a stanza, case-insensitive like apt.
+fn field<'a>(fields: &[(&str, &'a str)], key: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| *v)
+}
+
+/// Parse the dpkg status file.
+///
+/// # Arguments
+/// + `text` - The content of `/var/lib/dpkg/status`.
+///
+/// # Returns
+/// The packages that are fully installed.
+pub(super) fn parse_status(text: &str) -> Vec<InstalledPackage> {
+    stanzas(text)
+        .filter_map(|fields| {
+            // `Status: want flag status`, e.g. `hold ok installed`.
+            let status = field(&fields, "Status")?;
+            if status.split_whitespace().nth(2) != Some("installed") {
+                return None;
+            }
+            Some(InstalledPackage {
+                name: field(&fields, "Package")?.to_string(),
+                arch: field(&fields, "Architecture")?.to_string(),
+                version: field(&fields, "Version")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a `Packages` index.
+///
+/// # Arguments
+/// + `text` - The content of the index.
+///
+/// # Returns
+/// The package versions it lists.
+pub(super) fn parse_packages(text: &str) -> Vec<AvailablePackage> {
+    stanzas(text)
+        .filter_map(|fields| {
+            Some(AvailablePackage {
+                name: field(&fields, "Package")?.to_string(),
+                arch: field(&fields, "Architecture")?.to_string(),
+                version: field(&fields, "Version")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a `Release` or clear-signed `InRelease` file.
+///
+/// # Arguments
+/// + `text` - The content of the file.
+/// + `host` - The host the archive is downloaded from.
+/// + `component` - The component of the index.
+///
+/// # Returns
+/// The archive.
+pub(super) fn parse_release(text: &str, host: &str, component: &str) -> Archive {
+    // The signed message starts after the armor header and its blank line.
+    let text = match text.strip_prefix("-----BEGIN PGP SIGNED MESSAGE-----") {
+        Some(v) => v.split_once("\n\n").map_or("", |(_, v)| v),
+        None => text,
+    };
+    let text = text
+        .split("-----BEGIN PGP SIGNATURE-----")
+        .next()
+        .unwrap_or_default();
+
+    let fields = stanzas(text).next().unwrap_or_default();
+    let get = |key: &str| field(&fields, key).unwrap_or_default().to_string();
+    let yes = |key: &str| field(&fields, key).is_some_and(|v| v.eq_ignore_ascii_case("yes"));
+    Archive {
+        host: host.to_string(),
+        component: component.to_string(),
+        origin: get("Origin"),
+        label: get("Label"),
+        suite: get("Suite"),
+        codename: get("Codename"),
+        version: get("Version"),
+        not_automatic: yes("NotAutomatic"),
+        but_automatic_upgrades: yes("ButAutomaticUpgrades"),
+    }
+}
+
+/// Parse an `apt_preferences(5)` file.
+///
+/// Records without a valid `Pin` or `Pin-Priority` are skipped, like apt
+/// does with a warning.
+///
+/// # Arguments
+/// + `text` - The content of the file.
+///
+/// # Returns
+/// The pins in order of appearance.
+pub(super) fn parse_preferences(text: &str) -> Vec<Pin> {
+    let mut ret = Vec::new();
+    for fields in stanzas(text) {
+        let (Some(packages), Some(pin), Some(priority)) = (
+            field(&fields, "Package"),
+            field(&fields, "Pin"),
+            field(&fields, "Pin-Priority").and_then(|v| v.parse::<i32>().ok()),
+        ) else {
+            continue;
+        };
+
+        let (kind, value) = pin.split_once(char::is_whitespace).unwrap_or((pin, ""));
+        let value = value.trim();
+        let target = match kind {
+            "version" => PinTarget::Version(Pattern::new(value)),
+            "origin" => PinTarget::Origin(Pattern::new(value.trim_matches('"'))),
+            // A bare value such as `release stable` is the archive.
+            "release" if !value.contains('=') => {
+                PinTarget::Release(vec![("a".to_string(), Pattern::new(value))])
+            }
+            "release" => PinTarget::Release(
+                value
+                    .split(',')
+                    .filter_map(|v| v.split_once('='))
+                    .map(|(k, v)| (k.trim().to_string(), Pattern::new(v.trim())))
+                    .collect(),
+            ),
+            _ => continue,
+        };
+
+        ret.push(Pin {
+            packages: packages.split_whitespace().map(Pattern::new).collect(),
+            target,
+            priority,
+        });
+    }
+    ret
+}
+
+/// Work out the priority of a package version from one source.
+///
+/// Like apt, the first specific pin that matches wins, then the first
+/// general one, then the default priority of the source.
+///
+/// # Arguments
+/// + `pins` - The pins of the preferences.
+/// + `name` - The name of package.
+/// + `version` - The version.
+/// + `archive` - The archive providing it, `None` for the dpkg status.
+///
+/// # Returns
+/// The priority.
+pub(super) fn priority(pins: &[Pin], name: &str, version: &str, archive: Option<&Archive>) -> i32 {
+    let specific = pins
+        .iter()
+        .filter(|x| !x.is_general())
+        .find(|x| x.matches(name, version, archive));
+    let general = || {
+        pins.iter()
+            .filter(|x| x.is_general())
+            .find(|x| x.matches(name, version, archive))
+    };
+    match specific.or_else(general) {
+        Some(pin) => pin.priority,
+        None => archive.map_or(100, |x| x.default_priority()),
+    }
+}
+
+/// Work out the packages apt would upgrade.
+///
+/// The candidate of a package is the version with the highest priority, the
+/// newest one among equals. A version older than the installed one is only a
+/// candidate with a priority of 1000 or more, and versions with a priority
+/// below 1 never are. A version provided by several sources gets the highest
+/// of their priorities.
+///
+/// # Arguments
+/// + `installed` - The installed packages.
+/// + `indices` - The package indices and the archives they belong to.
+/// + `pins` - The pins of the preferences.
+///
+/// # Returns
+/// The packages whose candidate is not the installed version.
+pub(super) fn upgradable(
+    installed: &[InstalledPackage],
+    indices: &[(Archive, Vec<AvailablePackage>)],
+    pins: &[Pin],
+) -> Vec<Upgrade> {
+    use std::cmp::Ordering;
+
+    let mut available: std::collections::HashMap<(&str, &str), Vec<(&str, &Archive)>> =
+        std::collections::HashMap::new();
+    for (archive, pkgs) in indices.iter() {
+        for pkg in pkgs.iter() {
+            available
+                .entry((&pkg.name, &pkg.arch))
+                .or_default()
+                .push((&pkg.version, archive));
+        }
+    }
+
+    let mut ret = Vec::new();
+    for pkg in installed.iter() {
+        let Some(sources) = available.get(&(pkg.name.as_str(), pkg.arch.as_str())) else {
+            continue;
+        };
+
+        let mut versions: Vec<(&str, i32)> = vec![(
+            pkg.version.as_str(),
+            priority(pins, &pkg.name, &pkg.version, None),
+        )];
+        for (version, archive) in sources.iter() {
+            let prio = priority(pins, &pkg.name, version, Some(archive));
+            match versions.iter_mut().find(|(v, _)| v == version) {
+                Some((_, v)) => *v = (*v).max(prio),
+                None => versions.push((*version, prio)),
+            }
+        }
+
+        let candidate = versions
+            .iter()
+            .filter(|(v, prio)| {
+                *v == pkg.version
+                    || (*prio > 0
+                        && (*prio >= 1000
+                            || crate::backend::compare_versions(v, &pkg.version)
+                                == Ordering::Greater))
+            })
+            .max_by(|a, b| {
+                a.1.cmp(&b.1)
+                    .then_with(|| crate::backend::compare_versions(a.0, b.0))
+            });
+        let Some((candidate, _)) = candidate else {
+            continue;
+        };
+        if *candidate == pkg.version {
+            continue;
+        }
+
+        let mut archives: Vec<String> = Vec::new();
+        for (version, archive) in sources.iter() {
+            let name = archive.name().to_string();
+            if version == candidate && !archives.contains(&name) {
+                archives.push(name);
+            }
+        }
+        ret.push(Upgrade {
+            name: pkg.name.clone(),
+            arch: pkg.arch.clone(),
+            current_version: pkg.version.clone(),
+            candidate_version: candidate.to_string(),
+            archives,
+        });
+    }
+
+    ret
+}
+
+/// Find the archive of a `Packages` index in the lists directory.
+///
+/// The index `<uri>_dists_<suite>_<component>_binary-<arch>_Packages` sits
+/// next to `<uri>_dists_<suite>_InRelease` or `_Release`. Flat repositories
+/// have no `dists`, so the longest prefix with a release file wins.
+///
+/// # Arguments
+/// + `dir` - The lists directory.
+/// + `index` - The file name of the index.
+///
+/// # Returns
+/// The release file and the component, `None` if there is no release file.
+fn release_of(dir: &std::path::Path, index: &str) -> Option<(std::path::PathBuf, String)> {
+    let bounds: Vec<usize> = index.match_indices('_').map(|(i, _)| i).collect();
+    for i in bounds.into_iter().rev() {
+        let (prefix, rest) = index.split_at(i);
+        for name in ["InRelease", "Release"] {
+            let path = dir.join(format!("{}_{}", prefix, name));
+            if path.is_file() {
+                let component = rest[1..].split("_binary-").next().unwrap_or_default();
+                return Some((path, component.to_string()));
+            }
+        }
+    }
+    None
+}
+
+/// Read a package index, compressed or not.
+///
+/// # Arguments
+/// + `path` - The path of the index.
+///
+/// # Returns
+/// The content of the index.
+fn read_index(path: &std::path::Path) -> anyhow::Result<String> {
+    if path.extension().is_none() {
+        return Ok(std::fs::read_to_string(path)?);
+    }
+
+    // apt stores indices compressed with `Acquire::GzipIndexes`.
+    let apt = std::process::Command::new(APT_HELPER)
+        .arg("cat-file")
+        .arg(path)
+        .output()?;
+    if !apt.status.success() {
+        let output = String::from_utf8_lossy(&apt.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&apt.stdout).to_string())
+}
+
+/// Load the package indices apt downloaded.
+///
+/// # Arguments
+/// + `wanted` - Only keep the versions of these packages.
+///
+/// # Returns
+/// The indices and the archives they belong to.
+fn load_indices(
+    wanted: &std::collections::HashSet<&str>,
+) -> anyhow::Result<Vec<(Archive, Vec<AvailablePackage>)>> {
+    let dir = std::path::Path::new(APT_LISTS_DIR);
+    let mut names: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|v| v.ok())
+        .map(|v| v.file_name().to_string_lossy().to_string())
+        .filter(|v| v.ends_with("_Packages") || v.contains("_Packages."))
+        .collect();
+    names.sort();
+
+    let mut ret = Vec::new();
+    for name in names {
+        let index = name.split("_Packages").next().unwrap_or_default();
+        let Some((release, component)) = release_of(dir, index) else {
+            log::warn!("no release file for '{}', skipping it.", name);
+            continue;
+        };
+        let host = name.split('_').next().unwrap_or_default();
+        let archive = parse_release(&std::fs::read_to_string(release)?, host, &component);
+
+        let pkgs = parse_packages(&read_index(&dir.join(&name))?)
+            .into_iter()
+            .filter(|x| wanted.contains(x.name.as_str()))
+            .collect();
+        ret.push((archive, pkgs));
+    }
+
+    Ok(ret)
+}
+
+/// Load the pins of the preferences.
+///
+/// `/etc/apt/preferences` comes first, then the files in
+/// `/etc/apt/preferences.d` that apt reads: no extension or `.pref`, made
+/// of letters, digits, `_`, `-` and `.`.
+///
+/// # Returns
+/// The pins in the order apt reads them.
+fn load_pins() -> Vec<Pin> {
+    let mut paths = vec![std::path::PathBuf::from(APT_PREFERENCES)];
+    if let Ok(entries) = std::fs::read_dir(APT_PREFERENCES_DIR) {
+        let mut entries: Vec<std::path::PathBuf> = entries
+            .filter_map(|v| v.ok())
+            .map(|v| v.path())
+            .filter(|v| {
+                let name = v.file_name().unwrap_or_default().to_string_lossy();
+                name.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+                    && v.extension().is_none_or(|v| v == "pref")
+            })
+            .collect();
+        entries.sort();
+        paths.extend(entries);
+    }
+
+    let mut ret = Vec::new();
+    for path in paths {
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            ret.extend(parse_preferences(&text));
+        }
+    }
+    ret
+}
+
+/// Work out the packages apt would upgrade from the files on disk.
+///
+/// `APT::Default-Release` is not taken into account.
+///
+/// # Returns
+/// The upgradable packages.
+pub(super) fn load_upgradable() -> anyhow::Result<Vec<Upgrade>> {
+    let installed = parse_status(&std::fs::read_to_string(DPKG_STATUS)?);
+    let wanted: std::collections::HashSet<&str> =
+        installed.iter().map(|x| x.name.as_str()).collect();
+    let indices = load_indices(&wanted)?;
+
+    Ok(upgradable(&installed, &indices, &load_pins()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "\
+Package: bash
+Status: install ok installed
+Priority: required
+Architecture: amd64
+Version: 5.2.15-2+b2
+Description: GNU Bourne Again SHell
+ Bash is an sh-compatible command language interpreter.
+
+Package: curl
+Status: hold ok installed
+Architecture: amd64
+Version: 7.88.1-10+deb12u5
+
+Package: nano
+Status: deinstall ok config-files
+Architecture: amd64
+Version: 7.2-1
+
+Package: libc6
+Status: install ok half-configured
+Architecture: amd64
+Version: 2.36-9+deb12u4
+";
+
+    const PACKAGES: &str = "\
+Package: bash
+Version: 5.2.15-2+b7
+Architecture: amd64
+Filename: pool/main/b/bash/bash_5.2.15-2+b7_amd64.deb
+
+Package: curl
+Source: curl
+Version: 7.88.1-10+deb12u8
+Architecture: amd64
+Description: command line tool for transferring data with URL syntax
+ curl is a command line tool for transferring data.
+
+Package: broken
+Architecture: amd64
+";
+
+    fn archive(suite: &str) -> Archive {
+        Archive {
+            host: "deb.debian.org".to_string(),
+            component: "main".to_string(),
+            origin: "Debian".to_string(),
+            label: "Debian".to_string(),
+            suite: suite.to_string(),
+            codename: "bookworm".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn installed(name: &str, version: &str) -> InstalledPackage {
+        InstalledPackage {
+            name: name.to_string(),
+            arch: "amd64".to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    fn available(name: &str, version: &str) -> AvailablePackage {
+        AvailablePackage {
+            name: name.to_string(),
+            arch: "amd64".to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    fn candidates(upgrades: Vec<Upgrade>) -> Vec<(String, String)> {
+        upgrades
+            .into_iter()
+            .map(|x| (x.name, x.candidate_version))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            parse_status(STATUS),
+            [
+                installed("bash", "5.2.15-2+b2"),
+                installed("curl", "7.88.1-10+deb12u5")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_packages() {
+        assert_eq!(
+            parse_packages(PACKAGES),
+            [
+                available("bash", "5.2.15-2+b7"),
+                available("curl", "7.88.1-10+deb12u8")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_release() {
+        let text = "\
+-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA512
+
+Origin: Debian Backports
+Label: Debian Backports
+Suite: bookworm-backports
+Codename: bookworm-backports
+NotAutomatic: yes
+ButAutomaticUpgrades: yes
+Components: main contrib
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCgAdFiEE
+-----END PGP SIGNATURE-----
+";
+        let archive = parse_release(text, "deb.debian.org", "main");
+        assert_eq!(archive.origin, "Debian Backports");
+        assert_eq!(archive.name(), "bookworm-backports");
+        assert_eq!(archive.component, "main");
+        assert_eq!(archive.default_priority(), 100);
+
+        let archive = parse_release("Origin: Example\nCodename: stable\n", "example.com", "");
+        assert_eq!(archive.name(), "stable");
+        assert_eq!(archive.default_priority(), 500);
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert!(Pattern::new("curl").matches("curl"));
+        assert!(!Pattern::new("curl").matches("libcurl4"));
+        assert!(Pattern::new("libcurl*").matches("libcurl4"));
+        assert!(Pattern::new("5.?-1").matches("5.2-1"));
+        assert!(!Pattern::new("5.?-1").matches("5.2.1-1"));
+        assert!(Pattern::new("/^lib.*-dev$/").matches("libssl-dev"));
+        assert!(!Pattern::new("/^lib.*-dev$/").matches("libssl3"));
+        assert!(!Pattern::new("/lib(/").matches("lib("));
+        assert_eq!(Pattern::new("*").as_str(), "*");
+    }
+
+    #[test]
+    fn test_parse_preferences() {
+        let text = "\
+# Keep curl on bookworm.
+Package: curl libcurl*
+Pin: release a=bookworm-backports, n=bookworm-backports
+Pin-Priority: 990
+
+Package: *
+Pin: origin \"ppa.example.com\"
+Pin-Priority: -1
+
+Package: bash
+Pin: version 5.2*
+Pin-Priority: 1001
+
+Package: nano
+Pin: release stable
+Pin-Priority: 600
+
+Package: broken
+Pin: release a=stable
+Pin-Priority: high
+";
+        assert_eq!(
+            parse_preferences(text),
+            [
+                Pin {
+                    packages: vec![Pattern::new("curl"), Pattern::new("libcurl*")],
+                    target: PinTarget::Release(vec![
+                        ("a".to_string(), Pattern::new("bookworm-backports")),
+                        ("n".to_string(), Pattern::new("bookworm-backports")),
+                    ]),
+                    priority: 990,
+                },
+                Pin {
+                    packages: vec![Pattern::new("*")],
+                    target: PinTarget::Origin(Pattern::new("ppa.example.com")),
+                    priority: -1,
+                },
+                Pin {
+                    packages: vec![Pattern::new("bash")],
+                    target: PinTarget::Version(Pattern::new("5.2*")),
+                    priority: 1001,
+                },
+                Pin {
+                    packages: vec![Pattern::new("nano")],
+                    target: PinTarget::Release(vec![("a".to_string(), Pattern::new("stable"))]),
+                    priority: 600,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        let stable = archive("stable");
+        let backports = Archive {
+            not_automatic: true,
+            ..archive("stable-backports")
+        };
+        let pins = parse_preferences(
+            "\
+Package: *
+Pin: release a=stable
+Pin-Priority: 200
+
+Package: /^lib.*-dev$/
+Pin: release a=stable
+Pin-Priority: 700
+
+Package: curl
+Pin: origin deb.debian.org
+Pin-Priority: 800
+",
+        );
+
+        // A specific pin wins over a general one, whatever their order.
+        assert_eq!(priority(&pins, "curl", "1.0", Some(&stable)), 800);
+        assert_eq!(priority(&pins, "libssl-dev", "1.0", Some(&stable)), 700);
+        assert_eq!(priority(&pins, "bash", "1.0", Some(&stable)), 200);
+        // Without a matching pin the archive decides.
+        assert_eq!(priority(&pins, "bash", "1.0", Some(&backports)), 1);
+        assert_eq!(priority(&[], "bash", "1.0", Some(&stable)), 500);
+        assert_eq!(priority(&pins, "bash", "1.0", None), 100);
+    }
+
+    #[test]
+    fn test_upgradable() {
+        let installed = parse_status(STATUS);
+        let stable = (
+            archive("stable"),
+            vec![
+                available("bash", "5.2.15-2+b7"),
+                available("curl", "7.88.1-10+deb12u8"),
+            ],
+        );
+        let backports = (
+            Archive {
+                not_automatic: true,
+                but_automatic_upgrades: true,
+                ..archive("stable-backports")
+            },
+            vec![available("curl", "8.5.0-2~bpo12+1")],
+        );
+        let indices = [stable, backports];
+
+        // The newest version of the highest priority.
+        let upgrades = upgradable(&installed, &indices, &[]);
+        assert_eq!(
+            candidates(upgrades.clone()),
+            [
+                ("bash".to_string(), "5.2.15-2+b7".to_string()),
+                ("curl".to_string(), "7.88.1-10+deb12u8".to_string()),
+            ]
+        );
+        assert_eq!(upgrades[0].current_version, "5.2.15-2+b2");
+        assert_eq!(upgrades[0].archives, ["stable"]);
+
+        // A pin can move to another archive.
+        let pins = parse_preferences(
+            "Package: curl\nPin: release a=stable-backports\nPin-Priority: 600\n",
+        );
+        assert_eq!(
+            candidates(upgradable(&installed, &indices, &pins)),
+            [
+                ("bash".to_string(), "5.2.15-2+b7".to_string()),
+                ("curl".to_string(), "8.5.0-2~bpo12+1".to_string()),
+            ]
+        );
+
+        // A priority below 1 is never a candidate.
+        let pins = parse_preferences("Package: bash\nPin: release a=stable\nPin-Priority: -1\n");
+        assert_eq!(
+            candidates(upgradable(&installed, &indices, &pins)),
+            [("curl".to_string(), "7.88.1-10+deb12u8".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_upgradable_downgrade() {
+        let installed = [installed("curl", "8.5.0-2~bpo12+1")];
+        let indices = [(
+            archive("stable"),
+            vec![available("curl", "7.88.1-10+deb12u8")],
+        )];
+
+        // An older version is only a candidate from a priority of 1000.
+        let pins = |priority: i32| {
+            parse_preferences(&format!(
+                "Package: curl\nPin: release a=stable\nPin-Priority: {}\n",
+                priority
+            ))
+        };
+        assert!(upgradable(&installed, &indices, &pins(999)).is_empty());
+        assert_eq!(
+            candidates(upgradable(&installed, &indices, &pins(1000))),
+            [("curl".to_string(), "7.88.1-10+deb12u8".to_string())]
+        );
+    }
+}
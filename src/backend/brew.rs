@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct BrewBackend {}
 
 impl BrewBackend {
@@ -12,7 +12,7 @@ impl crate::UpmBackend for BrewBackend {
         let child = std::process::Command::new("brew")
             .arg("--version")
             .output()?;
-        if child.status.success() == false {
+        if !child.status.success() {
             return Err(anyhow::anyhow!("brew is not found."));
         }
 
@@ -20,13 +20,32 @@ impl crate::UpmBackend for BrewBackend {
             update: false,
             outdated: false,
             upgrade: false,
+            install: false,
+            uninstall: false,
+            resolve: false,
+            search: false,
+            info: false,
+            list_installed: false,
+            simulate: false,
+            hold: false,
+            holds: false,
+            clean: false,
+            orphans: false,
+            rollback: false,
+            changelog: false,
+            repos: false,
+            repo_edit: false,
+            deps: false,
+            owns: false,
+            files: false,
+            verify: false,
         });
         Ok(setup)
     }
 
     fn update(&self) -> anyhow::Result<()> {
         let brew = std::process::Command::new("brew").arg("update").output()?;
-        if brew.status.success() == false {
+        if !brew.status.success() {
             let output = String::from_utf8_lossy(&brew.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
@@ -37,45 +56,1576 @@ impl crate::UpmBackend for BrewBackend {
     fn outdated(&self) -> anyhow::Result<crate::rpc::OutdatedResult> {
         let brew = std::process::Command::new("brew")
             .env("HOMEBREW_NO_ENV_HINTS", "1")
-            .args(&["outdated", "--json=v2"])
+            .args(["outdated", "--json=v2"])
             .output()?;
-        if brew.status.success() == false {
+        if !brew.status.success() {
             let output = String::from_utf8_lossy(&brew.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
 
         let output = String::from_utf8_lossy(&brew.stdout).to_string();
-        let output: serde_json::Value = serde_json::from_str(&output)?;
-        let formulae = &output["formulae"];
+        parse_outdated(&output)
+    }
 
-        let mut ret = crate::rpc::OutdatedResult { pkgs: Vec::new() };
-        for item in formulae.as_array().unwrap() {
-            let name = item["name"].as_str().unwrap();
-            let current_version = item["installed_versions"][0].as_str().unwrap();
-            let target_version = item["current_version"].as_str().unwrap();
+    fn upgrade(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::UpgradeResult> {
+        let mut ret = crate::rpc::UpgradeResult {
+            pending: Vec::new(),
+        };
+        let targets = crate::upgrade_targets(self, options)?;
+        if targets.as_ref().is_some_and(|v| v.is_empty()) {
+            return Ok(ret);
+        }
 
-            let item = crate::rpc::OutdateItem {
-                name: name.to_string(),
-                vendor: "formulae".to_string(),
-                current_version: current_version.to_string(),
-                target_version: target_version.to_string(),
+        // `brew fetch` and the cache lookup need the names.
+        let mut cmd = std::process::Command::new("brew");
+        if options.download_only || options.offline {
+            let pkgs = match targets {
+                Some(v) => v,
+                None => self.outdated()?.pkgs.into_iter().map(|x| x.name).collect(),
             };
+            if options.download_only {
+                brew_run(&["fetch"], &pkgs)?;
+                return Ok(ret);
+            }
+
+            let (cached, pending): (Vec<String>, Vec<String>) =
+                pkgs.into_iter().partition(|x| brew_cached(x));
+            ret.pending = pending;
+            if cached.is_empty() {
+                return Ok(ret);
+            }
+            cmd.env("HOMEBREW_NO_AUTO_UPDATE", "1")
+                .arg("upgrade")
+                .args(upgrade_flags(options))
+                .args(cached);
+        } else {
+            cmd.arg("upgrade")
+                .args(upgrade_flags(options))
+                .args(targets.unwrap_or_default());
+        }
 
-            ret.pkgs.push(item);
+        let brew = cmd.output()?;
+        if !brew.status.success() {
+            let output = String::from_utf8_lossy(&brew.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
         }
 
         Ok(ret)
     }
 
-    fn upgrade(&self) -> anyhow::Result<()> {
+    fn install(&self, pkgs: &[crate::rpc::PackageSpec]) -> anyhow::Result<()> {
+        let mut args = vec!["install".to_string()];
+        for pkg in pkgs {
+            // Homebrew only knows versioned formulae such as `python@3.12`.
+            if pkg.version.is_some() {
+                return Err(anyhow::anyhow!(
+                    "brew does not support version pinning for '{}'.",
+                    pkg.name
+                ));
+            }
+            args.push(pkg.name.clone());
+        }
+
         let brew = std::process::Command::new("brew")
-            .args(&["upgrade"])
+            .env("HOMEBREW_NO_ENV_HINTS", "1")
+            .args(&args)
             .output()?;
-        if brew.status.success() == false {
+        if !brew.status.success() {
             let output = String::from_utf8_lossy(&brew.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
 
         Ok(())
     }
+
+    fn uninstall(
+        &self,
+        pkgs: &[crate::rpc::PackageSpec],
+        options: &crate::rpc::UninstallOptions,
+    ) -> anyhow::Result<crate::rpc::UninstallResult> {
+        let mut args = vec!["uninstall".to_string()];
+        if options.force {
+            args.push("--force".to_string());
+        }
+        if options.purge {
+            // Only casks have data to zap.
+            args.push("--zap".to_string());
+        }
+        let names: Vec<String> = pkgs.iter().map(|x| x.name.clone()).collect();
+        args.extend(names.iter().cloned());
+
+        // `brew uninstall` has no dry run, so work out from the installed
+        // formulae what it and `brew autoremove` after it would remove.
+        let installed = brew_info(&["--installed"])?;
+        let mut ret = crate::rpc::UninstallResult {
+            pkgs: plan_uninstall(&installed, &names, options.autoremove)?,
+        };
+        if options.dry_run {
+            return Ok(ret);
+        }
+
+        let brew = std::process::Command::new("brew")
+            .env("HOMEBREW_NO_ENV_HINTS", "1")
+            .args(&args)
+            .output()?;
+        if !brew.status.success() {
+            let output = String::from_utf8_lossy(&brew.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        if options.autoremove {
+            let output = brew_autoremove(false)?;
+            ret.pkgs.truncate(names.len());
+            ret.pkgs.extend(parse_uninstalled(&output));
+        }
+
+        Ok(ret)
+    }
+
+    fn resolve(&self, name: &str) -> anyhow::Result<Vec<crate::rpc::PackageSpec>> {
+        // brew fails for names it does not know.
+        let Ok(output) = brew_info(&[name]) else {
+            return Ok(Vec::new());
+        };
+
+        let mut ret = Vec::new();
+        for item in output["formulae"].as_array().into_iter().flatten() {
+            ret.push(crate::rpc::PackageSpec {
+                name: item["name"].as_str().unwrap_or(name).to_string(),
+                version: item["versions"]["stable"].as_str().map(|v| v.to_string()),
+                vendor: Some("formulae".to_string()),
+            });
+        }
+        for item in output["casks"].as_array().into_iter().flatten() {
+            ret.push(crate::rpc::PackageSpec {
+                name: item["token"].as_str().unwrap_or(name).to_string(),
+                version: item["version"].as_str().map(|v| v.to_string()),
+                vendor: Some("casks".to_string()),
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn search(&self, query: &str) -> anyhow::Result<Vec<crate::rpc::SearchItem>> {
+        let mut ret = Vec::new();
+        for kind in ["--formula", "--cask"] {
+            let names = brew_search(kind, query)?;
+            if names.is_empty() {
+                continue;
+            }
+
+            let mut args = vec![kind];
+            args.extend(names.iter().map(|v| v.as_str()));
+            let output = brew_info(&args)?;
+
+            for item in output["formulae"].as_array().into_iter().flatten() {
+                ret.push(crate::rpc::SearchItem {
+                    name: item["name"].as_str().unwrap_or_default().to_string(),
+                    backend: "brew".to_string(),
+                    vendor: "formulae".to_string(),
+                    version: item["versions"]["stable"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    summary: item["desc"].as_str().unwrap_or_default().to_string(),
+                    installed: item["installed"].as_array().is_some_and(|v| !v.is_empty()),
+                });
+            }
+            for item in output["casks"].as_array().into_iter().flatten() {
+                ret.push(crate::rpc::SearchItem {
+                    name: item["token"].as_str().unwrap_or_default().to_string(),
+                    backend: "brew".to_string(),
+                    vendor: "casks".to_string(),
+                    version: item["version"].as_str().unwrap_or_default().to_string(),
+                    summary: item["desc"].as_str().unwrap_or_default().to_string(),
+                    installed: !item["installed"].is_null(),
+                });
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn info(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageInfo>> {
+        // brew fails for names it does not know.
+        let Ok(output) = brew_info(&[name]) else {
+            return Ok(None);
+        };
+
+        let as_string = |v: &serde_json::Value| v.as_str().map(|v| v.to_string());
+        let as_strings = |v: &serde_json::Value| -> Vec<String> {
+            v.as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_string())
+                .collect()
+        };
+
+        if let Some(item) = output["formulae"].as_array().and_then(|v| v.first()) {
+            let installed_version = item["installed"]
+                .as_array()
+                .and_then(|v| v.last())
+                .and_then(|v| as_string(&v["version"]));
+            return Ok(Some(crate::rpc::PackageInfo {
+                name: as_string(&item["name"]).unwrap_or(name.to_string()),
+                backend: "brew".to_string(),
+                description: as_string(&item["desc"]).unwrap_or_default(),
+                homepage: as_string(&item["homepage"]),
+                license: as_string(&item["license"]),
+                installed_version,
+                candidate_version: as_string(&item["versions"]["stable"]),
+                download_size: None,
+                installed_size: None,
+                origin: as_string(&item["tap"]),
+                dependencies: as_strings(&item["dependencies"]),
+            }));
+        }
+
+        if let Some(item) = output["casks"].as_array().and_then(|v| v.first()) {
+            return Ok(Some(crate::rpc::PackageInfo {
+                name: as_string(&item["token"]).unwrap_or(name.to_string()),
+                backend: "brew".to_string(),
+                description: as_string(&item["desc"]).unwrap_or_default(),
+                homepage: as_string(&item["homepage"]),
+                license: None,
+                installed_version: as_string(&item["installed"]),
+                candidate_version: as_string(&item["version"]),
+                download_size: None,
+                installed_size: None,
+                origin: as_string(&item["tap"]),
+                dependencies: as_strings(&item["depends_on"]["formula"]),
+            }));
+        }
+
+        Ok(None)
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<crate::rpc::InstalledItem>> {
+        let output = brew_info(&["--installed"])?;
+
+        let mut ret = Vec::new();
+        for item in output["formulae"].as_array().into_iter().flatten() {
+            let Some(installed) = item["installed"].as_array().and_then(|v| v.last()) else {
+                continue;
+            };
+            ret.push(crate::rpc::InstalledItem {
+                name: item["name"].as_str().unwrap_or_default().to_string(),
+                version: installed["version"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                arch: String::new(),
+                backend: "brew".to_string(),
+                origin: item["tap"].as_str().unwrap_or_default().to_string(),
+                manual: installed["installed_on_request"].as_bool().unwrap_or(true),
+                revision: String::new(),
+            });
+        }
+        // Casks are never installed as a dependency.
+        for item in output["casks"].as_array().into_iter().flatten() {
+            ret.push(crate::rpc::InstalledItem {
+                name: item["token"].as_str().unwrap_or_default().to_string(),
+                version: item["installed"].as_str().unwrap_or_default().to_string(),
+                arch: String::new(),
+                backend: "brew".to_string(),
+                origin: item["tap"].as_str().unwrap_or_default().to_string(),
+                manual: true,
+                revision: String::new(),
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn simulate(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::SimulateResult> {
+        let mut args = vec!["upgrade".to_string(), "--dry-run".to_string()];
+        args.extend(upgrade_flags(options));
+        if let Some(pkgs) = crate::upgrade_targets(self, options)? {
+            if pkgs.is_empty() {
+                return Ok(crate::rpc::SimulateResult::default());
+            }
+            args.extend(pkgs);
+        }
+
+        let brew = std::process::Command::new("brew")
+            .env("HOMEBREW_NO_ENV_HINTS", "1")
+            .args(&args)
+            .output()?;
+        if !brew.status.success() {
+            let output = String::from_utf8_lossy(&brew.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        let output = String::from_utf8_lossy(&brew.stdout).to_string();
+        Ok(parse_dry_run(&output))
+    }
+
+    fn hold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        brew_run(&["pin"], pkgs)?;
+        Ok(())
+    }
+
+    fn unhold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        brew_run(&["unpin"], pkgs)?;
+        Ok(())
+    }
+
+    fn holds(&self) -> anyhow::Result<Vec<String>> {
+        let output = brew_run(&["list", "--pinned"], &[])?;
+        Ok(output.lines().map(|v| v.trim().to_string()).collect())
+    }
+
+    fn clean(&self, options: &crate::rpc::CleanOptions) -> anyhow::Result<crate::rpc::CleanResult> {
+        let mut args = vec!["cleanup"];
+        if options.all {
+            args.push("--prune=all");
+        }
+
+        if options.dry_run {
+            args.push("--dry-run");
+            let output = brew_run(&args, &[])?;
+            return Ok(crate::rpc::CleanResult {
+                reclaimed: super::disk_usage(&parse_cleanup(&output)),
+            });
+        }
+
+        // Old versions are removed from the cellar as well as the cache.
+        let mut dirs = Vec::new();
+        for arg in ["--cache", "--cellar"] {
+            dirs.push(brew_run(&[arg], &[])?.trim().to_string());
+        }
+
+        let before = super::disk_usage(&dirs);
+        brew_run(&args, &[])?;
+        let after = super::disk_usage(&dirs);
+
+        Ok(crate::rpc::CleanResult {
+            reclaimed: before.saturating_sub(after),
+        })
+    }
+
+    fn orphans(&self) -> anyhow::Result<Vec<crate::rpc::RemoveItem>> {
+        let output = brew_autoremove(true)?;
+        let holds = self.holds()?;
+        let mut ret = parse_uninstalled(&output);
+        ret.retain(|x| !holds.contains(&x.name));
+        Ok(ret)
+    }
+
+    fn changelog(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageChangelog>> {
+        let Ok(json) = brew_info(&[name]) else {
+            return Ok(None);
+        };
+
+        // Formulae and casks only carry the current version, so all there is
+        // to show is where the release notes are.
+        let (current, target, item) = if let Some(item) = json["formulae"].get(0) {
+            let current = item["installed"][0]["version"].as_str();
+            let target = item["versions"]["stable"].as_str();
+            (current, target, item)
+        } else if let Some(item) = json["casks"].get(0) {
+            (item["installed"].as_str(), item["version"].as_str(), item)
+        } else {
+            return Ok(None);
+        };
+        let Some(target) = target else {
+            return Ok(None);
+        };
+
+        let mut text = String::new();
+        if let Some(url) = release_notes_url(item) {
+            text = format!("Release notes: {}", url);
+        }
+        Ok(Some(crate::rpc::PackageChangelog {
+            name: name.to_string(),
+            current_version: current.map(|v| v.to_string()),
+            target_version: target.to_string(),
+            entries: vec![crate::rpc::ChangelogEntry {
+                version: target.to_string(),
+                date: String::new(),
+                text,
+            }],
+        }))
+    }
+
+    fn rollback(&self, changes: &[crate::history::Change]) -> anyhow::Result<()> {
+        for change in changes {
+            let Some(version) = &change.old_version else {
+                brew_run(&["uninstall"], std::slice::from_ref(&change.name))?;
+                continue;
+            };
+
+            // brew only has the current version of a formula, older ones
+            // are only available as versioned formulae like `python@3.11`.
+            let formula = match brew_formula_for(&change.name, version) {
+                Some(v) => v,
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "brew has no formula for version {} of '{}'.",
+                        version,
+                        change.name
+                    ));
+                }
+            };
+            if formula != change.name && change.new_version.is_some() {
+                brew_run(
+                    &["uninstall", "--ignore-dependencies"],
+                    std::slice::from_ref(&change.name),
+                )?;
+            }
+            brew_run(&["install"], &[formula])?;
+        }
+
+        Ok(())
+    }
+
+    fn repos(&self) -> anyhow::Result<Vec<crate::rpc::Repository>> {
+        let output = brew_run(&["tap-info", "--json", "--installed"], &[])?;
+        let taps: Vec<serde_json::Value> = serde_json::from_str(&output)?;
+
+        let mut ret = Vec::new();
+        for tap in taps.iter() {
+            let Some(name) = tap["name"].as_str() else {
+                continue;
+            };
+            ret.push(crate::rpc::Repository {
+                name: name.to_string(),
+                url: tap["remote"].as_str().unwrap_or_default().to_string(),
+                enabled: true,
+                backend: "brew".to_string(),
+                suites: Vec::new(),
+                components: Vec::new(),
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn repo_add(
+        &self,
+        repo: &crate::rpc::Repository,
+        options: &crate::rpc::RepoAddOptions,
+    ) -> anyhow::Result<()> {
+        if options.key.is_some() {
+            return Err(anyhow::anyhow!("brew taps are not signed."));
+        }
+        if !repo.enabled {
+            return Err(anyhow::anyhow!("brew taps cannot be disabled."));
+        }
+
+        let mut args = vec![repo.name.clone()];
+        if !repo.url.is_empty() {
+            args.push(repo.url.clone());
+        }
+        brew_run(&["tap"], &args)?;
+        Ok(())
+    }
+
+    fn repo_remove(&self, name: &str) -> anyhow::Result<()> {
+        brew_run(&["untap"], &[name.to_string()])?;
+        Ok(())
+    }
+
+    fn repo_enable(&self, _name: &str, _enabled: bool) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "brew taps cannot be enabled or disabled, use `upm repo add` and `upm repo remove`."
+        ))
+    }
+
+    fn deps(
+        &self,
+        name: &str,
+        options: &crate::rpc::DepsOptions,
+    ) -> anyhow::Result<Option<Vec<crate::rpc::Dependency>>> {
+        // brew fails for names it does not know.
+        let Ok(root) = brew_info(&[name]) else {
+            return Ok(None);
+        };
+        let installed: std::collections::HashSet<String> =
+            self.list_installed()?.into_iter().map(|x| x.name).collect();
+        let node = |name: &str, kind: crate::rpc::DependencyKind| crate::rpc::Dependency {
+            name: name.to_string(),
+            constraint: String::new(),
+            kind,
+            installed: installed.contains(name),
+            deps: Vec::new(),
+        };
+
+        let mut direct: std::collections::HashMap<String, Vec<crate::rpc::Dependency>> =
+            std::collections::HashMap::new();
+        if options.reverse {
+            // brew only knows which of the installed formulae use a package.
+            let output = brew_info(&["--installed"])?;
+            for item in brew_items(&output) {
+                let Some(user) = brew_item_name(item) else {
+                    continue;
+                };
+                for (dep, kind) in brew_relations(item) {
+                    if kind != crate::rpc::DependencyKind::Build {
+                        direct.entry(dep).or_default().push(node(user, kind));
+                    }
+                }
+            }
+            return Ok(Some(super::dependency_tree(name, &direct)));
+        }
+
+        // Dependencies are poured from bottles, so only the build
+        // dependencies of the package itself matter.
+        let mut level: Vec<&serde_json::Value> = brew_items(&root).collect();
+        let mut outputs = Vec::new();
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for item in level {
+                let Some(parent) = brew_item_name(item) else {
+                    continue;
+                };
+                let mut deps = Vec::new();
+                for (dep, kind) in brew_relations(item) {
+                    let build = kind == crate::rpc::DependencyKind::Build;
+                    if (build && parent != name) || (options.installed && !installed.contains(&dep))
+                    {
+                        continue;
+                    }
+                    if !build && !direct.contains_key(&dep) && !next.contains(&dep) {
+                        next.push(dep.clone());
+                    }
+                    deps.push(node(&dep, kind));
+                }
+                direct.insert(parent.to_string(), deps);
+            }
+
+            next.retain(|x| !direct.contains_key(x));
+            if next.is_empty() {
+                break;
+            }
+            let mut args = vec!["--formula"];
+            args.extend(next.iter().map(|v| v.as_str()));
+            outputs.push(brew_info(&args)?);
+            level = brew_items(outputs.last().unwrap()).collect();
+        }
+
+        Ok(Some(super::dependency_tree(name, &direct)))
+    }
+
+    fn owns(&self, path: &std::path::Path) -> anyhow::Result<Vec<crate::rpc::FileOwner>> {
+        // Everything brew links into its prefix points into a keg,
+        // `Cellar/<name>/<version>`, or `Caskroom/<token>/<version>`.
+        let Ok(path) = std::fs::canonicalize(path) else {
+            return Ok(Vec::new());
+        };
+        for dir in [brew_dir("--cellar")?, brew_dir("--caskroom")?] {
+            let Ok(rest) = path.strip_prefix(&dir) else {
+                continue;
+            };
+            let mut parts = rest.iter().map(|v| v.to_string_lossy().to_string());
+            if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+                return Ok(vec![crate::rpc::FileOwner {
+                    name,
+                    version,
+                    backend: "brew".to_string(),
+                }]);
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn files(&self, name: &str) -> anyhow::Result<Option<Vec<std::path::PathBuf>>> {
+        // `opt/<name>` links to the keg of the installed version.
+        let opt = brew_dir("--prefix")?.join("opt").join(name);
+        if let Ok(keg) = std::fs::canonicalize(opt) {
+            return Ok(Some(super::list_files(&keg)));
+        }
+
+        let caskroom = brew_dir("--caskroom")?.join(name);
+        let Ok(entries) = std::fs::read_dir(&caskroom) else {
+            return Ok(None);
+        };
+        let mut ret = Vec::new();
+        for entry in entries.filter_map(|v| v.ok()) {
+            if entry.file_name() != ".metadata" {
+                ret.extend(super::list_files(&entry.path()));
+            }
+        }
+        Ok(Some(ret))
+    }
+
+    fn verify(&self, pkgs: &[String]) -> anyhow::Result<Vec<crate::rpc::FileIssue>> {
+        let mut ret = Vec::new();
+
+        // brew keeps no checksums of installed files, so check that kegs
+        // still find the libraries they link against.
+        let formulae = if pkgs.is_empty() {
+            brew_run(&["list", "--formula", "-1"], &[])?
+                .lines()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        } else {
+            pkgs.to_vec()
+        };
+        if !formulae.is_empty() {
+            // `--test` fails when something is broken, so only stdout counts.
+            let brew = std::process::Command::new("brew")
+                .env("HOMEBREW_NO_ENV_HINTS", "1")
+                .args(["linkage", "--test"])
+                .args(&formulae)
+                .output()?;
+            let output = String::from_utf8_lossy(&brew.stdout).to_string();
+            ret.extend(parse_linkage(&output, &formulae));
+        }
+
+        // Then check what is linked into the prefix.
+        let prefix = brew_dir("--prefix")?;
+        for dir in ["bin", "sbin", "lib", "include"] {
+            let Ok(entries) = std::fs::read_dir(prefix.join(dir)) else {
+                continue;
+            };
+            for entry in entries.filter_map(|v| v.ok()) {
+                let path = entry.path();
+                let Ok(target) = std::fs::read_link(&path) else {
+                    // Nothing brew installs is a regular file here, except
+                    // brew itself.
+                    if pkgs.is_empty() && path.is_file() && path != prefix.join("bin/brew") {
+                        ret.push(crate::rpc::FileIssue {
+                            name: String::new(),
+                            path: path.to_string_lossy().to_string(),
+                            status: crate::rpc::FileStatus::Unexpected,
+                        });
+                    }
+                    continue;
+                };
+                if path.exists() {
+                    continue;
+                }
+
+                // Links into the Cellar look like `../Cellar/<name>/<version>/...`.
+                let mut parts = target.iter().skip_while(|v| *v != "Cellar").skip(1);
+                let Some(name) = parts.next().map(|v| v.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                if pkgs.is_empty() || pkgs.contains(&name) {
+                    ret.push(crate::rpc::FileIssue {
+                        name,
+                        path: path.to_string_lossy().to_string(),
+                        status: crate::rpc::FileStatus::Missing,
+                    });
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Parse the output of `brew linkage --test`.
+///
+/// The report of each keg starts with `==> Checking <name> linkage` when more
+/// than one is checked, and lists broken links under `Missing libraries:` and
+/// `Broken dependencies:`, one indented entry per line.
+///
+/// # Arguments
+/// + `output` - The output of `brew linkage --test`.
+/// + `formulae` - The formulae that were checked.
+///
+/// # Returns
+/// The missing libraries and dependencies.
+fn parse_linkage(output: &str, formulae: &[String]) -> Vec<crate::rpc::FileIssue> {
+    let mut ret = Vec::new();
+    let mut name = match formulae {
+        [name] => name.clone(),
+        _ => String::new(),
+    };
+    let mut broken = false;
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("==> Checking ") {
+            name = header.trim_end_matches(" linkage").to_string();
+            broken = false;
+        } else if !line.starts_with(' ') {
+            broken =
+                line.starts_with("Missing libraries:") || line.starts_with("Broken dependencies:");
+        } else if broken && !line.trim().is_empty() {
+            ret.push(crate::rpc::FileIssue {
+                name: name.clone(),
+                path: line.trim().to_string(),
+                status: crate::rpc::FileStatus::Missing,
+            });
+        }
+    }
+    ret
+}
+
+/// Get a directory of the brew installation.
+///
+/// # Arguments
+/// + `option` - The brew option that prints it, e.g. `--cellar`.
+///
+/// # Returns
+/// The directory with symlinks resolved.
+fn brew_dir(option: &str) -> anyhow::Result<std::path::PathBuf> {
+    let output = brew_run(&[option], &[])?;
+    let dir = std::path::PathBuf::from(output.trim());
+    Ok(std::fs::canonicalize(&dir).unwrap_or(dir))
+}
+
+/// Parse the output of `brew outdated --json=v2`.
+///
+/// # Arguments
+/// + `output` - The output of `brew outdated --json=v2`.
+///
+/// # Returns
+/// The outdated formulae, then the outdated casks.
+fn parse_outdated(output: &str) -> anyhow::Result<crate::rpc::OutdatedResult> {
+    let output: serde_json::Value = serde_json::from_str(output)?;
+
+    let mut ret = crate::rpc::OutdatedResult { pkgs: Vec::new() };
+    for vendor in ["formulae", "casks"] {
+        for item in output[vendor].as_array().into_iter().flatten() {
+            // Casks cannot be pinned.
+            let status = if item["pinned"].as_bool().unwrap_or(false) {
+                crate::rpc::OutdateStatus::Held
+            } else {
+                crate::rpc::OutdateStatus::Upgradable
+            };
+
+            ret.pkgs.push(crate::rpc::OutdateItem {
+                name: item["name"].as_str().unwrap_or_default().to_string(),
+                vendor: vendor.to_string(),
+                current_version: item["installed_versions"][0]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                target_version: item["current_version"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                status,
+                // Homebrew publishes no security metadata.
+                security: false,
+            });
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Iterate over the formulae and casks of `brew info --json=v2`.
+///
+/// # Arguments
+/// + `output` - The output of brew info.
+///
+/// # Returns
+/// The formulae followed by the casks.
+fn brew_items(output: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+    let formulae = output["formulae"].as_array().into_iter().flatten();
+    let casks = output["casks"].as_array().into_iter().flatten();
+    formulae.chain(casks)
+}
+
+/// Get the name of a formula or cask from `brew info --json=v2`.
+///
+/// # Arguments
+/// + `item` - The formula or cask.
+///
+/// # Returns
+/// The name of a formula or the token of a cask.
+fn brew_item_name(item: &serde_json::Value) -> Option<&str> {
+    item["name"].as_str().or_else(|| item["token"].as_str())
+}
+
+/// Get the dependencies a formula or cask declares.
+///
+/// # Arguments
+/// + `item` - The formula or cask from `brew info --json=v2`.
+///
+/// # Returns
+/// The name and kind of each direct dependency.
+fn brew_relations(item: &serde_json::Value) -> Vec<(String, crate::rpc::DependencyKind)> {
+    use crate::rpc::DependencyKind;
+
+    let fields = [
+        (&item["dependencies"], DependencyKind::Depends),
+        (
+            &item["recommended_dependencies"],
+            DependencyKind::Recommends,
+        ),
+        (&item["build_dependencies"], DependencyKind::Build),
+        (&item["depends_on"]["formula"], DependencyKind::Depends),
+        (&item["depends_on"]["cask"], DependencyKind::Depends),
+    ];
+
+    let mut ret = Vec::new();
+    for (value, kind) in fields {
+        for name in value.as_array().into_iter().flatten() {
+            if let Some(name) = name.as_str() {
+                ret.push((name.to_string(), kind));
+            }
+        }
+    }
+    ret
+}
+
+/// Check whether the download of a formula or cask is cached.
+///
+/// # Arguments
+/// + `name` - The name of formula or cask.
+///
+/// # Returns
+/// `true` if `brew fetch` already downloaded it.
+fn brew_cached(name: &str) -> bool {
+    brew_run(&["--cache"], &[name.to_string()])
+        .is_ok_and(|v| std::path::Path::new(v.trim()).exists())
+}
+
+/// Find where the release notes of a formula or cask are published.
+///
+/// # Arguments
+/// + `item` - The formula or cask from `brew info --json=v2`.
+///
+/// # Returns
+/// The GitHub releases page if the project is hosted there, otherwise the
+/// homepage.
+fn release_notes_url(item: &serde_json::Value) -> Option<String> {
+    let re = regex::Regex::new(r"^https://github\.com/([^/]+/[^/]+)").unwrap();
+    let urls = [
+        item["homepage"].as_str(),
+        item["urls"]["stable"]["url"].as_str(),
+        item["url"].as_str(),
+    ];
+    for url in urls.iter().flatten() {
+        if let Some(caps) = re.captures(url) {
+            let repo = caps.get(1).unwrap().as_str().trim_end_matches(".git");
+            return Some(format!("https://github.com/{}/releases", repo));
+        }
+    }
+
+    item["homepage"].as_str().map(|v| v.to_string())
+}
+
+/// Find a formula that installs a version of a package.
+///
+/// # Arguments
+/// + `name` - The name of formula.
+/// + `version` - The wanted version, optionally with a `_N` revision.
+///
+/// # Returns
+/// The formula itself if its current version matches, otherwise the most
+/// specific versioned formula, `None` if there is neither.
+fn brew_formula_for(name: &str, version: &str) -> Option<String> {
+    let version = version.split_once('_').map_or(version, |(v, _)| v);
+
+    let stable = |formula: &str| -> Option<String> {
+        let json = brew_info(&[formula]).ok()?;
+        json["formulae"][0]["versions"]["stable"]
+            .as_str()
+            .map(|v| v.to_string())
+    };
+    if stable(name).as_deref() == Some(version) {
+        return Some(name.to_string());
+    }
+
+    let parts: Vec<&str> = version.split('.').collect();
+    (1..=parts.len())
+        .rev()
+        .map(|n| format!("{}@{}", name, parts[..n].join(".")))
+        .find(|formula| stable(formula).is_some())
+}
+
+/// Parse the output of `brew cleanup --dry-run`.
+///
+/// Each path that would be removed is listed as `Would remove: path (size)`.
+///
+/// # Arguments
+/// + `output` - The output of brew.
+///
+/// # Returns
+/// The paths that would be removed.
+fn parse_cleanup(output: &str) -> Vec<String> {
+    let re = regex::Regex::new(r"^Would remove: (.+?)(?: \([^)]*\))?$").unwrap();
+    output
+        .lines()
+        .filter_map(|line| re.captures(line))
+        .map(|caps| caps.get(1).unwrap().as_str().to_string())
+        .collect()
+}
+
+/// Build the `brew upgrade` options of an upgrade.
+///
+/// # Arguments
+/// + `options` - How to upgrade.
+///
+/// # Returns
+/// The options, without the packages to upgrade.
+fn upgrade_flags(options: &crate::rpc::UpgradeOptions) -> Vec<String> {
+    let mut ret = Vec::new();
+    match options.brew.kind {
+        crate::rpc::BrewKind::All => {}
+        crate::rpc::BrewKind::Formula => ret.push("--formula".to_string()),
+        crate::rpc::BrewKind::Cask => ret.push("--cask".to_string()),
+    }
+    if options.brew.greedy {
+        ret.push("--greedy".to_string());
+    }
+    ret.extend(options.brew.args.iter().cloned());
+    ret.extend(options.extra_args.iter().cloned());
+    ret
+}
+
+/// Run a brew command on packages.
+///
+/// # Arguments
+/// + `args` - The brew command and its options.
+/// + `pkgs` - The names of packages.
+///
+/// # Returns
+/// The output of brew.
+fn brew_run(args: &[&str], pkgs: &[String]) -> anyhow::Result<String> {
+    let brew = std::process::Command::new("brew")
+        .args(args)
+        .args(pkgs)
+        .output()?;
+    if !brew.status.success() {
+        let output = String::from_utf8_lossy(&brew.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&brew.stdout).to_string())
+}
+
+/// Parse the output of `brew upgrade --dry-run`.
+///
+/// Each `==> Would upgrade`/`==> Would install` header is followed by one
+/// package per line, either `name old -> new` or `name version`.
+///
+/// # Arguments
+/// + `output` - The output of `brew upgrade --dry-run`.
+///
+/// # Returns
+/// The planned transaction.
+fn parse_dry_run(output: &str) -> crate::rpc::SimulateResult {
+    let mut ret = crate::rpc::SimulateResult::default();
+    let mut section: Option<&mut Vec<crate::rpc::PlanItem>> = None;
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("==> ") {
+            section = if header.starts_with("Would upgrade") {
+                Some(&mut ret.upgrades)
+            } else if header.starts_with("Would install") {
+                Some(&mut ret.installs)
+            } else {
+                None
+            };
+            continue;
+        }
+
+        let Some(items) = section.as_mut() else {
+            continue;
+        };
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let item = match cols.as_slice() {
+            [name, old, "->", new] => crate::rpc::PlanItem {
+                name: name.to_string(),
+                current_version: old.to_string(),
+                target_version: new.to_string(),
+            },
+            [name, new] => crate::rpc::PlanItem {
+                name: name.to_string(),
+                current_version: String::new(),
+                target_version: new.to_string(),
+            },
+            [name] => crate::rpc::PlanItem {
+                name: name.to_string(),
+                current_version: String::new(),
+                target_version: String::new(),
+            },
+            _ => continue,
+        };
+        items.push(item);
+    }
+
+    ret
+}
+
+/// Search formulae or casks by name.
+///
+/// # Arguments
+/// + `kind` - Either `--formula` or `--cask`.
+/// + `query` - The search keyword.
+///
+/// # Returns
+/// The matching names.
+fn brew_search(kind: &str, query: &str) -> anyhow::Result<Vec<String>> {
+    let brew = std::process::Command::new("brew")
+        .env("HOMEBREW_NO_ENV_HINTS", "1")
+        .args(["search", kind, query])
+        .output()?;
+    if !brew.status.success() {
+        // brew exits with an error if nothing matches.
+        return Ok(Vec::new());
+    }
+
+    let output = String::from_utf8_lossy(&brew.stdout).to_string();
+    let ret = output
+        .lines()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && !v.starts_with("==>"))
+        .map(|v| v.to_string())
+        .collect();
+
+    Ok(ret)
+}
+
+/// Get the metadata of formulae and casks.
+///
+/// # Arguments
+/// + `args` - Extra arguments and names passed to `brew info`.
+///
+/// # Returns
+/// The JSON output of `brew info --json=v2`.
+fn brew_info(args: &[&str]) -> anyhow::Result<serde_json::Value> {
+    let brew = std::process::Command::new("brew")
+        .env("HOMEBREW_NO_ENV_HINTS", "1")
+        .args(["info", "--json=v2"])
+        .args(args)
+        .output()?;
+    if !brew.status.success() {
+        let output = String::from_utf8_lossy(&brew.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    let output = String::from_utf8_lossy(&brew.stdout).to_string();
+    let output: serde_json::Value = serde_json::from_str(&output)?;
+    Ok(output)
+}
+
+/// Work out what uninstalling formulae and casks removes.
+///
+/// Like `brew autoremove`, a formula is left over when it was installed as a
+/// dependency and nothing that stays installed needs it.
+///
+/// # Arguments
+/// + `installed` - The output of `brew info --json=v2 --installed`.
+/// + `names` - The names of the formulae and casks to uninstall.
+/// + `autoremove` - Also list the formulae left over afterwards.
+///
+/// # Returns
+/// The named packages first, then the left over formulae.
+fn plan_uninstall(
+    installed: &serde_json::Value,
+    names: &[String],
+    autoremove: bool,
+) -> anyhow::Result<Vec<crate::rpc::RemoveItem>> {
+    let formulae: Vec<&serde_json::Value> = installed["formulae"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    let casks: Vec<&serde_json::Value> = installed["casks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    let is_named = |name: &str, full_name: &str| names.iter().any(|x| x == name || x == full_name);
+
+    let mut ret = Vec::new();
+    for name in names {
+        let formula = formulae
+            .iter()
+            .find(|x| x["name"] == name.as_str() || x["full_name"] == name.as_str());
+        let version = match formula {
+            Some(item) => item["installed"]
+                .as_array()
+                .and_then(|v| v.last())
+                .and_then(|v| v["version"].as_str()),
+            None => match casks.iter().find(|x| x["token"] == name.as_str()) {
+                Some(item) => item["installed"].as_str(),
+                None => return Err(anyhow::anyhow!("'{}' is not installed.", name)),
+            },
+        };
+        ret.push(crate::rpc::RemoveItem {
+            name: name.clone(),
+            version: version.unwrap_or_default().to_string(),
+        });
+    }
+    if !autoremove {
+        return Ok(ret);
+    }
+
+    // Everything that stays and was installed on request keeps its runtime
+    // dependencies, casks keep the formulae they depend on.
+    let mut needed = std::collections::HashSet::new();
+    for item in formulae.iter() {
+        let name = item["name"].as_str().unwrap_or_default();
+        let full_name = item["full_name"].as_str().unwrap_or(name);
+        let Some(installed) = item["installed"].as_array().and_then(|v| v.last()) else {
+            continue;
+        };
+        if is_named(name, full_name) || !installed["installed_on_request"].as_bool().unwrap_or(true)
+        {
+            continue;
+        }
+        needed.insert(full_name.to_string());
+        for dep in installed["runtime_dependencies"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(v) = dep["full_name"].as_str() {
+                needed.insert(v.to_string());
+            }
+        }
+    }
+    let mut wanted: Vec<String> = Vec::new();
+    for item in casks.iter() {
+        if is_named(item["token"].as_str().unwrap_or_default(), "") {
+            continue;
+        }
+        for dep in item["depends_on"]["formula"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(v) = dep.as_str() {
+                wanted.push(v.to_string());
+            }
+        }
+    }
+    for item in formulae.iter() {
+        let name = item["name"].as_str().unwrap_or_default();
+        let full_name = item["full_name"].as_str().unwrap_or(name);
+        if !wanted.iter().any(|x| x == name || x == full_name) {
+            continue;
+        }
+        needed.insert(full_name.to_string());
+        let installed = item["installed"].as_array().and_then(|v| v.last());
+        for dep in installed
+            .and_then(|v| v["runtime_dependencies"].as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(v) = dep["full_name"].as_str() {
+                needed.insert(v.to_string());
+            }
+        }
+    }
+
+    for item in formulae.iter() {
+        let name = item["name"].as_str().unwrap_or_default();
+        let full_name = item["full_name"].as_str().unwrap_or(name);
+        if is_named(name, full_name) || needed.contains(full_name) {
+            continue;
+        }
+        let Some(installed) = item["installed"].as_array().and_then(|v| v.last()) else {
+            continue;
+        };
+        ret.push(crate::rpc::RemoveItem {
+            name: name.to_string(),
+            version: installed["version"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+
+    Ok(ret)
+}
+
+/// Remove formulae that were only installed as dependencies.
+///
+/// # Arguments
+/// + `dry_run` - Only list what would be removed.
+///
+/// # Returns
+/// The output of `brew autoremove`.
+fn brew_autoremove(dry_run: bool) -> anyhow::Result<String> {
+    let mut cmd = std::process::Command::new("brew");
+    cmd.env("HOMEBREW_NO_ENV_HINTS", "1").arg("autoremove");
+    if dry_run {
+        cmd.arg("--dry-run");
+    }
+
+    let brew = cmd.output()?;
+    if !brew.status.success() {
+        let output = String::from_utf8_lossy(&brew.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&brew.stdout).to_string())
+}
+
+/// Parse the formulae listed by `brew autoremove`.
+///
+/// The output is a `==>` header followed by one formula per line.
+///
+/// # Arguments
+/// + `output` - The output of `brew autoremove`.
+///
+/// # Returns
+/// The removed formulae.
+fn parse_uninstalled(output: &str) -> Vec<crate::rpc::RemoveItem> {
+    let mut ret = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("==>") || line.starts_with("Uninstalling") {
+            continue;
+        }
+
+        ret.push(crate::rpc::RemoveItem {
+            name: line.to_string(),
+            version: String::new(),
+        });
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removals(items: Vec<crate::rpc::RemoveItem>) -> Vec<(String, String)> {
+        items.into_iter().map(|x| (x.name, x.version)).collect()
+    }
+
+    fn pair(name: &str, version: &str) -> (String, String) {
+        (name.to_string(), version.to_string())
+    }
+
+    #[test]
+    fn test_parse_linkage() {
+        let output = "\
+==> Checking wget linkage
+System libraries:
+  /usr/lib/libSystem.B.dylib
+Missing libraries:
+  /opt/homebrew/opt/libidn2/lib/libidn2.0.dylib
+==> Checking curl linkage
+Broken dependencies:
+  /opt/homebrew/opt/openssl@3/lib/libssl.3.dylib (openssl@3)
+";
+        let issues: Vec<(String, String)> =
+            parse_linkage(output, &["wget".to_string(), "curl".to_string()])
+                .into_iter()
+                .map(|x| (x.name, x.path))
+                .collect();
+        assert_eq!(
+            issues,
+            [
+                pair("wget", "/opt/homebrew/opt/libidn2/lib/libidn2.0.dylib"),
+                pair(
+                    "curl",
+                    "/opt/homebrew/opt/openssl@3/lib/libssl.3.dylib (openssl@3)"
+                ),
+            ]
+        );
+
+        // A single keg has no header.
+        let output = "Missing libraries:\n  /usr/local/lib/libfoo.dylib\n";
+        let issues = parse_linkage(output, &["foo".to_string()]);
+        assert_eq!(issues[0].name, "foo");
+        assert_eq!(issues[0].status, crate::rpc::FileStatus::Missing);
+    }
+
+    #[test]
+    fn test_brew_relations() {
+        use crate::rpc::DependencyKind::{Build, Depends, Recommends};
+
+        let formula = serde_json::json!({
+            "name": "wget",
+            "dependencies": ["libidn2", "openssl@3"],
+            "recommended_dependencies": ["gettext"],
+            "build_dependencies": ["pkgconf"],
+        });
+        assert_eq!(
+            brew_relations(&formula),
+            [
+                ("libidn2".to_string(), Depends),
+                ("openssl@3".to_string(), Depends),
+                ("gettext".to_string(), Recommends),
+                ("pkgconf".to_string(), Build),
+            ]
+        );
+
+        let cask = serde_json::json!({
+            "token": "docker",
+            "depends_on": {"macos": {">=": ["12"]}, "cask": ["docker-compose"]},
+        });
+        assert_eq!(
+            brew_relations(&cask),
+            [("docker-compose".to_string(), Depends)]
+        );
+    }
+
+    #[test]
+    fn test_release_notes_url() {
+        let formula = serde_json::json!({
+            "homepage": "https://www.gnu.org/software/wget/",
+            "urls": {"stable": {"url": "https://github.com/mirror/wget.git"}},
+        });
+        assert_eq!(
+            release_notes_url(&formula).as_deref(),
+            Some("https://github.com/mirror/wget/releases")
+        );
+
+        let cask = serde_json::json!({
+            "homepage": "https://www.mozilla.org/firefox/",
+            "url": "https://download-installer.cdn.mozilla.net/firefox.dmg",
+        });
+        assert_eq!(
+            release_notes_url(&cask).as_deref(),
+            Some("https://www.mozilla.org/firefox/")
+        );
+        assert_eq!(release_notes_url(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn test_parse_cleanup() {
+        let output = "\
+Would remove: /opt/homebrew/Cellar/wget/1.21.3 (91 files, 4.2MB)
+Would remove: /Users/me/Library/Caches/Homebrew/wget--1.21.3.bottle.tar.gz (1.4MB)
+Would remove: /Users/me/Library/Logs/Homebrew/wget
+==> This operation would free approximately 5.6MB of disk space.
+";
+        assert_eq!(
+            parse_cleanup(output),
+            [
+                "/opt/homebrew/Cellar/wget/1.21.3",
+                "/Users/me/Library/Caches/Homebrew/wget--1.21.3.bottle.tar.gz",
+                "/Users/me/Library/Logs/Homebrew/wget",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_outdated() {
+        let output = r#"{
+  "formulae": [
+    {
+      "name": "git",
+      "installed_versions": ["2.44.0"],
+      "current_version": "2.45.1",
+      "pinned": false,
+      "pinned_version": null
+    },
+    {
+      "name": "node",
+      "installed_versions": ["21.7.1"],
+      "current_version": "22.2.0",
+      "pinned": true,
+      "pinned_version": "21.7.1"
+    }
+  ],
+  "casks": [
+    {
+      "name": "firefox",
+      "installed_versions": ["125.0.3"],
+      "current_version": "126.0"
+    }
+  ]
+}"#;
+        let ret = parse_outdated(output).unwrap();
+        let pkgs: Vec<(&str, &str, &str, &str, crate::rpc::OutdateStatus)> = ret
+            .pkgs
+            .iter()
+            .map(|x| {
+                (
+                    x.name.as_str(),
+                    x.vendor.as_str(),
+                    x.current_version.as_str(),
+                    x.target_version.as_str(),
+                    x.status,
+                )
+            })
+            .collect();
+        assert_eq!(
+            pkgs,
+            vec![
+                (
+                    "git",
+                    "formulae",
+                    "2.44.0",
+                    "2.45.1",
+                    crate::rpc::OutdateStatus::Upgradable
+                ),
+                (
+                    "node",
+                    "formulae",
+                    "21.7.1",
+                    "22.2.0",
+                    crate::rpc::OutdateStatus::Held
+                ),
+                (
+                    "firefox",
+                    "casks",
+                    "125.0.3",
+                    "126.0",
+                    crate::rpc::OutdateStatus::Upgradable
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upgrade_flags() {
+        let mut options = crate::rpc::UpgradeOptions::default();
+        assert!(upgrade_flags(&options).is_empty());
+
+        options.brew.kind = crate::rpc::BrewKind::Cask;
+        options.brew.greedy = true;
+        options.brew.args = vec!["--no-quarantine".to_string()];
+        options.extra_args = vec!["--verbose".to_string()];
+        assert_eq!(
+            upgrade_flags(&options),
+            ["--cask", "--greedy", "--no-quarantine", "--verbose"]
+        );
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let output = "\
+==> Would upgrade 2 outdated packages:
+wget 1.21.3 -> 1.24.5
+firefox 124.0 -> 125.0.1
+==> Would install 1 dependency for wget:
+libidn2 2.3.7
+==> Would upgrade 1 cask:
+==> Fetching downloads for: wget
+";
+        let ret = parse_dry_run(output);
+        let plan = |items: &[crate::rpc::PlanItem]| -> Vec<(String, String, String)> {
+            items
+                .iter()
+                .map(|x| {
+                    (
+                        x.name.clone(),
+                        x.current_version.clone(),
+                        x.target_version.clone(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(
+            plan(&ret.upgrades),
+            [
+                (
+                    "wget".to_string(),
+                    "1.21.3".to_string(),
+                    "1.24.5".to_string()
+                ),
+                (
+                    "firefox".to_string(),
+                    "124.0".to_string(),
+                    "125.0.1".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            plan(&ret.installs),
+            [("libidn2".to_string(), String::new(), "2.3.7".to_string())]
+        );
+        assert!(ret.removals.is_empty());
+    }
+
+    #[test]
+    fn test_plan_uninstall() {
+        let installed = serde_json::json!({
+            "formulae": [
+                {
+                    "name": "wget",
+                    "full_name": "wget",
+                    "installed": [{
+                        "version": "1.24.5",
+                        "installed_on_request": true,
+                        "runtime_dependencies": [
+                            {"full_name": "libidn2"},
+                            {"full_name": "openssl@3"},
+                        ],
+                    }],
+                },
+                {
+                    "name": "curl",
+                    "full_name": "curl",
+                    "installed": [{
+                        "version": "8.7.1",
+                        "installed_on_request": true,
+                        "runtime_dependencies": [{"full_name": "openssl@3"}],
+                    }],
+                },
+                {
+                    "name": "libidn2",
+                    "full_name": "libidn2",
+                    "installed": [{"version": "2.3.7", "installed_on_request": false}],
+                },
+                {
+                    "name": "openssl@3",
+                    "full_name": "openssl@3",
+                    "installed": [{"version": "3.3.0", "installed_on_request": false}],
+                },
+                {
+                    "name": "xz",
+                    "full_name": "xz",
+                    "installed": [{"version": "5.4.6", "installed_on_request": false}],
+                },
+            ],
+            "casks": [
+                {"token": "firefox", "installed": "125.0.1"},
+                {"token": "xquartz", "installed": "2.8.5", "depends_on": {"formula": ["xz"]}},
+            ],
+        });
+
+        let ret = plan_uninstall(&installed, &["wget".to_string()], false).unwrap();
+        assert_eq!(removals(ret), [pair("wget", "1.24.5")]);
+
+        // openssl@3 stays for curl, xz for the xquartz cask.
+        let ret = plan_uninstall(&installed, &["wget".to_string()], true).unwrap();
+        assert_eq!(
+            removals(ret),
+            [pair("wget", "1.24.5"), pair("libidn2", "2.3.7")]
+        );
+
+        let ret = plan_uninstall(
+            &installed,
+            &[
+                "curl".to_string(),
+                "wget".to_string(),
+                "xquartz".to_string(),
+            ],
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            removals(ret),
+            [
+                pair("curl", "8.7.1"),
+                pair("wget", "1.24.5"),
+                pair("xquartz", "2.8.5"),
+                pair("libidn2", "2.3.7"),
+                pair("openssl@3", "3.3.0"),
+                pair("xz", "5.4.6"),
+            ]
+        );
+
+        assert!(plan_uninstall(&installed, &["vim".to_string()], false).is_err());
+    }
+
+    #[test]
+    fn test_parse_uninstalled() {
+        let output = "\
+==> Autoremoving 2 unneeded formulae:
+libidn2
+openssl@3
+Uninstalling /opt/homebrew/Cellar/libidn2/2.3.7... (78 files, 1MB)
+";
+        assert_eq!(
+            removals(parse_uninstalled(output)),
+            [pair("libidn2", ""), pair("openssl@3", "")]
+        );
+    }
 }
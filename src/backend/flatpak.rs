@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct FlatpakBackend {}
 
 impl FlatpakBackend {
@@ -12,7 +12,7 @@ impl crate::UpmBackend for FlatpakBackend {
         let child = std::process::Command::new("flatpak")
             .arg("--version")
             .output()?;
-        if child.status.success() == false {
+        if !child.status.success() {
             return Err(anyhow::anyhow!("flatpak is not found."));
         }
 
@@ -20,6 +20,25 @@ impl crate::UpmBackend for FlatpakBackend {
             update: true,
             outdated: false,
             upgrade: false,
+            install: false,
+            uninstall: false,
+            resolve: false,
+            search: false,
+            info: false,
+            list_installed: false,
+            simulate: false,
+            hold: true,
+            holds: false,
+            clean: false,
+            orphans: false,
+            rollback: false,
+            changelog: false,
+            repos: false,
+            repo_edit: true,
+            deps: false,
+            owns: false,
+            files: false,
+            verify: true,
         });
 
         Ok(setup)
@@ -27,10 +46,10 @@ impl crate::UpmBackend for FlatpakBackend {
 
     fn update(&self) -> anyhow::Result<()> {
         let flatpak = std::process::Command::new("flatpak")
-            .args(&["update", "--appstream"])
+            .args(["update", "--appstream"])
             .output()?;
 
-        if flatpak.status.success() == false {
+        if !flatpak.status.success() {
             let output = String::from_utf8_lossy(&flatpak.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
@@ -39,8 +58,9 @@ impl crate::UpmBackend for FlatpakBackend {
 
     fn outdated(&self) -> anyhow::Result<crate::rpc::OutdatedResult> {
         let mut ret = crate::rpc::OutdatedResult { pkgs: Vec::new() };
-        let updates = flatpak_remote_ls_updates()?;
+        let updates = flatpak_remote_ls_updates(false)?;
         let installs = flatpak_ls()?;
+        let masks = self.holds()?;
 
         for item in updates {
             let install = installs.iter().find(|&x| x.name == item.name);
@@ -50,6 +70,13 @@ impl crate::UpmBackend for FlatpakBackend {
                     vendor: item.vendor.clone(),
                     current_version: install.version.clone(),
                     target_version: item.version.clone(),
+                    status: if masks.iter().any(|x| mask_matches(x, &item.name)) {
+                        crate::rpc::OutdateStatus::Held
+                    } else {
+                        crate::rpc::OutdateStatus::Upgradable
+                    },
+                    // flatpak remotes publish no security metadata.
+                    security: false,
                 };
 
                 ret.pkgs.push(outdate_item);
@@ -59,17 +86,1317 @@ impl crate::UpmBackend for FlatpakBackend {
         Ok(ret)
     }
 
-    fn upgrade(&self) -> anyhow::Result<()> {
+    fn upgrade(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::UpgradeResult> {
+        let mut ret = crate::rpc::UpgradeResult {
+            pending: Vec::new(),
+        };
+        let mut args = vec!["update".to_string(), "--noninteractive".to_string()];
+        if options.download_only {
+            args.push("--no-deploy".to_string());
+        }
+        if options.offline {
+            args.push("--no-pull".to_string());
+        }
+        args.extend(upgrade_flags(options));
+        let targets = crate::upgrade_targets(self, options)?;
+        if let Some(pkgs) = &targets {
+            if pkgs.is_empty() {
+                return Ok(ret);
+            }
+            args.extend(pkgs.iter().cloned());
+        }
+
+        let flatpak = std::process::Command::new("flatpak").args(&args).output()?;
+
+        if !flatpak.status.success() {
+            let output = String::from_utf8_lossy(&flatpak.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        // Whatever is still outdated was not pulled before.
+        if options.offline {
+            ret.pending = flatpak_remote_ls_updates(true)?
+                .into_iter()
+                .map(|x| x.name)
+                .filter(|x| targets.as_ref().is_none_or(|v| v.contains(x)))
+                .collect();
+        }
+        Ok(ret)
+    }
+
+    fn install(&self, pkgs: &[crate::rpc::PackageSpec]) -> anyhow::Result<()> {
+        // The remote is given per invocation, so install packages one by one.
+        for pkg in pkgs {
+            if pkg.version.is_some() {
+                return Err(anyhow::anyhow!(
+                    "flatpak does not support version pinning for '{}'.",
+                    pkg.name
+                ));
+            }
+
+            let mut cmd = std::process::Command::new("flatpak");
+            cmd.args(["install", "--noninteractive"]);
+            if let Some(remote) = &pkg.vendor {
+                cmd.arg(remote);
+            }
+            cmd.arg(&pkg.name);
+
+            let flatpak = cmd.output()?;
+            if !flatpak.status.success() {
+                let output = String::from_utf8_lossy(&flatpak.stderr);
+                return Err(anyhow::anyhow!("{}", output.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn uninstall(
+        &self,
+        pkgs: &[crate::rpc::PackageSpec],
+        options: &crate::rpc::UninstallOptions,
+    ) -> anyhow::Result<crate::rpc::UninstallResult> {
+        let installs = flatpak_ls()?;
+
+        let mut ret = crate::rpc::UninstallResult { pkgs: Vec::new() };
+        for pkg in pkgs {
+            let install = match installs.iter().find(|&x| x.name == pkg.name) {
+                Some(v) => v,
+                None => {
+                    return Err(anyhow::anyhow!("'{}' is not installed.", pkg.name));
+                }
+            };
+            ret.pkgs.push(crate::rpc::RemoveItem {
+                name: install.name.clone(),
+                version: install.version.clone(),
+            });
+        }
+
+        let mut args = vec!["uninstall".to_string()];
+        if options.purge {
+            args.push("--delete-data".to_string());
+        }
+        if options.force {
+            args.push("--force-remove".to_string());
+        }
+        for pkg in pkgs {
+            args.push(pkg.name.clone());
+        }
+
+        if options.dry_run {
+            // The transaction also has the locale and debug extensions.
+            let plan = parse_transaction(&flatpak_preview(&args)?);
+            let mut names: Vec<String> = plan.removals.into_iter().map(|x| x.name).collect();
+            if options.autoremove {
+                // `--unused` cannot be combined with refs, and only knows about
+                // what is unused before the removal.
+                let masks = self.holds()?;
+                let apps = flatpak_app_runtimes()?;
+                let refs: Vec<String> = installs.iter().map(|x| x.name.clone()).collect();
+                let unused = self
+                    .orphans()?
+                    .into_iter()
+                    .map(|x| x.name)
+                    .chain(runtimes_left_unused(&apps, &refs, &names));
+                for name in unused {
+                    if !names.contains(&name) && !masks.iter().any(|x| mask_matches(x, &name)) {
+                        names.push(name);
+                    }
+                }
+            }
+
+            ret.pkgs = names
+                .into_iter()
+                .map(|name| crate::rpc::RemoveItem {
+                    version: installs
+                        .iter()
+                        .find(|x| x.name == name)
+                        .map(|x| x.version.clone())
+                        .unwrap_or_default(),
+                    name,
+                })
+                .collect();
+            return Ok(ret);
+        }
+
         let flatpak = std::process::Command::new("flatpak")
-            .args(&["update", "--noninteractive"])
+            .args(&args)
+            .arg("--noninteractive")
             .output()?;
+        if !flatpak.status.success() {
+            let output = String::from_utf8_lossy(&flatpak.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+
+        if options.autoremove {
+            let flatpak = std::process::Command::new("flatpak")
+                .args(["uninstall", "--noninteractive", "--unused"])
+                .output()?;
+            if !flatpak.status.success() {
+                let output = String::from_utf8_lossy(&flatpak.stderr);
+                return Err(anyhow::anyhow!("{}", output.to_string()));
+            }
+        }
+
+        // Unused runtimes are only known after the fact, so report everything
+        // that disappeared.
+        let remains = flatpak_ls()?;
+        ret.pkgs = installs
+            .into_iter()
+            .filter(|x| !remains.iter().any(|y| y.name == x.name))
+            .map(|x| crate::rpc::RemoveItem {
+                name: x.name,
+                version: x.version,
+            })
+            .collect();
+
+        Ok(ret)
+    }
+
+    fn resolve(&self, name: &str) -> anyhow::Result<Vec<crate::rpc::PackageSpec>> {
+        // `flatpak search` is a fuzzy search, only keep exact matches of the
+        // application id, its last component or the display name.
+        let mut ret = Vec::new();
+        for item in flatpak_search(name)? {
+            let short = item.application.rsplit('.').next().unwrap_or_default();
+            if item.application != name
+                && !short.eq_ignore_ascii_case(name)
+                && !item.name.eq_ignore_ascii_case(name)
+            {
+                continue;
+            }
+
+            for remote in item.remotes.split(',') {
+                ret.push(crate::rpc::PackageSpec {
+                    name: item.application.clone(),
+                    version: Some(item.version.clone()).filter(|v| !v.is_empty()),
+                    vendor: Some(remote.to_string()),
+                });
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn search(&self, query: &str) -> anyhow::Result<Vec<crate::rpc::SearchItem>> {
+        let installs = flatpak_ls()?;
+
+        let mut ret = Vec::new();
+        for item in flatpak_search(query)? {
+            let installed = installs.iter().any(|x| x.name == item.application);
+            ret.push(crate::rpc::SearchItem {
+                name: item.application,
+                backend: "flatpak".to_string(),
+                vendor: item.remotes,
+                version: item.version,
+                summary: item.description,
+                installed,
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn info(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageInfo>> {
+        let local = flatpak_info(&["info", name])?;
+
+        // Find the remote to ask for the candidate version.
+        let remote_name = match local.as_ref().and_then(|x| x.field("Origin")) {
+            Some(v) => Some(v.to_string()),
+            None => flatpak_search(name)?
+                .into_iter()
+                .find(|x| x.application == name)
+                .and_then(|x| x.remotes.split(',').next().map(|v| v.to_string())),
+        };
+        let remote = match &remote_name {
+            Some(remote) => flatpak_info(&["remote-info", remote, name])?,
+            None => None,
+        };
+
+        let Some(any) = local.as_ref().or(remote.as_ref()) else {
+            return Ok(None);
+        };
+        let field = |key: &str| {
+            local
+                .as_ref()
+                .and_then(|x| x.field(key))
+                .or_else(|| remote.as_ref().and_then(|x| x.field(key)))
+                .map(|v| v.to_string())
+        };
+
+        let info = crate::rpc::PackageInfo {
+            name: field("ID").unwrap_or(name.to_string()),
+            backend: "flatpak".to_string(),
+            description: any.summary.clone(),
+            homepage: None,
+            license: field("License"),
+            installed_version: local
+                .as_ref()
+                .map(|x| x.field("Version").unwrap_or_default().to_string()),
+            candidate_version: remote
+                .as_ref()
+                .and_then(|x| x.field("Version"))
+                .map(|v| v.to_string()),
+            download_size: remote
+                .as_ref()
+                .and_then(|x| x.field("Download"))
+                .and_then(parse_size),
+            installed_size: field("Installed").as_deref().and_then(parse_size),
+            origin: remote_name,
+            dependencies: field("Runtime").into_iter().collect(),
+        };
+        Ok(Some(info))
+    }
+
+    fn list_installed(&self) -> anyhow::Result<Vec<crate::rpc::InstalledItem>> {
+        // Runtimes are only there to support applications.
+        let mut ret = Vec::new();
+        for (kind, manual) in [("--app", true), ("--runtime", false)] {
+            let flatpak = std::process::Command::new("flatpak")
+                .args([
+                    "list",
+                    kind,
+                    "--columns=application,version,arch,origin,active",
+                ])
+                .output()?;
+            if !flatpak.status.success() {
+                let output = String::from_utf8_lossy(&flatpak.stderr);
+                return Err(anyhow::anyhow!("{}", output.to_string()));
+            }
+
+            let output = String::from_utf8_lossy(&flatpak.stdout).to_string();
+            for line in output.lines() {
+                let cols: Vec<&str> = line.split('\t').collect();
+                if cols.len() != 5 {
+                    continue;
+                }
+
+                ret.push(crate::rpc::InstalledItem {
+                    name: cols[0].to_string(),
+                    version: cols[1].to_string(),
+                    arch: cols[2].to_string(),
+                    backend: "flatpak".to_string(),
+                    origin: cols[3].to_string(),
+                    manual,
+                    revision: cols[4].to_string(),
+                });
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn simulate(
+        &self,
+        options: &crate::rpc::UpgradeOptions,
+    ) -> anyhow::Result<crate::rpc::SimulateResult> {
+        // Passed through arguments such as `-y` would answer the preview.
+        let mut args = vec!["update".to_string()];
+        args.extend(installation_flags(options));
+        if let Some(pkgs) = crate::upgrade_targets(self, options)? {
+            if pkgs.is_empty() {
+                return Ok(crate::rpc::SimulateResult::default());
+            }
+            args.extend(pkgs);
+        }
+
+        let output = flatpak_preview(&args)?;
+        let mut ret = parse_transaction(&output);
+
+        // The preview has no versions, fill them in.
+        let installs = flatpak_ls()?;
+        let updates = flatpak_remote_ls_updates(false)?;
+        for item in ret.upgrades.iter_mut().chain(ret.removals.iter_mut()) {
+            if let Some(install) = installs.iter().find(|x| x.name == item.name) {
+                item.current_version = install.version.clone();
+            }
+        }
+        for item in ret.upgrades.iter_mut().chain(ret.installs.iter_mut()) {
+            if let Some(update) = updates.iter().find(|x| x.name == item.name) {
+                item.target_version = update.version.clone();
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn hold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        flatpak_mask(&[], pkgs)?;
+        Ok(())
+    }
+
+    fn unhold(&self, pkgs: &[String]) -> anyhow::Result<()> {
+        flatpak_mask(&["--remove"], pkgs)?;
+        Ok(())
+    }
+
+    fn holds(&self) -> anyhow::Result<Vec<String>> {
+        let output = flatpak_mask(&[], &[])?;
+        Ok(output
+            .lines()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect())
+    }
 
-        if flatpak.status.success() == false {
+    fn clean(&self, options: &crate::rpc::CleanOptions) -> anyhow::Result<crate::rpc::CleanResult> {
+        let dirs = flatpak_installations();
+
+        if options.dry_run {
+            let output = flatpak_preview(&["uninstall".to_string(), "--unused".to_string()])?;
+            let plan = parse_transaction(&output);
+            let mut paths = Vec::new();
+            for dir in dirs.iter() {
+                for item in plan.removals.iter() {
+                    paths.push(dir.join("app").join(&item.name));
+                    paths.push(dir.join("runtime").join(&item.name));
+                }
+            }
+            return Ok(crate::rpc::CleanResult {
+                reclaimed: super::disk_usage(&paths),
+            });
+        }
+
+        // flatpak prunes the repository after uninstalling, so the objects
+        // of the unused refs go too.
+        let before = super::disk_usage(&dirs);
+        let flatpak = std::process::Command::new("flatpak")
+            .args(["uninstall", "--noninteractive", "--unused"])
+            .output()?;
+        if !flatpak.status.success() {
             let output = String::from_utf8_lossy(&flatpak.stderr);
             return Err(anyhow::anyhow!("{}", output.to_string()));
         }
+        let after = super::disk_usage(&dirs);
+
+        Ok(crate::rpc::CleanResult {
+            reclaimed: before.saturating_sub(after),
+        })
+    }
+
+    fn orphans(&self) -> anyhow::Result<Vec<crate::rpc::RemoveItem>> {
+        let output = flatpak_preview(&["uninstall".to_string(), "--unused".to_string()])?;
+        let plan = parse_transaction(&output);
+        let installs = flatpak_ls()?;
+        let masks = self.holds()?;
+
+        let mut ret = Vec::new();
+        for item in plan.removals {
+            if masks.iter().any(|x| mask_matches(x, &item.name)) {
+                continue;
+            }
+            let version = installs
+                .iter()
+                .find(|x| x.name == item.name)
+                .map(|x| x.version.clone())
+                .unwrap_or_default();
+            ret.push(crate::rpc::RemoveItem {
+                name: item.name,
+                version,
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn changelog(&self, name: &str) -> anyhow::Result<Option<crate::rpc::PackageChangelog>> {
+        let installs = flatpak_ls()?;
+        let Some(install) = installs.into_iter().find(|x| x.name == name) else {
+            return Ok(None);
+        };
+        let target = flatpak_remote_ls_updates(false)?
+            .into_iter()
+            .find(|x| x.name == name)
+            .map_or(install.version.clone(), |x| x.version);
+
+        // The release notes come from the appstream data of the remote.
+        let mut entries = Vec::new();
+        for dir in flatpak_installations() {
+            let dir = dir.join("appstream").join(&install.vendor);
+            let Ok(arches) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for arch in arches.filter_map(|v| v.ok()) {
+                let active = arch.path().join("active");
+                let Ok(xml) = super::read_text(&active.join("appstream.xml.gz"))
+                    .or_else(|_| super::read_text(&active.join("appstream.xml")))
+                else {
+                    continue;
+                };
+                if let Some(v) = parse_appstream_releases(&xml, name) {
+                    entries = v;
+                    break;
+                }
+            }
+        }
+
+        let current = Some(install.version).filter(|v| !v.is_empty());
+        Ok(Some(crate::rpc::PackageChangelog {
+            name: name.to_string(),
+            entries: super::trim_changelog(entries, current.as_deref(), &target),
+            current_version: current,
+            target_version: target,
+        }))
+    }
+
+    fn rollback(&self, changes: &[crate::history::Change]) -> anyhow::Result<()> {
+        let run = |args: &[&str]| -> anyhow::Result<()> {
+            let flatpak = std::process::Command::new("flatpak")
+                .args(args)
+                .arg("--noninteractive")
+                .output()?;
+            if !flatpak.status.success() {
+                let output = String::from_utf8_lossy(&flatpak.stderr);
+                return Err(anyhow::anyhow!("{}", output.to_string()));
+            }
+            Ok(())
+        };
+
+        for change in changes {
+            if change.old_version.is_none() {
+                run(&["uninstall", &change.name])?;
+                continue;
+            }
+            if change.old_revision.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "the previous commit of '{}' is unknown.",
+                    change.name
+                ));
+            }
+
+            // Reinstall what was removed, then go back to the old commit.
+            if change.new_version.is_none() {
+                run(&["install", &change.name])?;
+            }
+            let commit = format!("--commit={}", change.old_revision);
+            run(&["update", &commit, &change.name])?;
+        }
+
+        Ok(())
+    }
+
+    fn repos(&self) -> anyhow::Result<Vec<crate::rpc::Repository>> {
+        let output = flatpak_run(
+            &["remotes", "--show-disabled", "--columns=name,url,options"],
+            None,
+        )?;
+
+        let mut ret = Vec::new();
+        for line in output.lines() {
+            let cols: Vec<&str> = line.split('\t').map(|v| v.trim()).collect();
+            let [name, url, options, ..] = cols[..] else {
+                continue;
+            };
+            ret.push(crate::rpc::Repository {
+                name: name.to_string(),
+                url: url.to_string(),
+                enabled: !options.split(',').any(|v| v.trim() == "disabled"),
+                backend: "flatpak".to_string(),
+                suites: Vec::new(),
+                components: Vec::new(),
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn repo_add(
+        &self,
+        repo: &crate::rpc::Repository,
+        options: &crate::rpc::RepoAddOptions,
+    ) -> anyhow::Result<()> {
+        let mut args = vec!["remote-add", "--if-not-exists"];
+        if !repo.enabled {
+            args.push("--disable");
+        }
+
+        // A `.flatpakrepo` file carries its key, others get it on stdin.
+        let key = match &options.key {
+            Some(v) => {
+                args.push("--gpg-import=-");
+                Some(super::read_key(v)?)
+            }
+            None => None,
+        };
+        args.push(&repo.name);
+        args.push(&repo.url);
+
+        flatpak_run(&args, key.as_deref())?;
         Ok(())
     }
+
+    fn repo_remove(&self, name: &str) -> anyhow::Result<()> {
+        flatpak_run(&["remote-delete", name], None)?;
+        Ok(())
+    }
+
+    fn repo_enable(&self, name: &str, enabled: bool) -> anyhow::Result<()> {
+        let option = if enabled { "--enable" } else { "--disable" };
+        flatpak_run(&["remote-modify", option, name], None)?;
+        Ok(())
+    }
+
+    fn deps(
+        &self,
+        name: &str,
+        options: &crate::rpc::DepsOptions,
+    ) -> anyhow::Result<Option<Vec<crate::rpc::Dependency>>> {
+        let Some(metadata) = self.metadata(name)? else {
+            return Ok(None);
+        };
+        let installed: Vec<String> = flatpak_ls()?.into_iter().map(|x| x.name).collect();
+        // Extension points are prefixes of the extensions that fill them.
+        let is_installed = |name: &str| {
+            installed
+                .iter()
+                .any(|x| x == name || x.starts_with(&format!("{}.", name)))
+        };
+
+        let mut direct: std::collections::HashMap<String, Vec<crate::rpc::Dependency>> =
+            std::collections::HashMap::new();
+        if options.reverse {
+            // Only installed applications and runtimes say what they run on.
+            let output = flatpak_run(&["list", "--columns=application,runtime"], None)?;
+            for line in output.lines() {
+                let Some((user, runtime)) = line.split_once('\t') else {
+                    continue;
+                };
+                let (runtime, branch) = split_partial_ref(runtime.trim());
+                if runtime.is_empty() || runtime == user {
+                    continue;
+                }
+                direct
+                    .entry(runtime.to_string())
+                    .or_default()
+                    .push(crate::rpc::Dependency {
+                        name: user.trim().to_string(),
+                        constraint: branch.to_string(),
+                        kind: crate::rpc::DependencyKind::Runtime,
+                        installed: true,
+                        deps: Vec::new(),
+                    });
+            }
+            return Ok(Some(super::dependency_tree(name, &direct)));
+        }
+
+        // Follow the runtimes, extension points and SDKs have no metadata of
+        // their own to look up.
+        let mut queue = vec![(name.to_string(), metadata)];
+        while let Some((parent, metadata)) = queue.pop() {
+            let mut deps = Vec::new();
+            for (name, constraint, kind) in parse_metadata_relations(&metadata) {
+                if name == parent || (options.installed && !is_installed(&name)) {
+                    continue;
+                }
+                let runtime = kind == crate::rpc::DependencyKind::Runtime;
+                if runtime && !direct.contains_key(&name) && !queue.iter().any(|(k, _)| *k == name)
+                {
+                    let partial = if constraint.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}//{}", name, constraint)
+                    };
+                    if let Some(metadata) = self.metadata(&partial)? {
+                        queue.push((name.clone(), metadata));
+                    }
+                }
+                deps.push(crate::rpc::Dependency {
+                    installed: is_installed(&name),
+                    name,
+                    constraint,
+                    kind,
+                    deps: Vec::new(),
+                });
+            }
+            direct.insert(parent, deps);
+        }
+
+        Ok(Some(super::dependency_tree(name, &direct)))
+    }
+
+    fn owns(&self, path: &std::path::Path) -> anyhow::Result<Vec<crate::rpc::FileOwner>> {
+        // Exported files link into the deploy directory of their ref,
+        // `<installation>/{app,runtime}/<id>/<arch>/<branch>/<commit>`.
+        let Ok(path) = std::fs::canonicalize(path) else {
+            return Ok(Vec::new());
+        };
+        for dir in flatpak_installations() {
+            let dir = std::fs::canonicalize(&dir).unwrap_or(dir);
+            let Ok(rest) = path.strip_prefix(&dir) else {
+                continue;
+            };
+            let mut parts = rest.iter().map(|v| v.to_string_lossy().to_string());
+            let (Some(kind), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            if kind != "app" && kind != "runtime" {
+                continue;
+            }
+
+            let version = flatpak_ls()?
+                .into_iter()
+                .find(|x| x.name == name)
+                .map(|x| x.version)
+                .unwrap_or_default();
+            return Ok(vec![crate::rpc::FileOwner {
+                name,
+                version,
+                backend: "flatpak".to_string(),
+            }]);
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn files(&self, name: &str) -> anyhow::Result<Option<Vec<std::path::PathBuf>>> {
+        let flatpak = std::process::Command::new("flatpak")
+            .args(["info", "--show-location", name])
+            .output()?;
+        // flatpak fails for refs that are not installed.
+        if !flatpak.status.success() {
+            return Ok(None);
+        }
+
+        let output = String::from_utf8_lossy(&flatpak.stdout).to_string();
+        Ok(Some(super::list_files(std::path::Path::new(output.trim()))))
+    }
+
+    fn verify(&self, pkgs: &[String]) -> anyhow::Result<Vec<crate::rpc::FileIssue>> {
+        // flatpak only checks the objects of its repository, it reports the
+        // refs it would have to delete and pull again.
+        let output = flatpak_run(&["repair", "--system", "--dry-run"], None)?;
+        let issues = parse_repair(&output)
+            .into_iter()
+            .filter(|x| pkgs.is_empty() || pkgs.contains(&x.name))
+            .collect();
+        Ok(issues)
+    }
+}
+
+impl FlatpakBackend {
+    /// Get the metadata of an application or runtime, installed or from an
+    /// enabled remote.
+    ///
+    /// # Arguments
+    /// + `name` - The id, optionally with `//branch`.
+    ///
+    /// # Returns
+    /// The metadata key file, `None` if no remote has the ref.
+    fn metadata(&self, name: &str) -> anyhow::Result<Option<String>> {
+        use crate::UpmBackend;
+
+        let show = |args: &[&str]| -> anyhow::Result<Option<String>> {
+            let flatpak = std::process::Command::new("flatpak")
+                .env("LANG", "C")
+                .args(args)
+                .args(["--show-metadata", name])
+                .output()?;
+            if !flatpak.status.success() {
+                return Ok(None);
+            }
+            Ok(Some(String::from_utf8_lossy(&flatpak.stdout).to_string()))
+        };
+
+        if let Some(v) = show(&["info"])? {
+            return Ok(Some(v));
+        }
+        for remote in self.repos()?.iter().filter(|x| x.enabled) {
+            if let Some(v) = show(&["remote-info", &remote.name])? {
+                return Ok(Some(v));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Split a partial ref such as `org.gnome.Platform/x86_64/46`.
+///
+/// # Arguments
+/// + `partial` - The partial ref.
+///
+/// # Returns
+/// The id and the branch, empty if not given.
+fn split_partial_ref(partial: &str) -> (&str, &str) {
+    let mut parts = partial.split('/');
+    let name = parts.next().unwrap_or_default();
+    let branch = parts.nth(1).unwrap_or_default();
+    (name, branch)
+}
+
+/// Get the relations declared in the metadata of an application or runtime.
+///
+/// The metadata is a key file, `runtime` and `sdk` in the `[Application]` or
+/// `[Runtime]` group name partial refs and each `[Extension id]` group
+/// declares an extension point.
+///
+/// # Arguments
+/// + `metadata` - The metadata key file.
+///
+/// # Returns
+/// The id, the branch or versions and the kind of each relation.
+fn parse_metadata_relations(metadata: &str) -> Vec<(String, String, crate::rpc::DependencyKind)> {
+    use crate::rpc::DependencyKind;
+
+    let mut ret = Vec::new();
+    let mut group = "";
+    for line in metadata.lines() {
+        let line = line.trim();
+        if let Some(v) = line.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            group = v;
+            if let Some(id) = group.strip_prefix("Extension ") {
+                ret.push((id.to_string(), String::new(), DependencyKind::Extension));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        match (group, key.trim()) {
+            ("Application" | "Runtime", "runtime") => {
+                let (name, branch) = split_partial_ref(value.trim());
+                ret.push((
+                    name.to_string(),
+                    branch.to_string(),
+                    DependencyKind::Runtime,
+                ));
+            }
+            ("Application" | "Runtime", "sdk") => {
+                let (name, branch) = split_partial_ref(value.trim());
+                ret.push((name.to_string(), branch.to_string(), DependencyKind::Build));
+            }
+            (g, "version" | "versions") if g.starts_with("Extension ") => {
+                if let Some(last) = ret.last_mut() {
+                    last.1 = value.trim().trim_end_matches(';').replace(';', ", ");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+/// Run a flatpak command.
+///
+/// # Arguments
+/// + `args` - The arguments passed to flatpak.
+/// + `input` - The data to write to its stdin, if any.
+///
+/// # Returns
+/// The output of flatpak.
+fn flatpak_run(args: &[&str], input: Option<&[u8]>) -> anyhow::Result<String> {
+    use std::io::Write;
+
+    let mut child = std::process::Command::new("flatpak")
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.unwrap_or_default())?;
+    }
+    let flatpak = child.wait_with_output()?;
+    if !flatpak.status.success() {
+        let output = String::from_utf8_lossy(&flatpak.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&flatpak.stdout).to_string())
+}
+
+/// Parse the output of `flatpak repair --dry-run`.
+///
+/// Broken refs are reported as `Deleting ref <ref> due to missing objects`
+/// or `... due to invalid objects`, with `<ref>` possibly prefixed with the
+/// remote as `remote:app/<id>/<arch>/<branch>`.
+///
+/// # Arguments
+/// + `output` - The output of `flatpak repair --dry-run`.
+///
+/// # Returns
+/// The broken refs, with the ref as path.
+fn parse_repair(output: &str) -> Vec<crate::rpc::FileIssue> {
+    let mut ret: Vec<crate::rpc::FileIssue> = Vec::new();
+    for line in output.lines() {
+        let Some((_, rest)) = line.split_once("Deleting ref ") else {
+            continue;
+        };
+        let (reference, reason) = rest.split_once(" due to ").unwrap_or((rest, ""));
+        let reference = reference.trim();
+        let bare = reference.rsplit(':').next().unwrap_or(reference);
+        let Some(name) = bare.split('/').nth(1) else {
+            continue;
+        };
+        if ret.iter().any(|x| x.path == reference) {
+            continue;
+        }
+
+        let status = if reason.contains("missing") {
+            crate::rpc::FileStatus::Missing
+        } else {
+            crate::rpc::FileStatus::Modified
+        };
+        ret.push(crate::rpc::FileIssue {
+            name: name.to_string(),
+            path: reference.to_string(),
+            status,
+        });
+    }
+    ret
+}
+
+/// Build the `flatpak update` options of an upgrade.
+///
+/// # Arguments
+/// + `options` - How to upgrade.
+///
+/// # Returns
+/// The options, without the refs to update.
+fn upgrade_flags(options: &crate::rpc::UpgradeOptions) -> Vec<String> {
+    let mut ret = installation_flags(options);
+    ret.extend(options.flatpak.args.iter().cloned());
+    ret.extend(options.extra_args.iter().cloned());
+    ret
+}
+
+/// Build the `flatpak update` options that select the installation.
+///
+/// # Arguments
+/// + `options` - How to upgrade.
+///
+/// # Returns
+/// `--system`, `--user` or nothing for both.
+fn installation_flags(options: &crate::rpc::UpgradeOptions) -> Vec<String> {
+    match options.flatpak.installation {
+        crate::rpc::FlatpakInstallation::Both => Vec::new(),
+        crate::rpc::FlatpakInstallation::System => vec!["--system".to_string()],
+        crate::rpc::FlatpakInstallation::User => vec!["--user".to_string()],
+    }
+}
+
+/// The system and user installations of flatpak.
+///
+/// # Returns
+/// The directories of the installations.
+fn flatpak_installations() -> Vec<std::path::PathBuf> {
+    let mut ret = vec![std::path::PathBuf::from("/var/lib/flatpak")];
+    if let Some(home) = std::env::var_os("HOME") {
+        ret.push(std::path::Path::new(&home).join(".local/share/flatpak"));
+    }
+    ret
+}
+
+/// Print the transaction of a flatpak command without running it.
+///
+/// flatpak has no dry run, but prints the transaction before asking for
+/// confirmation. Answer no.
+///
+/// # Arguments
+/// + `args` - The arguments passed to flatpak.
+///
+/// # Returns
+/// The output of flatpak.
+fn flatpak_preview(args: &[String]) -> anyhow::Result<String> {
+    use std::io::Write;
+
+    let mut child = std::process::Command::new("flatpak")
+        .env("LANG", "C")
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(b"n\n")?;
+    }
+    let flatpak = child.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&flatpak.stderr);
+    // Declining the transaction may count as a failure.
+    if !flatpak.status.success() && !stderr.contains("Aborted") {
+        return Err(anyhow::anyhow!("{}", stderr.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&flatpak.stdout).to_string())
+}
+
+/// Find the releases of an application in appstream data.
+///
+/// # Arguments
+/// + `xml` - The appstream catalog of a remote.
+/// + `id` - The application id.
+///
+/// # Returns
+/// The releases, newest first. `None` if the application is not in the
+/// catalog.
+fn parse_appstream_releases(xml: &str, id: &str) -> Option<Vec<crate::rpc::ChangelogEntry>> {
+    let component = regex::Regex::new(r"(?s)<component\b[^>]*>(.*?)</component>").unwrap();
+    let release = regex::Regex::new(r"(?s)<release\b([^>]*?)(?:/>|>(.*?)</release>)").unwrap();
+    let attr = regex::Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+    let description = regex::Regex::new(r"(?s)<description>(.*?)</description>").unwrap();
+    let translated = regex::Regex::new(r#"(?s)<(\w+) xml:lang="[^"]*">.*?</\w+>"#).unwrap();
+    let tag = regex::Regex::new(r"<[^>]*>").unwrap();
+
+    let ids = [
+        format!("<id>{}</id>", id),
+        format!("<id>{}.desktop</id>", id),
+    ];
+    let body = component
+        .captures_iter(xml)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .find(|body| ids.iter().any(|v| body.contains(v.as_str())))?;
+
+    let mut ret = Vec::new();
+    for caps in release.captures_iter(body) {
+        let attrs: std::collections::HashMap<&str, &str> = attr
+            .captures_iter(caps.get(1).unwrap().as_str())
+            .map(|v| (v.get(1).unwrap().as_str(), v.get(2).unwrap().as_str()))
+            .collect();
+        let Some(version) = attrs.get("version") else {
+            continue;
+        };
+        let date = match (attrs.get("date"), attrs.get("timestamp")) {
+            (Some(v), _) => v.to_string(),
+            (None, Some(v)) => crate::history::format_timestamp(v.parse().unwrap_or(0))
+                .split(' ')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            (None, None) => String::new(),
+        };
+
+        let mut text = String::new();
+        let inner = caps.get(2).map_or("", |v| v.as_str());
+        if let Some(desc) = description.captures(inner) {
+            let desc = translated.replace_all(desc.get(1).unwrap().as_str(), "");
+            let desc = desc.replace("<li>", "\n* ").replace("</p>", "\n");
+            let desc = tag.replace_all(&desc, "");
+            text = desc
+                .lines()
+                .map(|v| v.split_whitespace().collect::<Vec<&str>>().join(" "))
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<String>>()
+                .join("\n")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&");
+        }
+
+        ret.push(crate::rpc::ChangelogEntry {
+            version: version.to_string(),
+            date,
+            text,
+        });
+    }
+
+    Some(ret)
+}
+
+/// Run `flatpak mask`, which lists the masked patterns when given none.
+///
+/// # Arguments
+/// + `args` - The options of flatpak mask.
+/// + `patterns` - The patterns to mask or unmask.
+///
+/// # Returns
+/// The output of flatpak.
+fn flatpak_mask(args: &[&str], patterns: &[String]) -> anyhow::Result<String> {
+    let flatpak = std::process::Command::new("flatpak")
+        .arg("mask")
+        .args(args)
+        .args(patterns)
+        .output()?;
+    if !flatpak.status.success() {
+        let output = String::from_utf8_lossy(&flatpak.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&flatpak.stdout).to_string())
+}
+
+/// Check whether an application id is matched by a `flatpak mask` pattern,
+/// where `*` matches anything.
+///
+/// # Arguments
+/// + `pattern` - The mask pattern.
+/// + `name` - The application id.
+///
+/// # Returns
+/// `true` if the pattern matches.
+fn mask_matches(pattern: &str, name: &str) -> bool {
+    let re = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join(".*");
+    match regex::Regex::new(&format!("^(?:.*/)?{}(?:/.*)?$", re)) {
+        Ok(re) => re.is_match(name),
+        Err(_) => false,
+    }
+}
+
+/// List the runtime of every installed application.
+///
+/// # Returns
+/// Pairs of application id and runtime id.
+fn flatpak_app_runtimes() -> anyhow::Result<Vec<(String, String)>> {
+    let flatpak = std::process::Command::new("flatpak")
+        .args(["list", "--app", "--columns=application,runtime"])
+        .output()?;
+    if !flatpak.status.success() {
+        let output = String::from_utf8_lossy(&flatpak.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    let output = String::from_utf8_lossy(&flatpak.stdout).to_string();
+    Ok(parse_app_runtimes(&output))
+}
+
+/// Parse the output of `flatpak list --app --columns=application,runtime`.
+///
+/// The runtime is a ref like `org.gnome.Platform/x86_64/46`.
+///
+/// # Arguments
+/// + `output` - The output of flatpak.
+///
+/// # Returns
+/// Pairs of application id and runtime id.
+fn parse_app_runtimes(output: &str) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    for line in output.lines() {
+        let mut columns = line.split_whitespace();
+        let (Some(app), Some(runtime)) = (columns.next(), columns.next()) else {
+            continue;
+        };
+        let runtime = runtime.split('/').next().unwrap_or(runtime);
+        ret.push((app.to_string(), runtime.to_string()));
+    }
+    ret
+}
+
+/// Find the runtimes no application uses once some are uninstalled.
+///
+/// # Arguments
+/// + `apps` - Pairs of application id and runtime id.
+/// + `refs` - The ids of every installed ref.
+/// + `removed` - The ids of the refs to uninstall.
+///
+/// # Returns
+/// The runtimes left unused and their extensions.
+fn runtimes_left_unused(
+    apps: &[(String, String)],
+    refs: &[String],
+    removed: &[String],
+) -> Vec<String> {
+    let mut ret = Vec::new();
+    for (app, runtime) in apps {
+        if !removed.contains(app) || ret.contains(runtime) {
+            continue;
+        }
+        if apps
+            .iter()
+            .any(|(other, v)| v == runtime && !removed.contains(other))
+        {
+            continue;
+        }
+        ret.push(runtime.clone());
+    }
+
+    let extensions: Vec<String> = refs
+        .iter()
+        .filter(|x| ret.iter().any(|v| x.starts_with(&format!("{}.", v))))
+        .cloned()
+        .collect();
+    ret.retain(|x| refs.contains(x));
+    ret.extend(extensions);
+    ret
+}
+
+/// Parse the transaction table flatpak prints before asking for confirmation.
+///
+/// Each row looks like ` 1.  org.gnome.Platform  46  u  flathub  < 1 MB`,
+/// where the op is `i` for install, `u` for update and `r` for remove.
+///
+/// # Arguments
+/// + `output` - The output of flatpak.
+///
+/// # Returns
+/// The planned transaction, without versions.
+fn parse_transaction(output: &str) -> crate::rpc::SimulateResult {
+    let re = regex::Regex::new(r"^\s*\d+\.\s+(?:\[.\]\s+)?(\S+)\s+\S+\s+([iur])\s").unwrap();
+
+    let mut ret = crate::rpc::SimulateResult::default();
+    for line in output.lines() {
+        let Some(caps) = re.captures(line) else {
+            continue;
+        };
+
+        let item = crate::rpc::PlanItem {
+            name: caps.get(1).unwrap().as_str().to_string(),
+            current_version: String::new(),
+            target_version: String::new(),
+        };
+        match caps.get(2).unwrap().as_str() {
+            "i" => ret.installs.push(item),
+            "u" => ret.upgrades.push(item),
+            _ => ret.removals.push(item),
+        }
+    }
+
+    ret
+}
+
+/// The output of `flatpak info` or `flatpak remote-info`.
+#[derive(Debug)]
+struct FlatpakInfo {
+    /// The one line description from the title.
+    summary: String,
+    fields: Vec<(String, String)>,
+}
+
+impl FlatpakInfo {
+    fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Run `flatpak info` or `flatpak remote-info` and parse the output.
+///
+/// # Arguments
+/// + `args` - The arguments passed to flatpak.
+///
+/// # Returns
+/// The parsed output, `None` if the ref is unknown.
+fn flatpak_info(args: &[&str]) -> anyhow::Result<Option<FlatpakInfo>> {
+    let flatpak = std::process::Command::new("flatpak")
+        .env("LANG", "C")
+        .args(args)
+        .output()?;
+    if !flatpak.status.success() {
+        return Ok(None);
+    }
+
+    let output = String::from_utf8_lossy(&flatpak.stdout).to_string();
+    Ok(Some(parse_info(&output)))
+}
+
+/// Parse the output of `flatpak info` or `flatpak remote-info`.
+///
+/// The output starts with a `Name - summary` title followed by right aligned
+/// `Key: value` lines.
+///
+/// # Arguments
+/// + `output` - The output of flatpak.
+///
+/// # Returns
+/// The parsed output.
+fn parse_info(output: &str) -> FlatpakInfo {
+    let re = regex::Regex::new(r"^\s*([A-Za-z][A-Za-z ]*): (.*)$").unwrap();
+
+    let mut ret = FlatpakInfo {
+        summary: String::new(),
+        fields: Vec::new(),
+    };
+    for line in output.lines() {
+        if let Some(caps) = re.captures(line) {
+            let key = caps.get(1).unwrap().as_str();
+            let value = caps.get(2).unwrap().as_str().trim();
+            ret.fields.push((key.to_string(), value.to_string()));
+        } else if ret.summary.is_empty() && !line.trim().is_empty() {
+            let title = line.trim();
+            let summary = title.split_once(" - ").map(|(_, v)| v).unwrap_or(title);
+            ret.summary = summary.to_string();
+        }
+    }
+
+    ret
+}
+
+/// Parse a size printed by flatpak, such as `276.5 MB`.
+///
+/// flatpak separates the unit with a no-break space (U+00A0).
+///
+/// # Arguments
+/// + `size` - The printed size.
+///
+/// # Returns
+/// The size in bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let (value, unit) = size
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((size.trim(), "bytes"));
+    let value: f64 = value.parse().ok()?;
+    let scale = match unit.trim() {
+        "bytes" | "byte" => 1.0,
+        "kB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+
+    Some((value * scale) as u64)
+}
+
+#[derive(Debug)]
+struct FlatpakSearchItem {
+    name: String,
+    description: String,
+    application: String,
+    version: String,
+    remotes: String,
+}
+
+/// Search applications in all remotes.
+///
+/// # Arguments
+/// + `query` - The search keyword.
+///
+/// # Returns
+/// The matching applications.
+fn flatpak_search(query: &str) -> anyhow::Result<Vec<FlatpakSearchItem>> {
+    let flatpak = std::process::Command::new("flatpak")
+        .args([
+            "search",
+            "--columns=name,description,application,version,remotes",
+            query,
+        ])
+        .output()?;
+    if !flatpak.status.success() {
+        let output = String::from_utf8_lossy(&flatpak.stderr);
+        return Err(anyhow::anyhow!("{}", output.to_string()));
+    }
+
+    // Columns are tab separated and may be empty.
+    let output = String::from_utf8_lossy(&flatpak.stdout).to_string();
+    let mut ret = Vec::new();
+    for line in output.lines() {
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() != 5 {
+            continue;
+        }
+
+        ret.push(FlatpakSearchItem {
+            name: cols[0].to_string(),
+            description: cols[1].to_string(),
+            application: cols[2].to_string(),
+            version: cols[3].to_string(),
+            remotes: cols[4].to_string(),
+        });
+    }
+
+    Ok(ret)
 }
 
 #[derive(Debug)]
@@ -81,18 +1408,24 @@ struct FlatpakItem {
 
 /// List updates from remote.
 ///
+/// # Arguments
+/// + `cached` - Use the metadata fetched before instead of the network.
+///
 /// # Returns
 /// A list of updates.
-fn flatpak_remote_ls_updates() -> anyhow::Result<Vec<FlatpakItem>> {
-    let flatpak = std::process::Command::new("flatpak")
-        .args(&[
-            "remote-ls",
-            "--updates",
-            "--columns=application,version,origin",
-        ])
-        .output()?;
+fn flatpak_remote_ls_updates(cached: bool) -> anyhow::Result<Vec<FlatpakItem>> {
+    let mut cmd = std::process::Command::new("flatpak");
+    cmd.args([
+        "remote-ls",
+        "--updates",
+        "--columns=application,version,origin",
+    ]);
+    if cached {
+        cmd.arg("--cached");
+    }
+    let flatpak = cmd.output()?;
 
-    if flatpak.status.success() == false {
+    if !flatpak.status.success() {
         let output = String::from_utf8_lossy(&flatpak.stderr);
         return Err(anyhow::anyhow!("{}", output.to_string()));
     }
@@ -128,9 +1461,9 @@ fn flatpak_remote_ls_updates() -> anyhow::Result<Vec<FlatpakItem>> {
 /// A list of installed flatpak packages.
 fn flatpak_ls() -> anyhow::Result<Vec<FlatpakItem>> {
     let flatpak = std::process::Command::new("flatpak")
-        .args(&["list", "--columns=application,version,origin"])
+        .args(["list", "--columns=application,version,origin"])
         .output()?;
-    if flatpak.status.success() == false {
+    if !flatpak.status.success() {
         let output = String::from_utf8_lossy(&flatpak.stderr);
         return Err(anyhow::anyhow!("{}", output.to_string()));
     }
@@ -159,3 +1492,305 @@ fn flatpak_ls() -> anyhow::Result<Vec<FlatpakItem>> {
 
     Ok(ret)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[crate::rpc::PlanItem]) -> Vec<&str> {
+        items.iter().map(|x| x.name.as_str()).collect()
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_partial_ref() {
+        assert_eq!(
+            split_partial_ref("org.gnome.Platform/x86_64/46"),
+            ("org.gnome.Platform", "46")
+        );
+        assert_eq!(
+            split_partial_ref("org.gnome.Platform"),
+            ("org.gnome.Platform", "")
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_relations() {
+        use crate::rpc::DependencyKind::{Build, Extension, Runtime};
+
+        let metadata = "\
+[Application]
+name=org.gnome.Calculator
+runtime=org.gnome.Platform/x86_64/46
+sdk=org.gnome.Sdk/x86_64/46
+command=gnome-calculator
+
+[Context]
+shared=network;ipc;
+
+[Extension org.gnome.Calculator.Locale]
+directory=share/runtime/locale
+autodelete=true
+
+[Extension org.freedesktop.Platform.GL]
+versions=23.08;23.08extra;
+directory=lib/GL
+";
+        assert_eq!(
+            parse_metadata_relations(metadata),
+            [
+                ("org.gnome.Platform".to_string(), "46".to_string(), Runtime),
+                ("org.gnome.Sdk".to_string(), "46".to_string(), Build),
+                (
+                    "org.gnome.Calculator.Locale".to_string(),
+                    String::new(),
+                    Extension
+                ),
+                (
+                    "org.freedesktop.Platform.GL".to_string(),
+                    "23.08, 23.08extra".to_string(),
+                    Extension
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_repair() {
+        let output = "\
+Working on the system installation at /var/lib/flatpak
+Checking remotes...
+Pruning objects
+Deleting ref flathub:app/org.gnome.Calculator/x86_64/stable due to missing objects
+Deleting ref runtime/org.gnome.Platform/x86_64/46 due to invalid objects
+Deleting ref flathub:app/org.gnome.Calculator/x86_64/stable due to missing objects
+";
+        let issues: Vec<(String, String, crate::rpc::FileStatus)> = parse_repair(output)
+            .into_iter()
+            .map(|x| (x.name, x.path, x.status))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (
+                    "org.gnome.Calculator".to_string(),
+                    "flathub:app/org.gnome.Calculator/x86_64/stable".to_string(),
+                    crate::rpc::FileStatus::Missing
+                ),
+                (
+                    "org.gnome.Platform".to_string(),
+                    "runtime/org.gnome.Platform/x86_64/46".to_string(),
+                    crate::rpc::FileStatus::Modified
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upgrade_flags() {
+        let mut options = crate::rpc::UpgradeOptions::default();
+        assert!(upgrade_flags(&options).is_empty());
+
+        options.flatpak.installation = crate::rpc::FlatpakInstallation::User;
+        options.flatpak.args = strings(&["--no-related"]);
+        options.extra_args = strings(&["--verbose"]);
+        assert_eq!(
+            upgrade_flags(&options),
+            ["--user", "--no-related", "--verbose"]
+        );
+        assert_eq!(installation_flags(&options), ["--user"]);
+    }
+
+    #[test]
+    fn test_parse_appstream_releases() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<components version="0.8" origin="flathub">
+  <component type="desktop">
+    <id>org.gnome.Weather.desktop</id>
+    <releases>
+      <release version="46.0" timestamp="1700000000"/>
+    </releases>
+  </component>
+  <component type="desktop-application">
+    <id>org.gnome.Calculator</id>
+    <releases>
+      <release version="46.1" date="2024-04-20">
+        <description>
+          <p>Fixes:</p>
+          <p xml:lang="de">Korrekturen:</p>
+          <ul>
+            <li>Keep  the &quot;ans&quot; &amp; memory</li>
+            <li>Round &lt;1e-10 to zero</li>
+          </ul>
+        </description>
+      </release>
+      <release version="46.0" timestamp="1700000000"/>
+      <release date="2023-01-01"/>
+    </releases>
+  </component>
+</components>
+"#;
+        let entries = parse_appstream_releases(xml, "org.gnome.Calculator").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].version, "46.1");
+        assert_eq!(entries[0].date, "2024-04-20");
+        assert_eq!(
+            entries[0].text,
+            "Fixes:\n* Keep the \"ans\" & memory\n* Round <1e-10 to zero"
+        );
+        assert_eq!(entries[1].version, "46.0");
+        assert_eq!(entries[1].date, "2023-11-14");
+        assert_eq!(entries[1].text, "");
+
+        let entries = parse_appstream_releases(xml, "org.gnome.Weather").unwrap();
+        assert_eq!(entries[0].version, "46.0");
+        assert!(parse_appstream_releases(xml, "org.gnome.Maps").is_none());
+    }
+
+    #[test]
+    fn test_mask_matches() {
+        assert!(mask_matches("org.gnome.Calculator", "org.gnome.Calculator"));
+        assert!(mask_matches("org.gnome.*", "org.gnome.Calculator"));
+        assert!(mask_matches(
+            "org.gnome.Platform",
+            "runtime/org.gnome.Platform/x86_64/46"
+        ));
+        assert!(mask_matches("*", "org.gnome.Calculator"));
+        assert!(!mask_matches("org.gnome", "org.gnome.Calculator"));
+        assert!(!mask_matches(
+            "org.gnome.Calculator",
+            "org.gnomeXCalculator.Extra"
+        ));
+    }
+
+    #[test]
+    fn test_parse_app_runtimes() {
+        let output = "\
+org.gnome.Calculator\torg.gnome.Platform/x86_64/46
+org.mozilla.firefox\torg.freedesktop.Platform/x86_64/23.08
+
+";
+        assert_eq!(
+            parse_app_runtimes(output),
+            [
+                (
+                    "org.gnome.Calculator".to_string(),
+                    "org.gnome.Platform".to_string()
+                ),
+                (
+                    "org.mozilla.firefox".to_string(),
+                    "org.freedesktop.Platform".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runtimes_left_unused() {
+        let apps = [
+            ("org.gnome.Calculator", "org.gnome.Platform"),
+            ("org.gnome.Weather", "org.gnome.Platform"),
+            ("org.mozilla.firefox", "org.freedesktop.Platform"),
+        ]
+        .map(|(a, r)| (a.to_string(), r.to_string()));
+        let refs = strings(&[
+            "org.gnome.Calculator",
+            "org.gnome.Weather",
+            "org.mozilla.firefox",
+            "org.gnome.Platform",
+            "org.gnome.Platform.Locale",
+            "org.freedesktop.Platform",
+            "org.freedesktop.Platform.GL.default",
+        ]);
+
+        // Another application still uses the GNOME runtime.
+        assert!(runtimes_left_unused(&apps, &refs, &strings(&["org.gnome.Calculator"])).is_empty());
+        assert_eq!(
+            runtimes_left_unused(
+                &apps,
+                &refs,
+                &strings(&["org.gnome.Calculator", "org.gnome.Weather"])
+            ),
+            ["org.gnome.Platform", "org.gnome.Platform.Locale"]
+        );
+        assert_eq!(
+            runtimes_left_unused(&apps, &refs, &strings(&["org.mozilla.firefox"])),
+            [
+                "org.freedesktop.Platform",
+                "org.freedesktop.Platform.GL.default"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_transaction() {
+        let output = "\
+Looking for updates…
+
+        ID                                   Branch      Op     Remote      Download
+ 1. [✓] org.gnome.Platform.Locale            46          u      flathub     < 18.5 kB (partial)
+ 2.     org.gnome.Platform                   46          u      flathub     < 276.5 MB
+ 3.     org.freedesktop.Platform.GL.default  23.08       i      flathub     < 156.2 MB
+ 4.     org.gnome.Calculator                 stable      r      flathub     -
+
+Proceed with these changes to the system installation? [Y/n]: n
+";
+        let ret = parse_transaction(output);
+        assert_eq!(
+            names(&ret.upgrades),
+            ["org.gnome.Platform.Locale", "org.gnome.Platform"]
+        );
+        assert_eq!(
+            names(&ret.installs),
+            ["org.freedesktop.Platform.GL.default"]
+        );
+        assert_eq!(names(&ret.removals), ["org.gnome.Calculator"]);
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let output = "
+Calculator - Perform arithmetic, scientific or financial calculations
+
+          ID: org.gnome.Calculator
+         Ref: app/org.gnome.Calculator/x86_64/stable
+        Arch: x86_64
+      Branch: stable
+     Version: 46.1
+     License: GPL-3.0+
+      Origin: flathub
+  Collection: org.flathub.Stable
+Installation: system
+   Installed: 7.1\u{a0}MB
+     Runtime: org.gnome.Platform/x86_64/46
+         Sdk: org.gnome.Sdk/x86_64/46
+";
+        let info = parse_info(output);
+        assert_eq!(
+            info.summary,
+            "Perform arithmetic, scientific or financial calculations"
+        );
+        assert_eq!(info.field("Version"), Some("46.1"));
+        assert_eq!(info.field("Runtime"), Some("org.gnome.Platform/x86_64/46"));
+        assert_eq!(
+            info.field("Installed").and_then(parse_size),
+            Some(7_100_000)
+        );
+        assert_eq!(info.field("Commit"), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("276.5\u{a0}MB"), Some(276_500_000));
+        assert_eq!(parse_size("276.5 MB"), Some(276_500_000));
+        assert_eq!(parse_size("18.5\u{a0}kB"), Some(18_500));
+        assert_eq!(parse_size("1.2\u{a0}GB"), Some(1_200_000_000));
+        assert_eq!(parse_size("512 bytes"), Some(512));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("12 MiB"), None);
+        assert_eq!(parse_size("unknown"), None);
+    }
+}
@@ -1,3 +1,417 @@
 pub mod apt;
 pub mod brew;
 pub mod flatpak;
+
+/// The names of all backends.
+pub const NAMES: [&str; 3] = ["apt", "brew", "flatpak"];
+
+/// Measure the disk usage of files and directories, like `du`.
+///
+/// Directories are walked without following symlinks, and hard links are
+/// only counted once. Paths that do not exist or cannot be read count as
+/// empty.
+///
+/// # Arguments
+/// + `paths` - The files and directories to measure.
+///
+/// # Returns
+/// The disk usage in bytes.
+pub(crate) fn disk_usage<P: AsRef<std::path::Path>>(paths: &[P]) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut seen = std::collections::HashSet::new();
+    let mut pending: Vec<std::path::PathBuf> =
+        paths.iter().map(|v| v.as_ref().to_path_buf()).collect();
+    let mut ret = 0;
+    while let Some(path) = pending.pop() {
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if !seen.insert((meta.dev(), meta.ino())) {
+            continue;
+        }
+        ret += meta.blocks() * 512;
+
+        if meta.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                pending.extend(entries.filter_map(|v| v.ok()).map(|v| v.path()));
+            }
+        }
+    }
+
+    ret
+}
+
+/// Compare two versions the way dpkg does.
+///
+/// This also orders most upstream version schemes sensibly, so it is used
+/// for backends without a comparison of their own.
+///
+/// # Arguments
+/// + `a` - The first version, as `[epoch:]upstream[-revision]`.
+/// + `b` - The second version.
+///
+/// # Returns
+/// The ordering of `a` relative to `b`.
+pub(crate) fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let split = |v: &str| -> (u64, String, String) {
+        let (epoch, rest) = match v.split_once(':') {
+            Some((e, r)) if e.bytes().all(|c| c.is_ascii_digit()) => (e.parse().unwrap_or(0), r),
+            _ => (0, v),
+        };
+        match rest.rsplit_once('-') {
+            Some((u, r)) => (epoch, u.to_string(), r.to_string()),
+            None => (epoch, rest.to_string(), String::new()),
+        }
+    };
+
+    let (ea, ua, ra) = split(a);
+    let (eb, ub, rb) = split(b);
+    ea.cmp(&eb)
+        .then_with(|| compare_version_part(&ua, &ub))
+        .then_with(|| compare_version_part(&ra, &rb))
+}
+
+/// Compare the upstream or revision part of two versions, see `verrevcmp`
+/// in dpkg.
+fn compare_version_part(a: &str, b: &str) -> std::cmp::Ordering {
+    // `~` sorts before everything, even the end of the part, and letters
+    // sort before other characters.
+    let order = |c: Option<&u8>| -> i32 {
+        match c {
+            None => 0,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => *c as i32,
+            Some(b'~') => -1,
+            Some(c) => *c as i32 + 256,
+        }
+    };
+    let digit = |c: Option<&u8>| c.is_some_and(|c| c.is_ascii_digit());
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !digit(a.get(i))) || (j < b.len() && !digit(b.get(j))) {
+            let (ac, bc) = (order(a.get(i)), order(b.get(j)));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+
+        let mut first_diff = std::cmp::Ordering::Equal;
+        while digit(a.get(i)) && digit(b.get(j)) {
+            if first_diff.is_eq() {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if digit(a.get(i)) {
+            return std::cmp::Ordering::Greater;
+        }
+        if digit(b.get(j)) {
+            return std::cmp::Ordering::Less;
+        }
+        if !first_diff.is_eq() {
+            return first_diff;
+        }
+    }
+
+    std::cmp::Ordering::Equal
+}
+
+/// Keep the changelog entries between two versions.
+///
+/// # Arguments
+/// + `entries` - The changelog entries.
+/// + `from` - The installed version, `None` if not installed.
+/// + `to` - The candidate version.
+///
+/// # Returns
+/// The entries newer than `from` up to `to`. If there are none, the entry
+/// of `to` alone.
+pub(crate) fn trim_changelog(
+    entries: Vec<crate::rpc::ChangelogEntry>,
+    from: Option<&str>,
+    to: &str,
+) -> Vec<crate::rpc::ChangelogEntry> {
+    use std::cmp::Ordering;
+
+    let ret: Vec<crate::rpc::ChangelogEntry> = entries
+        .iter()
+        .filter(|x| {
+            compare_versions(&x.version, to) != Ordering::Greater
+                && from.is_none_or(|v| compare_versions(&x.version, v) == Ordering::Greater)
+        })
+        .cloned()
+        .collect();
+    if !ret.is_empty() {
+        return ret;
+    }
+
+    entries.into_iter().filter(|x| x.version == to).collect()
+}
+
+/// Read a text file that may be gzip compressed.
+///
+/// # Arguments
+/// + `path` - The path of the file, compressed if it ends with `.gz`.
+///
+/// # Returns
+/// The content of the file.
+pub(crate) fn read_text(path: &std::path::Path) -> anyhow::Result<String> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path)?;
+    let mut ret = String::new();
+    if path.extension().is_some_and(|v| v == "gz") {
+        flate2::read::GzDecoder::new(file).read_to_string(&mut ret)?;
+    } else {
+        std::io::BufReader::new(file).read_to_string(&mut ret)?;
+    }
+
+    Ok(ret)
+}
+
+/// Read a repository signing key.
+///
+/// Keys are only downloaded over https, redirects included, as whoever can
+/// change the key can sign packages.
+///
+/// # Arguments
+/// + `key` - The key, as a local path or an `https://` URL.
+///
+/// # Returns
+/// The key as it was published, armored or binary.
+pub(crate) fn read_key(key: &str) -> anyhow::Result<Vec<u8>> {
+    let data = if key.starts_with("https://") {
+        let curl = std::process::Command::new("curl")
+            .args([
+                "-fsSL",
+                "--proto",
+                "=https",
+                "--proto-redir",
+                "=https",
+                "--",
+                key,
+            ])
+            .output()?;
+        if !curl.status.success() {
+            let output = String::from_utf8_lossy(&curl.stderr);
+            return Err(anyhow::anyhow!("{}", output.to_string()));
+        }
+        curl.stdout
+    } else if key.contains("://") {
+        return Err(anyhow::anyhow!(
+            "key '{}' must be an https:// URL or a local file.",
+            key
+        ));
+    } else {
+        std::fs::read(key).map_err(|e| anyhow::anyhow!("cannot read key '{}': {}", key, e))?
+    };
+
+    if !is_public_key(&data) {
+        return Err(anyhow::anyhow!("'{}' is not an OpenPGP public key.", key));
+    }
+    Ok(data)
+}
+
+/// Check that data starts like an OpenPGP public key.
+///
+/// Keys are written where everyone can read them, so this keeps other files
+/// from being copied there.
+///
+/// # Arguments
+/// + `data` - The key, armored or binary.
+///
+/// # Returns
+/// `true` if it is an armored public key block or starts with a public key
+/// packet.
+fn is_public_key(data: &[u8]) -> bool {
+    if data
+        .trim_ascii_start()
+        .starts_with(b"-----BEGIN PGP PUBLIC KEY BLOCK-----")
+    {
+        return true;
+    }
+    match data.first() {
+        // Old format packet with tag 6, any length type.
+        Some(v) if v & 0xc0 == 0x80 => (v >> 2) & 0x0f == 6,
+        // New format packet with tag 6.
+        Some(v) => *v == 0xc6,
+        None => false,
+    }
+}
+
+/// Build a dependency tree from the direct dependencies of each package.
+///
+/// Each package is only expanded the first time it appears, which keeps the
+/// tree finite with cycles and small with shared dependencies.
+///
+/// # Arguments
+/// + `root` - The package at the top of the tree.
+/// + `direct` - The direct dependencies of each package, without their own.
+///
+/// # Returns
+/// The dependencies of the root.
+pub(crate) fn dependency_tree(
+    root: &str,
+    direct: &std::collections::HashMap<String, Vec<crate::rpc::Dependency>>,
+) -> Vec<crate::rpc::Dependency> {
+    fn expand(
+        name: &str,
+        direct: &std::collections::HashMap<String, Vec<crate::rpc::Dependency>>,
+        seen: &mut std::collections::HashSet<String>,
+    ) -> Vec<crate::rpc::Dependency> {
+        let Some(deps) = direct.get(name) else {
+            return Vec::new();
+        };
+
+        let mut ret = Vec::new();
+        for dep in deps {
+            let mut dep = dep.clone();
+            if seen.insert(dep.name.clone()) {
+                dep.deps = expand(&dep.name, direct, seen);
+            }
+            ret.push(dep);
+        }
+        ret
+    }
+
+    let mut seen = std::collections::HashSet::from([root.to_string()]);
+    expand(root, direct, &mut seen)
+}
+
+/// List the files below a directory, like `find dir ! -type d`.
+///
+/// Symlinks are listed but not followed.
+///
+/// # Arguments
+/// + `dir` - The directory.
+///
+/// # Returns
+/// The files in order, empty if the directory cannot be read.
+pub(crate) fn list_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut ret = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if !meta.is_dir() {
+            ret.push(path);
+        } else if let Ok(entries) = std::fs::read_dir(&path) {
+            pending.extend(entries.filter_map(|v| v.ok()).map(|v| v.path()));
+        }
+    }
+
+    ret.sort();
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering::{Equal, Greater, Less};
+
+    #[test]
+    fn test_compare_versions() {
+        // From dpkg's lib/dpkg/t/t-version.c and apt's version tests.
+        let cases = [
+            ("0", "0", Equal),
+            ("0-00", "00-0", Equal),
+            ("1:2-3", "1:2-3", Equal),
+            ("0:1.18.36", "1.18.36", Equal),
+            ("0-0", "1:0-0", Less),
+            ("2:0-0", "1:0-0", Greater),
+            ("1:0.4", "10.3", Greater),
+            ("0:a-0", "0:b-0", Less),
+            ("0:0-a", "0:0-b", Less),
+            ("7.6p2-4", "7.6-0", Greater),
+            ("1.0.3-3", "1.0-1", Greater),
+            ("1.3", "1.2.2-2", Greater),
+            ("0-pre", "0-pree", Less),
+            ("1.1.6r2-2", "1.1.6r-1", Greater),
+            ("2.6b2-1", "2.6b-2", Greater),
+            ("98.1p5-1", "98.1-pre2-b6-2", Less),
+            ("0.4a6-2", "0.4-1", Greater),
+            ("1:3.0.5-2", "1:3.0.5.1", Less),
+            ("1:1.25-4", "1:1.25-8", Less),
+            ("9:1.18.36:5.4-20", "10:0.5.1-22", Less),
+            ("9:1.18.36:5.4-20", "9:1.18.36:5.5-1", Less),
+            ("1.18.36-0.17.35-18", "1.18.36-19", Greater),
+            ("1:1.2.13-3", "1:1.2.13-3.1", Less),
+            ("2.0.7pre1-4", "2.0.7r-1", Less),
+            // `~` sorts before everything, even the end of the part.
+            ("1.0~rc1", "1.0", Less),
+            ("1.0~rc1-1", "1.0-1", Less),
+            ("1.0~~", "1.0~~a", Less),
+            ("1.0~~a", "1.0~", Less),
+            ("1.0~", "1.0", Less),
+            ("1.0", "1.0a", Less),
+            ("1.0-1~bpo1", "1.0-1", Less),
+            // Letters sort before other characters.
+            ("1.0a", "1.0+", Less),
+            ("1.0+", "1.0.", Less),
+            ("1.0+b1", "1.0", Greater),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(compare_versions(a, b), expected, "{} vs {}", a, b);
+            assert_eq!(compare_versions(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_trim_changelog() {
+        let entry = |version: &str| crate::rpc::ChangelogEntry {
+            version: version.to_string(),
+            date: String::new(),
+            text: String::new(),
+        };
+        let entries = vec![
+            entry("1.3-1"),
+            entry("1.2-1"),
+            entry("1.1-1"),
+            entry("1.0-1"),
+        ];
+
+        let versions = |entries: Vec<crate::rpc::ChangelogEntry>| -> Vec<String> {
+            entries.into_iter().map(|x| x.version).collect()
+        };
+        assert_eq!(
+            versions(trim_changelog(entries.clone(), Some("1.0-1"), "1.2-1")),
+            ["1.2-1", "1.1-1"]
+        );
+        assert_eq!(
+            versions(trim_changelog(entries.clone(), None, "1.1-1")),
+            ["1.1-1", "1.0-1"]
+        );
+        assert_eq!(
+            versions(trim_changelog(entries, Some("1.3-1"), "1.3-1")),
+            ["1.3-1"]
+        );
+    }
+
+    #[test]
+    fn test_is_public_key() {
+        assert!(is_public_key(b"\n-----BEGIN PGP PUBLIC KEY BLOCK-----\n"));
+        // Old format packet, tag 6 with a two byte length.
+        assert!(is_public_key(&[0x99, 0x01, 0x0d, 0x04]));
+        // New format packet, tag 6.
+        assert!(is_public_key(&[0xc6, 0x33, 0x04]));
+        // Old format packet, tag 2 (a signature).
+        assert!(!is_public_key(&[0x89, 0x02, 0x33]));
+        assert!(!is_public_key(b"<html>Not found</html>"));
+        assert!(!is_public_key(b""));
+    }
+}
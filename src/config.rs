@@ -0,0 +1,188 @@
+use serde::Deserialize;
+
+/// The default location of the configuration file.
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/upm/config.toml";
+
+/// The configuration of upm.
+///
+/// ```toml
+/// backend_priority = ["apt", "flatpak", "brew"]
+/// holds = ["openssl", "flatpak:org.gimp.GIMP"]
+///
+/// [upgrade.apt]
+/// strategy = "full-upgrade"
+///
+/// [upgrade.brew]
+/// greedy = true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Backends in order of preference when a package is provided by more than
+    /// one of them. Backends not listed come last.
+    #[serde(default = "default_backend_priority")]
+    pub backend_priority: Vec<String>,
+
+    /// Packages that `outdated` and `upgrade` leave alone, as `name` for every
+    /// backend or `backend:name` for one.
+    #[serde(default)]
+    pub holds: Vec<String>,
+
+    /// How each backend upgrades, unless overridden on the command line.
+    #[serde(default)]
+    pub upgrade: UpgradeConfig,
+}
+
+/// The default upgrade options of each backend.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpgradeConfig {
+    #[serde(default)]
+    pub apt: crate::rpc::AptUpgradeOptions,
+
+    #[serde(default)]
+    pub flatpak: crate::rpc::FlatpakUpgradeOptions,
+
+    #[serde(default)]
+    pub brew: crate::rpc::BrewUpgradeOptions,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backend_priority: default_backend_priority(),
+            holds: Vec::new(),
+            upgrade: UpgradeConfig::default(),
+        }
+    }
+}
+
+fn default_backend_priority() -> Vec<String> {
+    vec!["apt".to_string(), "flatpak".to_string(), "brew".to_string()]
+}
+
+impl Config {
+    /// Load the configuration from a file.
+    ///
+    /// # Arguments
+    /// + `path` - The path of the configuration file.
+    ///
+    /// # Returns
+    /// The configuration, or the default one if the file does not exist.
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Config> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let config: Config = toml::from_str(&data)
+            .map_err(|e| anyhow::anyhow!("invalid config '{}': {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Get the rank of a backend, lower is preferred.
+    ///
+    /// # Arguments
+    /// + `backend` - The name of backend.
+    ///
+    /// # Returns
+    /// The rank of the backend.
+    pub fn backend_rank(&self, backend: &str) -> usize {
+        self.backend_priority
+            .iter()
+            .position(|v| v == backend)
+            .unwrap_or(self.backend_priority.len())
+    }
+
+    /// Get the packages held for a backend.
+    ///
+    /// # Arguments
+    /// + `backend` - The name of backend.
+    ///
+    /// # Returns
+    /// The names of held packages.
+    pub fn holds_for(&self, backend: &str) -> Vec<String> {
+        let mut ret = Vec::new();
+        for hold in self.holds.iter() {
+            match hold.split_once(':') {
+                Some((k, name)) if k == backend => ret.push(name.to_string()),
+                Some((k, _)) if crate::backend::NAMES.contains(&k) => {}
+                _ => ret.push(hold.clone()),
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config: Config = toml::from_str(
+            r#"
+backend_priority = ["flatpak", "apt"]
+holds = ["openssl"]
+
+[upgrade.apt]
+strategy = "full-upgrade"
+
+[upgrade.brew]
+greedy = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.backend_priority, ["flatpak", "apt"]);
+        assert_eq!(
+            config.upgrade.apt.strategy,
+            crate::rpc::AptStrategy::FullUpgrade
+        );
+        assert!(config.upgrade.brew.greedy);
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.backend_priority, ["apt", "flatpak", "brew"]);
+        assert!(toml::from_str::<Config>("hold = [\"openssl\"]").is_err());
+    }
+
+    #[test]
+    fn test_backend_rank() {
+        let config = Config {
+            backend_priority: vec!["flatpak".to_string(), "apt".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.backend_rank("flatpak"), 0);
+        assert_eq!(config.backend_rank("apt"), 1);
+        assert_eq!(config.backend_rank("brew"), 2);
+    }
+
+    #[test]
+    fn test_holds_for() {
+        let config = Config {
+            holds: [
+                "openssl",
+                "flatpak:org.gimp.GIMP",
+                "apt:linux-image-amd64",
+                "brew:homebrew/cask/firefox",
+                "python@3.12:x",
+            ]
+            .iter()
+            .map(|v| v.to_string())
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.holds_for("apt"),
+            ["openssl", "linux-image-amd64", "python@3.12:x"]
+        );
+        assert_eq!(
+            config.holds_for("flatpak"),
+            ["openssl", "org.gimp.GIMP", "python@3.12:x"]
+        );
+        assert_eq!(
+            config.holds_for("brew"),
+            ["openssl", "homebrew/cask/firefox", "python@3.12:x"]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+
+/// The default location of the history database.
+pub const DEFAULT_HISTORY_PATH: &str = "/var/lib/upm/history.jsonl";
+
+/// A transaction that changed installed packages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    /// The id of the transaction, assigned when it is recorded.
+    pub id: u64,
+
+    /// The time the transaction finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// The user who ran the transaction, filled in when it is recorded.
+    pub user: String,
+
+    /// The method of the request, e.g. `upgrade`, `install` or `uninstall`.
+    pub method: String,
+
+    /// The name of backend.
+    pub backend: String,
+
+    /// The error message if the transaction failed.
+    pub error: Option<String>,
+
+    /// The packages that changed.
+    pub changes: Vec<Change>,
+
+    /// Whether the root worker ran the transaction and recorded it itself.
+    /// Only these are rolled back with root privilege.
+    #[serde(default)]
+    pub by_root: bool,
+}
+
+/// A package that changed in a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// The name of package.
+    pub name: String,
+
+    /// The architecture of package, empty if not applicable.
+    #[serde(default)]
+    pub arch: String,
+
+    /// The version before the transaction, `None` if it was installed by it.
+    pub old_version: Option<String>,
+
+    /// The version after the transaction, `None` if it was removed by it.
+    pub new_version: Option<String>,
+
+    /// The backend specific revision before the transaction, e.g. the flatpak
+    /// commit. Empty if the backend has none.
+    pub old_revision: String,
+}
+
+/// The history database, one JSON transaction per line.
+pub struct History {
+    path: std::path::PathBuf,
+}
+
+impl History {
+    /// Open the history database.
+    ///
+    /// # Arguments
+    /// + `path` - The path of the database.
+    ///
+    /// # Returns
+    /// The history database.
+    pub fn new(path: &std::path::Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Load every recorded transaction.
+    ///
+    /// # Returns
+    /// The transactions, oldest first. Empty if nothing was recorded yet.
+    pub fn load(&self) -> anyhow::Result<Vec<Transaction>> {
+        let data = match std::fs::read_to_string(&self.path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ret = Vec::new();
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let transaction: Transaction = serde_json::from_str(line).map_err(|e| {
+                anyhow::anyhow!("invalid history '{}:{}': {}", self.path.display(), i + 1, e)
+            })?;
+            ret.push(transaction);
+        }
+
+        Ok(ret)
+    }
+
+    /// Find a transaction.
+    ///
+    /// # Arguments
+    /// + `id` - The id of the transaction.
+    ///
+    /// # Returns
+    /// The transaction, `None` if there is none with this id.
+    pub fn find(&self, id: u64) -> anyhow::Result<Option<Transaction>> {
+        Ok(self.load()?.into_iter().find(|x| x.id == id))
+    }
+
+    /// Record a transaction.
+    ///
+    /// # Arguments
+    /// + `transaction` - The transaction, its id is assigned here.
+    ///
+    /// # Returns
+    /// The id of the recorded transaction.
+    pub fn append(&self, mut transaction: Transaction) -> anyhow::Result<u64> {
+        use std::io::Write;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // Workers may record at the same time, the lock keeps the ids unique.
+        let mut file = nix::fcntl::Flock::lock(file, nix::fcntl::FlockArg::LockExclusive)
+            .map_err(|(_, e)| e)?;
+
+        transaction.id = self.load()?.last().map_or(1, |x| x.id + 1);
+        writeln!(*file, "{}", serde_json::to_string(&transaction)?)?;
+
+        Ok(transaction.id)
+    }
+}
+
+/// Work out what changed between two lists of installed packages.
+///
+/// # Arguments
+/// + `before` - The packages installed before the transaction.
+/// + `after` - The packages installed after the transaction.
+///
+/// # Returns
+/// The packages that were installed, removed or changed version.
+pub fn diff(
+    before: &[crate::rpc::InstalledItem],
+    after: &[crate::rpc::InstalledItem],
+) -> Vec<Change> {
+    let find = |items: &[crate::rpc::InstalledItem], item: &crate::rpc::InstalledItem| {
+        items
+            .iter()
+            .find(|x| x.name == item.name && x.arch == item.arch)
+            .cloned()
+    };
+
+    let mut ret = Vec::new();
+    for old in before.iter() {
+        match find(after, old) {
+            Some(new) if new.version == old.version && new.revision == old.revision => {}
+            new => ret.push(Change {
+                name: old.name.clone(),
+                arch: old.arch.clone(),
+                old_version: Some(old.version.clone()),
+                new_version: new.map(|x| x.version),
+                old_revision: old.revision.clone(),
+            }),
+        }
+    }
+    for new in after.iter() {
+        if find(before, new).is_none() {
+            ret.push(Change {
+                name: new.name.clone(),
+                arch: new.arch.clone(),
+                old_version: None,
+                new_version: Some(new.version.clone()),
+                old_revision: String::new(),
+            });
+        }
+    }
+
+    ret
+}
+
+/// Format a timestamp as UTC date and time.
+///
+/// # Arguments
+/// + `timestamp` - Seconds since the Unix epoch.
+///
+/// # Returns
+/// The time as `YYYY-MM-DD hh:mm:ss`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(
+        name: &str,
+        arch: &str,
+        version: &str,
+        revision: &str,
+    ) -> crate::rpc::InstalledItem {
+        crate::rpc::InstalledItem {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: arch.to_string(),
+            backend: "apt".to_string(),
+            origin: String::new(),
+            manual: true,
+            revision: revision.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = [
+            installed("curl", "amd64", "7.88.1-10+deb12u5", ""),
+            installed("libc6", "amd64", "2.36-9", ""),
+            installed("libc6", "i386", "2.36-9", ""),
+            installed("nano", "amd64", "7.2-1", ""),
+            installed("org.gnome.Calculator", "x86_64", "46.1", "abc123"),
+        ];
+        let after = [
+            installed("curl", "amd64", "7.88.1-10+deb12u8", ""),
+            installed("libc6", "amd64", "2.36-9", ""),
+            installed("org.gnome.Calculator", "x86_64", "46.1", "def456"),
+            installed("htop", "amd64", "3.2.2-2", ""),
+        ];
+
+        let changes: Vec<(String, Option<String>, Option<String>, String)> = diff(&before, &after)
+            .into_iter()
+            .map(|x| {
+                (
+                    format!("{}:{}", x.name, x.arch),
+                    x.old_version,
+                    x.new_version,
+                    x.old_revision,
+                )
+            })
+            .collect();
+        let some = |v: &str| Some(v.to_string());
+        assert_eq!(
+            changes,
+            [
+                (
+                    "curl:amd64".to_string(),
+                    some("7.88.1-10+deb12u5"),
+                    some("7.88.1-10+deb12u8"),
+                    String::new()
+                ),
+                (
+                    "libc6:i386".to_string(),
+                    some("2.36-9"),
+                    None,
+                    String::new()
+                ),
+                ("nano:amd64".to_string(), some("7.2-1"), None, String::new()),
+                (
+                    "org.gnome.Calculator:x86_64".to_string(),
+                    some("46.1"),
+                    some("46.1"),
+                    "abc123".to_string()
+                ),
+                (
+                    "htop:amd64".to_string(),
+                    None,
+                    some("3.2.2-2"),
+                    String::new()
+                ),
+            ]
+        );
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14 22:13:20");
+        assert_eq!(format_timestamp(4107542399), "2100-02-28 23:59:59");
+    }
+}
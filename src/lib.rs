@@ -1,4 +1,8 @@
 pub mod backend;
+pub mod config;
+pub mod history;
+pub mod manifest;
+pub mod policy;
 pub mod rpc;
 
 #[derive(Debug, Clone, Copy)]
@@ -11,6 +15,106 @@ pub struct MethodPrivilege {
 
     /// The upgrade() method requires root privilege.
     pub upgrade: bool,
+
+    /// The install() method requires root privilege.
+    pub install: bool,
+
+    /// The uninstall() method requires root privilege.
+    pub uninstall: bool,
+
+    /// The resolve() method requires root privilege.
+    pub resolve: bool,
+
+    /// The search() method requires root privilege.
+    pub search: bool,
+
+    /// The info() method requires root privilege.
+    pub info: bool,
+
+    /// The list_installed() method requires root privilege.
+    pub list_installed: bool,
+
+    /// The simulate() method requires root privilege.
+    pub simulate: bool,
+
+    /// The hold() and unhold() methods require root privilege.
+    pub hold: bool,
+
+    /// The holds() method requires root privilege.
+    pub holds: bool,
+
+    /// The clean() method requires root privilege.
+    pub clean: bool,
+
+    /// The orphans() method requires root privilege.
+    pub orphans: bool,
+
+    /// The rollback() method requires root privilege.
+    pub rollback: bool,
+
+    /// The changelog() method requires root privilege.
+    pub changelog: bool,
+
+    /// The repos() method requires root privilege.
+    pub repos: bool,
+
+    /// The repo_add(), repo_remove() and repo_enable() methods require root
+    /// privilege.
+    pub repo_edit: bool,
+
+    /// The deps() method requires root privilege.
+    pub deps: bool,
+
+    /// The owns() method requires root privilege.
+    pub owns: bool,
+
+    /// The files() method requires root privilege.
+    pub files: bool,
+
+    /// The verify() method requires root privilege.
+    pub verify: bool,
+}
+
+impl MethodPrivilege {
+    /// Check whether a method requires root privilege.
+    ///
+    /// # Arguments
+    /// + `method` - The method of the request.
+    ///
+    /// # Returns
+    /// `true` if the method requires root privilege, or is unknown.
+    pub fn requires_root(&self, method: &str) -> bool {
+        use rpc::Request;
+
+        match method {
+            rpc::Update::METHOD => self.update,
+            rpc::Outdated::METHOD => self.outdated,
+            rpc::Upgrade::METHOD => self.upgrade,
+            rpc::Install::METHOD => self.install,
+            rpc::Uninstall::METHOD => self.uninstall,
+            rpc::Resolve::METHOD => self.resolve,
+            rpc::Search::METHOD => self.search,
+            rpc::Info::METHOD => self.info,
+            rpc::List::METHOD => self.list_installed,
+            rpc::Simulate::METHOD => self.simulate,
+            rpc::Hold::METHOD | rpc::Unhold::METHOD => self.hold,
+            rpc::Holds::METHOD => self.holds,
+            rpc::Clean::METHOD => self.clean,
+            rpc::Orphans::METHOD => self.orphans,
+            rpc::Rollback::METHOD => self.rollback,
+            rpc::Changelog::METHOD => self.changelog,
+            rpc::RepoList::METHOD => self.repos,
+            rpc::RepoAdd::METHOD
+            | rpc::RepoRemove::METHOD
+            | rpc::RepoEnable::METHOD
+            | rpc::RepoDisable::METHOD => self.repo_edit,
+            rpc::Deps::METHOD => self.deps,
+            rpc::Owns::METHOD => self.owns,
+            rpc::Files::METHOD => self.files,
+            rpc::Verify::METHOD => self.verify,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -41,9 +145,263 @@ pub trait UpmBackend {
 
     /// Upgrade packages.
     ///
+    /// # Arguments
+    /// + `options` - Which packages to upgrade.
+    ///
+    /// # Returns
+    /// The packages left out because they still need a download.
+    fn upgrade(&self, options: &rpc::UpgradeOptions) -> anyhow::Result<rpc::UpgradeResult>;
+
+    /// Install packages.
+    ///
+    /// # Arguments
+    /// + `pkgs` - The packages to install.
+    ///
+    /// # Returns
+    /// `Ok(())` if the install is successful, otherwise `Err(std::io::Error)`.
+    fn install(&self, pkgs: &[rpc::PackageSpec]) -> anyhow::Result<()>;
+
+    /// Uninstall packages.
+    ///
+    /// # Arguments
+    /// + `pkgs` - The packages to uninstall.
+    /// + `options` - How to uninstall. With `dry_run` set nothing is removed.
+    ///
+    /// # Returns
+    /// The packages that were (or would be) removed.
+    fn uninstall(
+        &self,
+        pkgs: &[rpc::PackageSpec],
+        options: &rpc::UninstallOptions,
+    ) -> anyhow::Result<rpc::UninstallResult>;
+
+    /// Find the packages this backend provides for a name.
+    ///
+    /// # Arguments
+    /// + `name` - The name given by the user.
+    ///
+    /// # Returns
+    /// The matching packages with their candidate version, empty if none.
+    fn resolve(&self, name: &str) -> anyhow::Result<Vec<rpc::PackageSpec>>;
+
+    /// Search packages.
+    ///
+    /// # Arguments
+    /// + `query` - The search keyword.
+    ///
+    /// # Returns
+    /// The packages matching the keyword.
+    fn search(&self, query: &str) -> anyhow::Result<Vec<rpc::SearchItem>>;
+
+    /// Get the details of a package.
+    ///
+    /// # Arguments
+    /// + `name` - The name of package.
+    ///
+    /// # Returns
+    /// The details of the package, `None` if the backend does not know it.
+    fn info(&self, name: &str) -> anyhow::Result<Option<rpc::PackageInfo>>;
+
+    /// List installed packages.
+    ///
     /// # Returns
-    /// `Ok(())` if the upgrade is successful, otherwise `Err(std::io::Error)`.
-    fn upgrade(&self) -> anyhow::Result<()>;
+    /// A list of installed packages.
+    fn list_installed(&self) -> anyhow::Result<Vec<rpc::InstalledItem>>;
+
+    /// Work out what an upgrade would do without changing anything.
+    ///
+    /// # Arguments
+    /// + `options` - Which packages to upgrade.
+    ///
+    /// # Returns
+    /// The planned transaction.
+    fn simulate(&self, options: &rpc::UpgradeOptions) -> anyhow::Result<rpc::SimulateResult>;
+
+    /// Keep packages at their current version.
+    ///
+    /// # Arguments
+    /// + `pkgs` - The names of packages.
+    ///
+    /// # Returns
+    /// `Ok(())` if the hold is successful, otherwise `Err(std::io::Error)`.
+    fn hold(&self, pkgs: &[String]) -> anyhow::Result<()>;
+
+    /// Allow held packages to be upgraded again.
+    ///
+    /// # Arguments
+    /// + `pkgs` - The names of packages.
+    ///
+    /// # Returns
+    /// `Ok(())` if the unhold is successful, otherwise `Err(std::io::Error)`.
+    fn unhold(&self, pkgs: &[String]) -> anyhow::Result<()>;
+
+    /// List held packages.
+    ///
+    /// # Returns
+    /// The names of held packages.
+    fn holds(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Remove cached package files.
+    ///
+    /// # Arguments
+    /// + `options` - What to clean.
+    ///
+    /// # Returns
+    /// The bytes reclaimed, or that would be reclaimed on a dry run.
+    fn clean(&self, options: &rpc::CleanOptions) -> anyhow::Result<rpc::CleanResult>;
+
+    /// List packages that were installed as a dependency and are no longer
+    /// required by anything. Held packages are left out.
+    ///
+    /// # Returns
+    /// The orphaned packages.
+    fn orphans(&self) -> anyhow::Result<Vec<rpc::RemoveItem>>;
+
+    /// Undo the changes of a transaction, reinstalling previous versions
+    /// where possible.
+    ///
+    /// # Arguments
+    /// + `changes` - The changes to undo.
+    ///
+    /// # Returns
+    /// `Ok(())` if the rollback is successful, otherwise `Err(std::io::Error)`.
+    fn rollback(&self, changes: &[history::Change]) -> anyhow::Result<()>;
+
+    /// Get the changes between the installed and the candidate version.
+    ///
+    /// # Arguments
+    /// + `name` - The name of package.
+    ///
+    /// # Returns
+    /// The changelog, `None` if the package is unknown.
+    fn changelog(&self, name: &str) -> anyhow::Result<Option<rpc::PackageChangelog>>;
+
+    /// List the configured repositories.
+    ///
+    /// # Returns
+    /// The repositories, enabled or not.
+    fn repos(&self) -> anyhow::Result<Vec<rpc::Repository>>;
+
+    /// Add a repository.
+    ///
+    /// # Arguments
+    /// + `repo` - The repository to add.
+    /// + `options` - The backend specific details of the repository.
+    ///
+    /// # Returns
+    /// `Ok(())` if the repository was added, otherwise `Err(std::io::Error)`.
+    fn repo_add(&self, repo: &rpc::Repository, options: &rpc::RepoAddOptions)
+        -> anyhow::Result<()>;
+
+    /// Remove a repository.
+    ///
+    /// # Arguments
+    /// + `name` - The name of repository.
+    ///
+    /// # Returns
+    /// `Ok(())` if the repository was removed, otherwise `Err(std::io::Error)`.
+    fn repo_remove(&self, name: &str) -> anyhow::Result<()>;
+
+    /// Enable or disable a repository.
+    ///
+    /// # Arguments
+    /// + `name` - The name of repository.
+    /// + `enabled` - Whether the backend should use the repository.
+    ///
+    /// # Returns
+    /// `Ok(())` if the repository was changed, otherwise `Err(std::io::Error)`.
+    fn repo_enable(&self, name: &str, enabled: bool) -> anyhow::Result<()>;
+
+    /// Get the dependencies, or the reverse dependencies, of a package.
+    ///
+    /// # Arguments
+    /// + `name` - The name of package.
+    /// + `options` - Which dependencies to find.
+    ///
+    /// # Returns
+    /// The dependency tree below the package, `None` if the package is
+    /// unknown.
+    fn deps(
+        &self,
+        name: &str,
+        options: &rpc::DepsOptions,
+    ) -> anyhow::Result<Option<Vec<rpc::Dependency>>>;
+
+    /// Find the packages that installed a file.
+    ///
+    /// # Arguments
+    /// + `path` - The absolute path of the file.
+    ///
+    /// # Returns
+    /// The installed packages that own the file, empty if none does.
+    fn owns(&self, path: &std::path::Path) -> anyhow::Result<Vec<rpc::FileOwner>>;
+
+    /// List the files a package installed.
+    ///
+    /// # Arguments
+    /// + `name` - The name of package.
+    ///
+    /// # Returns
+    /// The files, without directories. `None` if the package is not
+    /// installed.
+    fn files(&self, name: &str) -> anyhow::Result<Option<Vec<std::path::PathBuf>>>;
+
+    /// Check installed files against the package metadata.
+    ///
+    /// # Arguments
+    /// + `pkgs` - The names of packages, empty for every installed package.
+    ///
+    /// # Returns
+    /// The modified, missing and unexpected files.
+    fn verify(&self, pkgs: &[String]) -> anyhow::Result<Vec<rpc::FileIssue>>;
+}
+
+/// Work out which packages a targeted upgrade should touch.
+///
+/// # Arguments
+/// + `backend` - The backend to upgrade.
+/// + `options` - Which packages to upgrade.
+///
+/// # Returns
+/// `None` if everything should be upgraded, otherwise the packages to upgrade.
+pub fn upgrade_targets(
+    backend: &dyn UpmBackend,
+    options: &rpc::UpgradeOptions,
+) -> anyhow::Result<Option<Vec<String>>> {
+    if options.pkgs.is_empty() && options.exclude.is_empty() && !options.security_only {
+        return Ok(None);
+    }
+
+    // Without an explicit list, exclusions apply to everything a plain upgrade
+    // would install. Held, kept back and phased updates stay where they are.
+    let outdated = if options.pkgs.is_empty() || options.security_only {
+        backend.outdated()?.pkgs
+    } else {
+        Vec::new()
+    };
+    let pkgs = if options.pkgs.is_empty() {
+        outdated
+            .iter()
+            .filter(|x| x.status == rpc::OutdateStatus::Upgradable)
+            .filter(|x| !options.security_only || x.security)
+            .map(|x| x.name.clone())
+            .collect()
+    } else if options.security_only {
+        options
+            .pkgs
+            .iter()
+            .filter(|x| outdated.iter().any(|y| y.name == **x && y.security))
+            .cloned()
+            .collect()
+    } else {
+        options.pkgs.clone()
+    };
+
+    let pkgs = pkgs
+        .into_iter()
+        .filter(|x| !options.exclude.contains(x))
+        .collect();
+    Ok(Some(pkgs))
 }
 
 /// Check if the current user is root.
@@ -51,7 +409,7 @@ pub trait UpmBackend {
 /// # Returns
 /// `Ok(())` if the current user is root, otherwise `Err(std::io::Error)`.
 pub fn require_privilege() -> anyhow::Result<()> {
-    if nix::unistd::geteuid().is_root() == false {
+    if !nix::unistd::geteuid().is_root() {
         return Err(anyhow::anyhow!("This command requires root privilege."));
     }
 
@@ -1,4 +1,5 @@
 use clap::{Args, Parser, Subcommand};
+use upm::rpc::Request;
 
 #[derive(Debug, Parser)]
 #[command(version, about)]
@@ -13,16 +14,407 @@ struct UpmArgs {
 #[derive(Debug, Subcommand)]
 enum ActionMode {
     Update(BackendName),
-    Outdated(BackendName),
-    Upgrade(BackendName),
+    Outdated(OutdatedArgs),
+    Upgrade(UpgradeArgs),
 
     Install(PackageName),
-    Uninstall(PackageName),
+    Uninstall(UninstallArgs),
+    Search(SearchArgs),
+    Info(InfoArgs),
+    List(ListArgs),
+    Changelog(ChangelogArgs),
+    Deps(DepsArgs),
+    Rdeps(DepsArgs),
+    Owns(OwnsArgs),
+    Files(InfoArgs),
+    Verify(VerifyArgs),
+
+    Hold(PackageName),
+    Unhold(PackageName),
+    Holds(BackendName),
+
+    Clean(CleanArgs),
+    Autoremove(AutoremoveArgs),
+
+    History(HistoryArgs),
+    Rollback(RollbackArgs),
+
+    Repo(RepoArgs),
+    Export(ExportArgs),
+    Import(ImportArgs),
+    Diff(DiffArgs),
+    Converge(ConvergeArgs),
 }
 
 #[derive(Debug, Args)]
 struct PackageName {
-    #[arg(help = "The name of the package")]
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(
+        required = true,
+        help = "The name of the package, as `name`, `name=version` or `backend:name`"
+    )]
+    name: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct UninstallArgs {
+    #[command(flatten)]
+    pkg: PackageName,
+
+    #[arg(long, help = "Also remove configuration and data")]
+    purge: bool,
+
+    #[arg(long, help = "Remove even if other packages depend on it")]
+    force: bool,
+
+    #[arg(long, help = "Also remove dependencies that are no longer needed")]
+    autoremove: bool,
+
+    #[arg(short, long, help = "Do not ask for confirmation")]
+    yes: bool,
+}
+
+#[derive(Debug, Args)]
+struct SearchArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(help = "The search keyword")]
+    query: String,
+}
+
+#[derive(Debug, Args)]
+struct InfoArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+
+    #[arg(help = "The name of the package, as `name` or `backend:name`")]
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct ListArgs {
+    #[arg(short, long, help = "Only list packages of this backend")]
+    backend: Option<String>,
+
+    #[arg(long, help = "Only list packages from this repository or remote")]
+    origin: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "auto",
+        help = "Only list explicitly installed packages"
+    )]
+    manual: bool,
+
+    #[arg(long, help = "Only list packages installed as a dependency")]
+    auto: bool,
+
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct ChangelogArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(help = "The name of the package, as `name` or `backend:name`")]
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct OutdatedArgs {
+    #[arg(long, help = "Only list security updates")]
+    security: bool,
+
+    #[arg(long, help = "Show the changes of each update")]
+    changelog: bool,
+
+    #[arg(help = "The name of the backend")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct UpgradeArgs {
+    #[arg(long, help = "Show what would be done without changing anything")]
+    dry_run: bool,
+
+    #[arg(long, help = "Do not upgrade this package, may be repeated")]
+    exclude: Vec<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "apt_strategy",
+        help = "Also upgrade packages that need new dependencies or removals, same as --apt-strategy full-upgrade"
+    )]
+    full_upgrade: bool,
+
+    #[arg(
+        long,
+        value_parser = ["upgrade", "full-upgrade", "dist-upgrade"],
+        help = "The apt-get command that upgrades everything"
+    )]
+    apt_strategy: Option<String>,
+
+    #[arg(long, help = "Let apt-get upgrade install new dependencies")]
+    with_new_pkgs: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "system",
+        help = "Only upgrade the flatpak user installation"
+    )]
+    user: bool,
+
+    #[arg(long, help = "Only upgrade the flatpak system installation")]
+    system: bool,
+
+    #[arg(long, help = "Also upgrade brew casks that update themselves")]
+    greedy: bool,
+
+    #[arg(long, conflicts_with = "cask", help = "Only upgrade brew formulae")]
+    formula: bool,
+
+    #[arg(long, help = "Only upgrade brew casks")]
+    cask: bool,
+
+    #[arg(
+        long = "arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        help = "Pass an argument to the package manager as is, may be repeated, needs the name of the backend"
+    )]
+    extra_args: Vec<String>,
+
+    #[arg(long, help = "Only upgrade packages with security fixes")]
+    security_only: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "offline"],
+        help = "Only download the packages, to upgrade later with --offline"
+    )]
+    download_only: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "dry_run",
+        help = "Only upgrade packages that are already downloaded"
+    )]
+    offline: bool,
+
+    #[arg(help = "The name of the backend and/or the packages to upgrade")]
+    name: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct CleanArgs {
+    #[arg(long, help = "Remove every cached package, not only outdated ones")]
+    all: bool,
+
+    #[arg(long, help = "Estimate the space that would be reclaimed")]
+    dry_run: bool,
+
+    #[arg(help = "The name of the backend")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct AutoremoveArgs {
+    #[arg(long, help = "Only list the packages that would be removed")]
+    dry_run: bool,
+
+    #[arg(short, long, help = "Do not ask for confirmation")]
+    yes: bool,
+
+    #[arg(help = "The name of the backend")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct HistoryArgs {
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+
+    #[arg(help = "Show the changes of this transaction")]
+    id: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+struct RollbackArgs {
+    #[arg(short, long, help = "Do not ask for confirmation")]
+    yes: bool,
+
+    #[arg(help = "The transaction to undo")]
+    id: u64,
+}
+
+#[derive(Debug, Args)]
+struct DepsArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(long, help = "Only follow installed packages")]
+    installed: bool,
+
+    #[arg(long, help = "List every package once instead of a tree")]
+    flat: bool,
+
+    #[arg(help = "The name of the package, as `name` or `backend:name`")]
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct OwnsArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(help = "The file, or a command found in PATH")]
+    path: String,
+}
+
+#[derive(Debug, Args)]
+struct VerifyArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+
+    #[arg(help = "The packages to verify, as `name` or `backend:name`, all if none")]
+    name: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    #[arg(short, long, help = "Only export packages of this backend")]
+    backend: Option<String>,
+
+    #[arg(long, help = "Write JSON instead of TOML")]
+    json: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "json",
+        help = "Write a Brewfile of the brew packages"
+    )]
+    brewfile: bool,
+
+    #[arg(long, help = "Pin the installed versions")]
+    pin: bool,
+
+    #[arg(help = "The file to write, stdout if none")]
+    path: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ImportArgs {
+    #[arg(long, help = "Read JSON, guessed from the file name by default")]
+    json: bool,
+
+    #[arg(long, conflicts_with = "json", help = "Read a Brewfile")]
+    brewfile: bool,
+
+    #[arg(long, help = "Only print what would be done")]
+    dry_run: bool,
+
+    #[arg(help = "The manifest to import")]
+    path: std::path::PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct DiffArgs {
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+
+    #[arg(
+        long,
+        help = "Also report explicitly installed packages that are not declared"
+    )]
+    prune: bool,
+
+    #[arg(help = "The manifest of the desired packages")]
+    path: std::path::PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ConvergeArgs {
+    #[arg(long, help = "Make the changes instead of only printing them")]
+    apply: bool,
+
+    #[arg(
+        long,
+        help = "Also remove explicitly installed packages that are not declared"
+    )]
+    prune: bool,
+
+    #[arg(help = "The manifest of the desired packages")]
+    path: std::path::PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct RepoArgs {
+    #[command(subcommand)]
+    action: RepoAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum RepoAction {
+    List(RepoListArgs),
+    Add(RepoAddArgs),
+    Remove(RepoName),
+    Enable(RepoName),
+    Disable(RepoName),
+}
+
+#[derive(Debug, Args)]
+struct RepoListArgs {
+    #[arg(long, help = "Print as JSON")]
+    json: bool,
+
+    #[arg(help = "The name of the backend")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct RepoAddArgs {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(long = "suite", help = "A suite of an apt repository, e.g. `bookworm`")]
+    suites: Vec<String>,
+
+    #[arg(
+        long = "component",
+        help = "A component of an apt repository, e.g. `main`"
+    )]
+    components: Vec<String>,
+
+    #[arg(long, help = "The signing key, as a local path or URL")]
+    key: Option<String>,
+
+    #[arg(long, help = "Add the repository disabled")]
+    disabled: bool,
+
+    #[arg(help = "The name of the repository, as `name` or `backend:name`")]
+    name: String,
+
+    #[arg(help = "The URL of the repository, optional for brew taps on GitHub")]
+    url: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct RepoName {
+    #[arg(short, long, help = "The name of the backend")]
+    backend: Option<String>,
+
+    #[arg(help = "The name of the repository, as `name` or `backend:name`")]
     name: String,
 }
 
@@ -34,12 +426,37 @@ struct BackendName {
 
 fn list_package(pkg: &upm::rpc::OutdatedResult) -> anyhow::Result<()> {
     for item in pkg.pkgs.iter() {
+        let status = match item.status {
+            upm::rpc::OutdateStatus::Upgradable => String::new(),
+            upm::rpc::OutdateStatus::KeptBack => {
+                " (kept back: needs new dependencies or removals)".to_string()
+            }
+            upm::rpc::OutdateStatus::Phased { percentage } => {
+                format!(
+                    " (phased update: {}% rollout, not yet for this machine)",
+                    percentage
+                )
+            }
+            upm::rpc::OutdateStatus::Held => " (held)".to_string(),
+        };
         println!(
-            "{}: {} -> {}",
-            item.name, item.current_version, item.target_version
+            "{}: {} -> {}{}{}",
+            item.name,
+            item.current_version,
+            item.target_version,
+            if item.security { " [security]" } else { "" },
+            status
         );
     }
 
+    if pkg
+        .pkgs
+        .iter()
+        .any(|x| x.status == upm::rpc::OutdateStatus::KeptBack)
+    {
+        println!("Packages kept back are only upgraded with `upm upgrade --full-upgrade`.");
+    }
+
     Ok(())
 }
 
@@ -49,6 +466,17 @@ type UpmBackendSetupHashMap = std::collections::HashMap<String, upm::BackendSetu
 struct WorkerRouter {
     backends: UmpBackendHashMap,
     info: UpmBackendSetupHashMap,
+
+    /// The authorization policy and the user it is evaluated for. Only set on
+    /// the root worker.
+    policy: Option<(upm::policy::Policy, upm::policy::Caller)>,
+
+    /// The history the root worker records what it runs in.
+    history: Option<upm::history::History>,
+
+    /// The configuration as the root worker reads it. Native arguments of the
+    /// backends are taken from here instead of the request.
+    config: Option<upm::config::Config>,
 }
 
 impl WorkerRouter {
@@ -63,14 +491,181 @@ impl WorkerRouter {
         let info = UpmBackendSetupHashMap::new();
 
         Self {
-            backends: backends,
-            info: info,
+            backends,
+            info,
+            policy: None,
+            history: None,
+            config: None,
+        }
+    }
+
+    /// Evaluate every request against the policy on behalf of the caller.
+    fn with_policy(mut self, policy: upm::policy::Policy, caller: upm::policy::Caller) -> Self {
+        self.policy = Some((policy, caller));
+        self
+    }
+
+    /// Get the backend for a request, checking the authorization policy first.
+    ///
+    /// # Arguments
+    /// + `method` - The method of the request.
+    /// + `name` - The name of backend.
+    ///
+    /// # Returns
+    /// The backend.
+    fn backend(&self, method: &str, name: &str) -> anyhow::Result<&dyn upm::UpmBackend> {
+        if let Some((policy, caller)) = &self.policy {
+            if !policy.allows(caller, method, name) {
+                log::warn!(
+                    "deny '{}' on backend '{}' for user '{}' (uid {}).",
+                    method,
+                    name,
+                    caller.name,
+                    caller.uid
+                );
+                return Err(upm::rpc::PermissionDenied {
+                    user: caller.name.clone(),
+                    method: method.to_string(),
+                    backend_name: name.to_string(),
+                }
+                .into());
+            }
+        }
+
+        match self.backends.get(name) {
+            Some(v) => Ok(v.as_ref()),
+            None => Err(anyhow::anyhow!("backend '{}' not found.", name)),
+        }
+    }
+
+    /// Make sure native arguments only reach a package manager run by the root
+    /// worker if they come from the configuration or the policy grants them.
+    ///
+    /// # Arguments
+    /// + `name` - The name of backend.
+    /// + `options` - The upgrade options of the request.
+    ///
+    /// # Returns
+    /// The options with the native arguments of the configuration.
+    fn trusted_options(
+        &self,
+        name: &str,
+        mut options: upm::rpc::UpgradeOptions,
+    ) -> anyhow::Result<upm::rpc::UpgradeOptions> {
+        let Some(config) = &self.config else {
+            return Ok(options);
+        };
+        options.apt.args = config.upgrade.apt.args.clone();
+        options.flatpak.args = config.upgrade.flatpak.args.clone();
+        options.brew.args = config.upgrade.brew.args.clone();
+        if options.extra_args.is_empty() {
+            return Ok(options);
+        }
+
+        // Without a policy, only root itself may pass them.
+        let caller = upm::policy::Caller::current()?;
+        let allowed = match &self.policy {
+            Some((policy, caller)) => policy.allows(caller, upm::policy::NATIVE_ARGS, name),
+            None => caller.uid == 0,
+        };
+        if !allowed {
+            log::warn!(
+                "deny native arguments on backend '{}' for user '{}' (uid {}).",
+                name,
+                caller.name,
+                caller.uid
+            );
+            return Err(upm::rpc::PermissionDenied {
+                user: caller.name,
+                method: upm::policy::NATIVE_ARGS.to_string(),
+                backend_name: name.to_string(),
+            }
+            .into());
+        }
+        Ok(options)
+    }
+
+    /// Run a request that changes installed packages and, on the root worker,
+    /// record the changes in the history.
+    ///
+    /// Failing to record is only logged, the request has already been done.
+    ///
+    /// # Arguments
+    /// + `method` - The method of the request.
+    /// + `name` - The name of backend.
+    /// + `f` - Runs the request on the backend.
+    ///
+    /// # Returns
+    /// The result of the request.
+    fn recorded<T>(
+        &self,
+        method: &str,
+        name: &str,
+        f: impl FnOnce(&dyn upm::UpmBackend) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let backend = self.backend(method, name)?;
+        let Some(history) = &self.history else {
+            return f(backend);
+        };
+
+        // Nothing may turn the result of the request into an error, so look
+        // up everything that can fail up front and only log failures.
+        let user = match upm::policy::Caller::current() {
+            Ok(v) => v.name,
+            Err(e) => {
+                log::warn!("failed to look up the caller: {}", e);
+                "unknown".to_string()
+            }
+        };
+        let before = match backend.list_installed() {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("failed to record the transaction: {}", e);
+                return f(backend);
+            }
+        };
+        let ret = f(backend);
+        let after = match backend.list_installed() {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("failed to record the transaction: {}", e);
+                return ret;
+            }
+        };
+
+        let changes = upm::history::diff(&before, &after);
+        if changes.is_empty() && ret.is_ok() {
+            return ret;
+        }
+
+        let timestamp = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            Ok(v) => v.as_secs(),
+            Err(e) => {
+                log::warn!("failed to read the time of the transaction: {}", e);
+                0
+            }
+        };
+        let transaction = upm::history::Transaction {
+            id: 0,
+            timestamp,
+            user,
+            method: method.to_string(),
+            backend: name.to_string(),
+            error: ret.as_ref().err().map(|e| e.to_string()),
+            changes,
+            by_root: true,
+        };
+        match history.append(transaction) {
+            Ok(id) => log::info!("recorded transaction {}.", id),
+            Err(e) => log::warn!("failed to record the transaction: {}", e),
         }
+
+        ret
     }
 
     fn info(&mut self, name: &str) -> anyhow::Result<upm::BackendSetup> {
         if let Some(info) = self.info.get(name) {
-            return Ok(info.clone());
+            return Ok(*info);
         }
 
         let backend = match self.backends.get(&name) {
@@ -81,7 +676,7 @@ impl WorkerRouter {
         };
 
         let info = backend.setup()?;
-        self.info.insert(name.to_string(), info.clone());
+        self.info.insert(name.to_string(), info);
 
         Ok(info)
     }
@@ -98,15 +693,7 @@ impl upm::rpc::server::Router for WorkerRouter {
     }
 
     fn update(&self, params: upm::rpc::UpdateParams) -> anyhow::Result<upm::rpc::UpdateResult> {
-        let backend = match self.backends.get(&params.backend_name.as_str()) {
-            Some(v) => v,
-            None => {
-                return Err(anyhow::anyhow!(
-                    "backend '{}' not found.",
-                    params.backend_name
-                ));
-            }
-        };
+        let backend = self.backend(upm::rpc::Update::METHOD, &params.backend_name)?;
         backend.update()?;
         Ok(upm::rpc::UpdateResult {})
     }
@@ -115,31 +702,228 @@ impl upm::rpc::server::Router for WorkerRouter {
         &self,
         params: upm::rpc::OutdatedParams,
     ) -> anyhow::Result<upm::rpc::OutdatedResult> {
-        let backend = match self.backends.get(&params.backend_name.as_str()) {
-            Some(v) => v,
-            None => {
-                return Err(anyhow::anyhow!(
-                    "backend '{}' not found.",
-                    params.backend_name
-                ));
-            }
-        };
+        let backend = self.backend(upm::rpc::Outdated::METHOD, &params.backend_name)?;
         let ret = backend.outdated()?;
         Ok(ret)
     }
 
     fn upgrade(&self, params: upm::rpc::UpgradeParams) -> anyhow::Result<upm::rpc::UpgradeResult> {
-        let backend = match self.backends.get(&params.backend_name.as_str()) {
+        let options = self.trusted_options(&params.backend_name, params.options)?;
+        self.recorded(upm::rpc::Upgrade::METHOD, &params.backend_name, |backend| {
+            backend.upgrade(&options)
+        })
+    }
+
+    fn install(&self, params: upm::rpc::InstallParams) -> anyhow::Result<upm::rpc::InstallResult> {
+        self.recorded(upm::rpc::Install::METHOD, &params.backend_name, |backend| {
+            backend.install(&params.pkgs)
+        })?;
+        Ok(upm::rpc::InstallResult {})
+    }
+
+    fn uninstall(
+        &self,
+        params: upm::rpc::UninstallParams,
+    ) -> anyhow::Result<upm::rpc::UninstallResult> {
+        self.recorded(
+            upm::rpc::Uninstall::METHOD,
+            &params.backend_name,
+            |backend| backend.uninstall(&params.pkgs, &params.options),
+        )
+    }
+
+    fn resolve(&self, params: upm::rpc::ResolveParams) -> anyhow::Result<upm::rpc::ResolveResult> {
+        let backend = self.backend(upm::rpc::Resolve::METHOD, &params.backend_name)?;
+        let pkgs = backend.resolve(&params.name)?;
+        Ok(upm::rpc::ResolveResult { pkgs })
+    }
+
+    fn search(&self, params: upm::rpc::SearchParams) -> anyhow::Result<upm::rpc::SearchResult> {
+        let backend = self.backend(upm::rpc::Search::METHOD, &params.backend_name)?;
+        let pkgs = backend.search(&params.query)?;
+        Ok(upm::rpc::SearchResult { pkgs })
+    }
+
+    fn info(&self, params: upm::rpc::InfoParams) -> anyhow::Result<upm::rpc::InfoResult> {
+        let backend = self.backend(upm::rpc::Info::METHOD, &params.backend_name)?;
+        let pkg = backend.info(&params.name)?;
+        Ok(upm::rpc::InfoResult { pkg })
+    }
+
+    fn list(&self, params: upm::rpc::ListParams) -> anyhow::Result<upm::rpc::ListResult> {
+        let backend = self.backend(upm::rpc::List::METHOD, &params.backend_name)?;
+        let pkgs = backend.list_installed()?;
+        Ok(upm::rpc::ListResult { pkgs })
+    }
+
+    fn simulate(
+        &self,
+        params: upm::rpc::SimulateParams,
+    ) -> anyhow::Result<upm::rpc::SimulateResult> {
+        let backend = self.backend(upm::rpc::Simulate::METHOD, &params.backend_name)?;
+        let options = self.trusted_options(&params.backend_name, params.options)?;
+        let ret = backend.simulate(&options)?;
+        Ok(ret)
+    }
+
+    fn hold(&self, params: upm::rpc::HoldParams) -> anyhow::Result<upm::rpc::HoldResult> {
+        let backend = self.backend(upm::rpc::Hold::METHOD, &params.backend_name)?;
+        backend.hold(&params.pkgs)?;
+        Ok(upm::rpc::HoldResult {})
+    }
+
+    fn unhold(&self, params: upm::rpc::HoldParams) -> anyhow::Result<upm::rpc::HoldResult> {
+        let backend = self.backend(upm::rpc::Unhold::METHOD, &params.backend_name)?;
+        backend.unhold(&params.pkgs)?;
+        Ok(upm::rpc::HoldResult {})
+    }
+
+    fn holds(&self, params: upm::rpc::HoldsParams) -> anyhow::Result<upm::rpc::HoldsResult> {
+        let backend = self.backend(upm::rpc::Holds::METHOD, &params.backend_name)?;
+        let pkgs = backend.holds()?;
+        Ok(upm::rpc::HoldsResult { pkgs })
+    }
+
+    fn clean(&self, params: upm::rpc::CleanParams) -> anyhow::Result<upm::rpc::CleanResult> {
+        let backend = self.backend(upm::rpc::Clean::METHOD, &params.backend_name)?;
+        let ret = backend.clean(&params.options)?;
+        Ok(ret)
+    }
+
+    fn orphans(&self, params: upm::rpc::OrphansParams) -> anyhow::Result<upm::rpc::OrphansResult> {
+        let backend = self.backend(upm::rpc::Orphans::METHOD, &params.backend_name)?;
+        let pkgs = backend.orphans()?;
+        Ok(upm::rpc::OrphansResult { pkgs })
+    }
+
+    fn record(&self, params: upm::rpc::RecordParams) -> anyhow::Result<upm::rpc::RecordResult> {
+        upm::require_privilege()?;
+
+        // The root worker records what it runs itself. This is only for what
+        // the normal worker ran, which the caller could have done anyway.
+        let mut transaction = params.transaction;
+        let recordable = [
+            upm::rpc::Upgrade::METHOD,
+            upm::rpc::Install::METHOD,
+            upm::rpc::Uninstall::METHOD,
+            upm::rpc::Rollback::METHOD,
+        ];
+        if !recordable.contains(&transaction.method.as_str()) {
+            return Err(anyhow::anyhow!(
+                "'{}' is not recorded in the history.",
+                transaction.method
+            ));
+        }
+        let backend = self.backend(&transaction.method, &transaction.backend)?;
+        let needs_root = match backend.setup()? {
+            upm::BackendSetup::NotInstalled => true,
+            upm::BackendSetup::Installed(v) => v.requires_root(&transaction.method),
+        };
+        if needs_root {
+            return Err(anyhow::anyhow!(
+                "'{}' on backend '{}' is recorded by the root worker.",
+                transaction.method,
+                transaction.backend
+            ));
+        }
+
+        transaction.user = upm::policy::Caller::current()?.name;
+        transaction.by_root = false;
+        let path = std::path::Path::new(upm::history::DEFAULT_HISTORY_PATH);
+        let id = upm::history::History::new(path).append(transaction)?;
+        Ok(upm::rpc::RecordResult { id })
+    }
+
+    fn rollback(
+        &self,
+        params: upm::rpc::RollbackParams,
+    ) -> anyhow::Result<upm::rpc::RollbackResult> {
+        let path = std::path::Path::new(upm::history::DEFAULT_HISTORY_PATH);
+        let transaction = match upm::history::History::new(path).find(params.id)? {
             Some(v) => v,
-            None => {
-                return Err(anyhow::anyhow!(
-                    "backend '{}' not found.",
-                    params.backend_name
-                ));
-            }
+            None => return Err(anyhow::anyhow!("transaction {} not found.", params.id)),
         };
-        backend.upgrade()?;
-        Ok(upm::rpc::UpgradeResult {})
+        // Anyone may record what ran unprivileged, so only what the root
+        // worker recorded itself is trusted as input.
+        if self.history.is_some() && !transaction.by_root {
+            return Err(anyhow::anyhow!(
+                "transaction {} did not run as root, refuse to roll it back as root.",
+                params.id
+            ));
+        }
+
+        self.recorded(
+            upm::rpc::Rollback::METHOD,
+            &transaction.backend,
+            |backend| backend.rollback(&transaction.changes),
+        )?;
+        Ok(upm::rpc::RollbackResult {})
+    }
+
+    fn changelog(
+        &self,
+        params: upm::rpc::ChangelogParams,
+    ) -> anyhow::Result<upm::rpc::ChangelogResult> {
+        let backend = self.backend(upm::rpc::Changelog::METHOD, &params.backend_name)?;
+        let changelog = backend.changelog(&params.name)?;
+        Ok(upm::rpc::ChangelogResult { changelog })
+    }
+
+    fn deps(&self, params: upm::rpc::DepsParams) -> anyhow::Result<upm::rpc::DepsResult> {
+        let backend = self.backend(upm::rpc::Deps::METHOD, &params.backend_name)?;
+        let deps = backend.deps(&params.name, &params.options)?;
+        Ok(upm::rpc::DepsResult { deps })
+    }
+
+    fn owns(&self, params: upm::rpc::OwnsParams) -> anyhow::Result<upm::rpc::OwnsResult> {
+        let backend = self.backend(upm::rpc::Owns::METHOD, &params.backend_name)?;
+        let owners = backend.owns(&params.path)?;
+        Ok(upm::rpc::OwnsResult { owners })
+    }
+
+    fn files(&self, params: upm::rpc::FilesParams) -> anyhow::Result<upm::rpc::FilesResult> {
+        let backend = self.backend(upm::rpc::Files::METHOD, &params.backend_name)?;
+        let files = backend.files(&params.name)?;
+        Ok(upm::rpc::FilesResult { files })
+    }
+
+    fn verify(&self, params: upm::rpc::VerifyParams) -> anyhow::Result<upm::rpc::VerifyResult> {
+        let backend = self.backend(upm::rpc::Verify::METHOD, &params.backend_name)?;
+        let issues = backend.verify(&params.pkgs)?;
+        Ok(upm::rpc::VerifyResult { issues })
+    }
+
+    fn repo_list(
+        &self,
+        params: upm::rpc::RepoListParams,
+    ) -> anyhow::Result<upm::rpc::RepoListResult> {
+        let backend = self.backend(upm::rpc::RepoList::METHOD, &params.backend_name)?;
+        let repos = backend.repos()?;
+        Ok(upm::rpc::RepoListResult { repos })
+    }
+
+    fn repo_add(&self, params: upm::rpc::RepoAddParams) -> anyhow::Result<upm::rpc::RepoAddResult> {
+        let backend = self.backend(upm::rpc::RepoAdd::METHOD, &params.backend_name)?;
+        backend.repo_add(&params.repo, &params.options)?;
+        Ok(upm::rpc::RepoAddResult {})
+    }
+
+    fn repo_remove(&self, params: upm::rpc::RepoParams) -> anyhow::Result<upm::rpc::RepoResult> {
+        let backend = self.backend(upm::rpc::RepoRemove::METHOD, &params.backend_name)?;
+        backend.repo_remove(&params.name)?;
+        Ok(upm::rpc::RepoResult {})
+    }
+
+    fn repo_enable(&self, params: upm::rpc::RepoParams) -> anyhow::Result<upm::rpc::RepoResult> {
+        let backend = self.backend(upm::rpc::RepoEnable::METHOD, &params.backend_name)?;
+        backend.repo_enable(&params.name, true)?;
+        Ok(upm::rpc::RepoResult {})
+    }
+
+    fn repo_disable(&self, params: upm::rpc::RepoParams) -> anyhow::Result<upm::rpc::RepoResult> {
+        let backend = self.backend(upm::rpc::RepoDisable::METHOD, &params.backend_name)?;
+        backend.repo_enable(&params.name, false)?;
+        Ok(upm::rpc::RepoResult {})
     }
 }
 
@@ -148,7 +932,22 @@ fn run_as_worker(port: u16) -> anyhow::Result<()> {
     let stream = std::net::TcpStream::connect(addr).unwrap();
 
     let mut server = upm::rpc::server::Server::new(stream);
-    let router = WorkerRouter::new();
+    let mut router = WorkerRouter::new();
+    if nix::unistd::geteuid().is_root() {
+        let path = std::path::Path::new(upm::policy::DEFAULT_POLICY_PATH);
+        match upm::policy::Policy::load(path)? {
+            Some(policy) => {
+                let caller = upm::policy::Caller::current()?;
+                log::info!("policy loaded for user '{}'.", caller.name);
+                router = router.with_policy(policy, caller);
+            }
+            None => log::debug!("no policy at {}, allow all requests.", path.display()),
+        }
+        let path = std::path::Path::new(upm::history::DEFAULT_HISTORY_PATH);
+        router.history = Some(upm::history::History::new(path));
+        let path = std::path::Path::new(upm::config::DEFAULT_CONFIG_PATH);
+        router.config = Some(upm::config::Config::load(path)?);
+    }
     server.serve(&router)?;
 
     Ok(())
@@ -157,6 +956,7 @@ fn run_as_worker(port: u16) -> anyhow::Result<()> {
 struct Controller {
     normal_worker: upm::rpc::client::Client,
     root_worker: upm::rpc::client::Client,
+    config: upm::config::Config,
 
     child1: std::process::Child,
     child2: std::process::Child,
@@ -174,7 +974,12 @@ impl Controller {
     }
 }
 
+// The workers exit once their connection is closed, so an early return here does
+// not leave them running.
+#[allow(clippy::zombie_processes)]
 fn run_as_controller(args: &UpmArgs) -> anyhow::Result<()> {
+    let config = upm::config::Config::load(std::path::Path::new(upm::config::DEFAULT_CONFIG_PATH))?;
+
     let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
     let addr = listener.local_addr().unwrap();
     log::info!("server start on {}", addr);
@@ -210,22 +1015,22 @@ fn run_as_controller(args: &UpmArgs) -> anyhow::Result<()> {
     let rsp1 = client1.call::<upm::rpc::Handshake>(&handshake)?;
     let rsp2 = client2.call::<upm::rpc::Handshake>(&handshake)?;
     assert_ne!(rsp1.privilige, rsp2.privilige);
-    println!("rsp1: {:?}", rsp1);
-    println!("rsp2: {:?}", rsp2);
 
     let mut ctl = if rsp1.privilige {
         Controller {
             normal_worker: client2,
             root_worker: client1,
-            child1: child1,
-            child2: child2,
+            config,
+            child1,
+            child2,
         }
     } else {
         Controller {
             normal_worker: client1,
             root_worker: client2,
-            child1: child1,
-            child2: child2,
+            config,
+            child1,
+            child2,
         }
     };
 
@@ -235,27 +1040,41 @@ fn run_as_controller(args: &UpmArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn do_job_outdated_full(ctl: &mut Controller, router: &mut WorkerRouter) -> anyhow::Result<()> {
+fn do_job_outdated_full(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &OutdatedArgs,
+) -> anyhow::Result<()> {
     let mut names = Vec::new();
     for (name, _) in router.backends.iter() {
         names.push(name.to_string());
     }
     for name in names {
-        do_job_outdated_item(ctl, router, &name)?;
+        do_job_outdated_item(ctl, router, &name, args)?;
     }
 
     Ok(())
 }
 
-fn do_job_outdated_item(
+/// Get the outdated packages of a backend.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `router` - The router that knows the backends.
+/// + `name` - The name of backend.
+///
+/// # Returns
+/// The outdated packages, `None` if the backend is not installed. Packages
+/// held in the configuration are marked as held.
+fn query_outdated(
     ctl: &mut Controller,
     router: &mut WorkerRouter,
     name: &str,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Option<upm::rpc::OutdatedResult>> {
     let info = match router.info(name)? {
         upm::BackendSetup::NotInstalled => {
             // The package manager is not installed.
-            return Ok(());
+            return Ok(None);
         }
         upm::BackendSetup::Installed(v) => v,
     };
@@ -263,12 +1082,44 @@ fn do_job_outdated_item(
     let params = upm::rpc::OutdatedParams {
         backend_name: name.to_string(),
     };
-    let rsp = if info.update {
+    let mut rsp = if info.outdated {
         ctl.root_worker.call::<upm::rpc::Outdated>(&params)?
     } else {
         ctl.normal_worker.call::<upm::rpc::Outdated>(&params)?
     };
-    list_package(&rsp)?;
+
+    let holds = ctl.config.holds_for(name);
+    for item in rsp.pkgs.iter_mut() {
+        if holds.contains(&item.name) {
+            item.status = upm::rpc::OutdateStatus::Held;
+        }
+    }
+
+    Ok(Some(rsp))
+}
+
+fn do_job_outdated_item(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    name: &str,
+    args: &OutdatedArgs,
+) -> anyhow::Result<()> {
+    let Some(mut rsp) = query_outdated(ctl, router, name)? else {
+        return Ok(());
+    };
+    if args.security {
+        rsp.pkgs.retain(|x| x.security);
+    }
+    list_package(&rsp)?;
+
+    if args.changelog {
+        for item in rsp.pkgs.iter() {
+            if let Some(changelog) = query_changelog(ctl, router, name, &item.name)? {
+                println!();
+                print_changelog(name, &changelog);
+            }
+        }
+    }
 
     Ok(())
 }
@@ -276,12 +1127,12 @@ fn do_job_outdated_item(
 fn do_job_outdated(
     ctl: &mut Controller,
     router: &mut WorkerRouter,
-    name: &Option<String>,
+    args: &OutdatedArgs,
 ) -> anyhow::Result<()> {
-    if let Some(name) = name {
-        do_job_outdated_item(ctl, router, name)?;
+    if let Some(name) = &args.name {
+        do_job_outdated_item(ctl, router, name, args)?;
     } else {
-        do_job_outdated_full(ctl, router)?;
+        do_job_outdated_full(ctl, router, args)?;
     }
 
     Ok(())
@@ -338,10 +1189,99 @@ fn do_job_update(
     Ok(())
 }
 
+/// Get the installed packages of a backend.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `name` - The name of backend.
+/// + `info` - The privileges of the backend.
+///
+/// # Returns
+/// The installed packages.
+fn query_installed(
+    ctl: &mut Controller,
+    name: &str,
+    info: &upm::MethodPrivilege,
+) -> anyhow::Result<Vec<upm::rpc::InstalledItem>> {
+    let params = upm::rpc::ListParams {
+        backend_name: name.to_string(),
+    };
+    let rsp = if info.list_installed {
+        ctl.root_worker.call::<upm::rpc::List>(&params)?
+    } else {
+        ctl.normal_worker.call::<upm::rpc::List>(&params)?
+    };
+
+    Ok(rsp.pkgs)
+}
+
+/// Run a request that changes installed packages and record the changes in
+/// the history.
+///
+/// The root worker records what it runs itself, requests run by the normal
+/// worker are recorded here. Failing to record is only logged, the request
+/// has already been done.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `name` - The name of backend.
+/// + `info` - The privileges of the backend.
+/// + `params` - The parameters of the request.
+///
+/// # Returns
+/// The result of the request.
+fn run_recorded<R>(
+    ctl: &mut Controller,
+    name: &str,
+    info: &upm::MethodPrivilege,
+    params: &R::Params,
+) -> anyhow::Result<R::Result>
+where
+    R: upm::rpc::Request,
+{
+    // The root worker records what it runs itself.
+    if info.requires_root(R::METHOD) {
+        return ctl.root_worker.call::<R>(params);
+    }
+
+    let before = query_installed(ctl, name, info)?;
+    let ret = ctl.normal_worker.call::<R>(params);
+    let after = query_installed(ctl, name, info)?;
+
+    let changes = upm::history::diff(&before, &after);
+    if changes.is_empty() && ret.is_ok() {
+        return ret;
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let params = upm::rpc::RecordParams {
+        transaction: upm::history::Transaction {
+            id: 0,
+            timestamp,
+            user: String::new(),
+            method: R::METHOD.to_string(),
+            backend: name.to_string(),
+            error: ret.as_ref().err().map(|e| e.to_string()),
+            changes,
+            by_root: false,
+        },
+    };
+    match ctl.root_worker.call::<upm::rpc::Record>(&params) {
+        Ok(rsp) => log::info!("recorded transaction {}.", rsp.id),
+        Err(e) => log::warn!("failed to record the transaction: {}", e),
+    }
+
+    ret
+}
+
 fn do_job_upgrade_item(
     ctl: &mut Controller,
     router: &mut WorkerRouter,
     name: &str,
+    options: &upm::rpc::UpgradeOptions,
+    dry_run: bool,
 ) -> anyhow::Result<()> {
     let info = match router.info(name)? {
         upm::BackendSetup::NotInstalled => {
@@ -351,25 +1291,139 @@ fn do_job_upgrade_item(
         upm::BackendSetup::Installed(v) => v,
     };
 
+    // Leave packages held in the configuration alone.
+    let holds = ctl.config.holds_for(name);
+    let mut options = options.clone();
+    if !options.pkgs.is_empty() {
+        for pkg in options.pkgs.iter().filter(|x| holds.contains(x)) {
+            println!("'{}' is held, skipping.", pkg);
+        }
+        options.pkgs.retain(|x| !holds.contains(x));
+        if options.pkgs.is_empty() {
+            return Ok(());
+        }
+    }
+    options.exclude.extend(holds);
+
+    if dry_run {
+        let params = upm::rpc::SimulateParams {
+            backend_name: name.to_string(),
+            options,
+        };
+        let rsp = if info.simulate {
+            ctl.root_worker.call::<upm::rpc::Simulate>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Simulate>(&params)?
+        };
+        print_plan(name, &rsp);
+        return Ok(());
+    }
+
     let params = upm::rpc::UpgradeParams {
         backend_name: name.to_string(),
+        options,
     };
-    if info.upgrade {
-        ctl.root_worker.call::<upm::rpc::Upgrade>(&params)?;
-    } else {
-        ctl.normal_worker.call::<upm::rpc::Upgrade>(&params)?;
+    let rsp = run_recorded::<upm::rpc::Upgrade>(ctl, name, &info, &params)?;
+    for pkg in rsp.pending.iter() {
+        println!("'{}' still needs a download, skipping.", pkg);
     }
 
     Ok(())
 }
 
-fn do_job_upgrade_full(ctl: &mut Controller, router: &mut WorkerRouter) -> anyhow::Result<()> {
+fn print_plan(name: &str, plan: &upm::rpc::SimulateResult) {
+    let sections = [
+        ("upgrade", &plan.upgrades),
+        ("install", &plan.installs),
+        ("remove", &plan.removals),
+        ("held back", &plan.held_back),
+    ];
+    for (title, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        println!("{} ({}):", title, name);
+        for item in items.iter() {
+            match (
+                item.current_version.is_empty(),
+                item.target_version.is_empty(),
+            ) {
+                (false, false) => println!(
+                    "  {}: {} -> {}",
+                    item.name, item.current_version, item.target_version
+                ),
+                (true, false) => println!("  {}: {}", item.name, item.target_version),
+                (false, true) => println!("  {}: {}", item.name, item.current_version),
+                (true, true) => println!("  {}", item.name),
+            }
+        }
+    }
+}
+
+fn do_job_upgrade_full(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    options: &upm::rpc::UpgradeOptions,
+    dry_run: bool,
+) -> anyhow::Result<()> {
     let mut names = Vec::new();
     for (name, _) in router.backends.iter() {
         names.push(name.to_string());
     }
     for name in names {
-        do_job_upgrade_item(ctl, router, &name)?;
+        do_job_upgrade_item(ctl, router, &name, options, dry_run)?;
+    }
+
+    Ok(())
+}
+
+/// Upgrade the named packages on whichever backend has an upgrade for them.
+fn do_job_upgrade_pkgs(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    pkgs: &[String],
+    options: &upm::rpc::UpgradeOptions,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    let mut add = |backend: &str, name: &str| match groups.iter_mut().find(|(k, _)| k == backend) {
+        Some((_, v)) => v.push(name.to_string()),
+        None => groups.push((backend.to_string(), vec![name.to_string()])),
+    };
+
+    let mut unknown = Vec::new();
+    for arg in pkgs {
+        match split_backend(router, arg) {
+            (Some(backend), name) => add(backend, name),
+            (None, name) => unknown.push(name.to_string()),
+        }
+    }
+
+    if !unknown.is_empty() {
+        for backend in sorted_backends(ctl, router) {
+            let Some(rsp) = query_outdated(ctl, router, &backend)? else {
+                continue;
+            };
+            for item in rsp.pkgs {
+                if unknown.contains(&item.name) {
+                    add(&backend, &item.name);
+                }
+            }
+        }
+
+        for name in unknown.iter() {
+            if !groups.iter().any(|(_, v)| v.contains(name)) {
+                println!("'{}' has no upgrade available.", name);
+            }
+        }
+    }
+
+    for (backend, pkgs) in groups {
+        let options = upm::rpc::UpgradeOptions {
+            pkgs,
+            ..options.clone()
+        };
+        do_job_upgrade_item(ctl, router, &backend, &options, dry_run)?;
     }
 
     Ok(())
@@ -378,30 +1432,2002 @@ fn do_job_upgrade_full(ctl: &mut Controller, router: &mut WorkerRouter) -> anyho
 fn do_job_upgrade(
     ctl: &mut Controller,
     router: &mut WorkerRouter,
-    name: &Option<String>,
+    args: &UpgradeArgs,
 ) -> anyhow::Result<()> {
-    if let Some(name) = name {
-        do_job_upgrade_item(ctl, router, name)?;
+    // The command line overrides the configuration.
+    let mut apt = ctl.config.upgrade.apt.clone();
+    match args.apt_strategy.as_deref() {
+        Some("upgrade") => apt.strategy = upm::rpc::AptStrategy::Upgrade,
+        Some("full-upgrade") => apt.strategy = upm::rpc::AptStrategy::FullUpgrade,
+        Some("dist-upgrade") => apt.strategy = upm::rpc::AptStrategy::DistUpgrade,
+        _ if args.full_upgrade => apt.strategy = upm::rpc::AptStrategy::FullUpgrade,
+        _ => {}
+    }
+    apt.with_new_pkgs |= args.with_new_pkgs;
+
+    let mut flatpak = ctl.config.upgrade.flatpak.clone();
+    if args.user {
+        flatpak.installation = upm::rpc::FlatpakInstallation::User;
+    } else if args.system {
+        flatpak.installation = upm::rpc::FlatpakInstallation::System;
+    }
+
+    let mut brew = ctl.config.upgrade.brew.clone();
+    if args.formula {
+        brew.kind = upm::rpc::BrewKind::Formula;
+    } else if args.cask {
+        brew.kind = upm::rpc::BrewKind::Cask;
+    }
+    brew.greedy |= args.greedy;
+
+    let options = upm::rpc::UpgradeOptions {
+        pkgs: Vec::new(),
+        exclude: args.exclude.clone(),
+        security_only: args.security_only,
+        download_only: args.download_only,
+        offline: args.offline,
+        apt,
+        flatpak,
+        brew,
+        extra_args: args.extra_args.clone(),
+    };
+
+    // Native arguments only make sense to one package manager.
+    let named = args
+        .name
+        .first()
+        .is_some_and(|v| router.backends.contains_key(v.as_str()));
+    if !options.extra_args.is_empty() && !named {
+        return Err(anyhow::anyhow!(
+            "--arg needs the name of the backend, e.g. 'upm upgrade apt --arg ...'."
+        ));
+    }
+
+    // `upm upgrade <backend> [pkgs...]` or `upm upgrade <pkgs...>`.
+    match args.name.first() {
+        Some(first) if router.backends.contains_key(first.as_str()) => {
+            let options = upm::rpc::UpgradeOptions {
+                pkgs: args.name[1..].to_vec(),
+                ..options
+            };
+            do_job_upgrade_item(ctl, router, first, &options, args.dry_run)?;
+        }
+        Some(_) => do_job_upgrade_pkgs(ctl, router, &args.name, &options, args.dry_run)?,
+        None => do_job_upgrade_full(ctl, router, &options, args.dry_run)?,
+    }
+
+    Ok(())
+}
+
+/// Parse a package given on the command line.
+///
+/// # Arguments
+/// + `arg` - The package, as `name` or `name=version`.
+///
+/// # Returns
+/// The package spec.
+fn parse_package_spec(arg: &str) -> upm::rpc::PackageSpec {
+    let (name, version) = match arg.split_once('=') {
+        Some((name, version)) => (name, Some(version.to_string())),
+        None => (arg, None),
+    };
+
+    upm::rpc::PackageSpec {
+        name: name.to_string(),
+        version,
+        vendor: None,
+    }
+}
+
+/// Get the names of all backends, most preferred first.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `router` - The router that knows the backends.
+///
+/// # Returns
+/// The names of backends.
+fn sorted_backends(ctl: &Controller, router: &WorkerRouter) -> Vec<String> {
+    let mut names: Vec<String> = router.backends.keys().map(|v| v.to_string()).collect();
+    names.sort_by_key(|v| (ctl.config.backend_rank(v), v.clone()));
+    names
+}
+
+/// Split an explicit `backend:name` given on the command line.
+///
+/// Only known backend names are split off, so apt architecture qualifiers
+/// such as `libc6:i386` are kept as is.
+///
+/// # Arguments
+/// + `router` - The router that knows the backends.
+/// + `arg` - The package given on the command line.
+///
+/// # Returns
+/// The backend name if given, and the package.
+fn split_backend<'a>(router: &WorkerRouter, arg: &'a str) -> (Option<&'a str>, &'a str) {
+    if let Some((backend, name)) = arg.split_once(':') {
+        if router.backends.contains_key(backend) {
+            return (Some(backend), name);
+        }
+    }
+
+    (None, arg)
+}
+
+/// Group packages given on the command line by backend.
+///
+/// # Arguments
+/// + `router` - The router that knows the backends.
+/// + `pkg` - The packages given on the command line.
+///
+/// # Returns
+/// The packages of each backend.
+fn group_by_backend(
+    router: &WorkerRouter,
+    pkg: &PackageName,
+) -> anyhow::Result<Vec<(String, Vec<upm::rpc::PackageSpec>)>> {
+    let mut ret: Vec<(String, Vec<upm::rpc::PackageSpec>)> = Vec::new();
+    for arg in pkg.name.iter() {
+        let (backend, name) = split_backend(router, arg);
+        let backend = match backend.or(pkg.backend.as_deref()) {
+            Some(v) => v,
+            None => {
+                return Err(anyhow::anyhow!(
+                    "please specify the backend of '{}' with --backend or as 'backend:{}'.",
+                    arg,
+                    arg
+                ));
+            }
+        };
+
+        let spec = parse_package_spec(name);
+        match ret.iter_mut().find(|(k, _)| k == backend) {
+            Some((_, v)) => v.push(spec),
+            None => ret.push((backend.to_string(), vec![spec])),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Find out which backend provides a package.
+///
+/// Every installed backend is asked, a backend that fails is skipped with a
+/// warning. If more than one provides the package, the candidates are ranked
+/// by the configured backend priority and the user chooses one.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `router` - The router that knows the backends.
+/// + `arg` - The package given on the command line.
+///
+/// # Returns
+/// The backend name and the package.
+fn resolve_package(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    arg: &str,
+) -> anyhow::Result<(String, upm::rpc::PackageSpec)> {
+    let spec = parse_package_spec(arg);
+
+    let mut candidates = Vec::new();
+    let mut errors = Vec::new();
+    for name in sorted_backends(ctl, router) {
+        let info = match router.info(&name) {
+            Ok(upm::BackendSetup::NotInstalled) => continue,
+            Ok(upm::BackendSetup::Installed(v)) => v,
+            Err(e) => {
+                errors.push((name, e));
+                continue;
+            }
+        };
+
+        let params = upm::rpc::ResolveParams {
+            backend_name: name.clone(),
+            name: spec.name.clone(),
+        };
+        let rsp = if info.resolve {
+            ctl.root_worker.call::<upm::rpc::Resolve>(&params)
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Resolve>(&params)
+        };
+        match rsp {
+            Ok(rsp) => {
+                for item in rsp.pkgs {
+                    candidates.push((name.clone(), item));
+                }
+            }
+            Err(e) => errors.push((name, e)),
+        }
+    }
+
+    if candidates.is_empty() {
+        if let Some((backend, e)) = errors.first() {
+            return Err(anyhow::anyhow!(
+                "no backend provides '{}', backend '{}' failed: {}",
+                spec.name,
+                backend,
+                e.to_string().trim()
+            ));
+        }
+        return Err(anyhow::anyhow!("no backend provides '{}'.", spec.name));
+    }
+    for (backend, e) in errors {
+        eprintln!(
+            "warning: skip backend '{}': {}",
+            backend,
+            e.to_string().trim()
+        );
+    }
+
+    let index = match candidates.len() {
+        1 => 0,
+        _ => choose_candidate(&spec.name, &candidates)?,
+    };
+
+    // Keep the version the user asked for, the candidate version is only shown.
+    let (backend, item) = candidates.swap_remove(index);
+    Ok((
+        backend,
+        upm::rpc::PackageSpec {
+            name: item.name,
+            version: spec.version,
+            vendor: item.vendor,
+        },
+    ))
+}
+
+/// Let the user choose between packages of the same name.
+///
+/// # Arguments
+/// + `name` - The name given by the user.
+/// + `candidates` - The ranked candidates.
+///
+/// # Returns
+/// The index of the chosen candidate.
+fn choose_candidate(
+    name: &str,
+    candidates: &[(String, upm::rpc::PackageSpec)],
+) -> anyhow::Result<usize> {
+    use std::io::{IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "'{}' is provided by more than one backend, use 'backend:name' to choose.",
+            name
+        ));
+    }
+
+    println!("'{}' is provided by:", name);
+    for (i, (backend, item)) in candidates.iter().enumerate() {
+        println!(
+            "  {}) {}:{} {} ({})",
+            i + 1,
+            backend,
+            item.name,
+            item.version.as_deref().unwrap_or(""),
+            item.vendor.as_deref().unwrap_or("")
+        );
+    }
+
+    loop {
+        print!("Choose one [1]: ");
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(anyhow::anyhow!("no choice made."));
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(0);
+        }
+        match answer.parse::<usize>() {
+            Ok(v) if v >= 1 && v <= candidates.len() => return Ok(v - 1),
+            _ => println!("invalid choice '{}'.", answer),
+        }
+    }
+}
+
+fn do_job_install(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    pkg: &PackageName,
+) -> anyhow::Result<()> {
+    let mut groups: Vec<(String, Vec<upm::rpc::PackageSpec>)> = Vec::new();
+    for arg in pkg.name.iter() {
+        let (backend, spec) = match split_backend(router, arg) {
+            (Some(backend), name) => (backend.to_string(), parse_package_spec(name)),
+            (None, name) => match &pkg.backend {
+                Some(backend) => (backend.clone(), parse_package_spec(name)),
+                None => resolve_package(ctl, router, name)?,
+            },
+        };
+
+        match groups.iter_mut().find(|(k, _)| *k == backend) {
+            Some((_, v)) => v.push(spec),
+            None => groups.push((backend, vec![spec])),
+        }
+    }
+
+    for (name, pkgs) in groups {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => {
+                return Err(anyhow::anyhow!("backend '{}' is not installed.", name));
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::InstallParams {
+            backend_name: name.clone(),
+            pkgs,
+        };
+        run_recorded::<upm::rpc::Install>(ctl, &name, &info, &params)?;
+    }
+
+    Ok(())
+}
+
+/// Ask the user to confirm on the terminal.
+///
+/// # Arguments
+/// + `prompt` - The question to ask.
+///
+/// # Returns
+/// `true` if the user answered yes.
+fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    use std::io::Write;
+
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+
+    Ok(answer == "y" || answer == "yes")
+}
+
+fn do_job_uninstall(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &UninstallArgs,
+) -> anyhow::Result<()> {
+    let options = upm::rpc::UninstallOptions {
+        purge: args.purge,
+        force: args.force,
+        autoremove: args.autoremove,
+        dry_run: true,
+    };
+
+    let mut plans = Vec::new();
+    for (name, pkgs) in group_by_backend(router, &args.pkg)? {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => {
+                return Err(anyhow::anyhow!("backend '{}' is not installed.", name));
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::UninstallParams {
+            backend_name: name,
+            pkgs,
+            options: options.clone(),
+        };
+        let plan = if info.uninstall {
+            ctl.root_worker.call::<upm::rpc::Uninstall>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Uninstall>(&params)?
+        };
+        plans.push((info, params, plan));
+    }
+
+    println!("The following packages will be removed:");
+    for (_, params, plan) in plans.iter() {
+        for item in plan.pkgs.iter() {
+            println!("  {}:{} {}", params.backend_name, item.name, item.version);
+        }
+    }
+    if !args.yes && !confirm("Do you want to continue?")? {
+        return Ok(());
+    }
+
+    for (info, mut params, _) in plans {
+        params.options.dry_run = false;
+        let rsp = run_recorded::<upm::rpc::Uninstall>(ctl, &params.backend_name, &info, &params)?;
+        for item in rsp.pkgs.iter() {
+            println!(
+                "removed {}:{} {}",
+                params.backend_name, item.name, item.version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Rank how well a package name matches the search keyword, lower is better.
+///
+/// # Arguments
+/// + `query` - The search keyword.
+/// + `name` - The name of package.
+///
+/// # Returns
+/// The rank.
+fn search_rank(query: &str, name: &str) -> u8 {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    let short = name.rsplit('.').next().unwrap_or_default();
+
+    if name == query || short == query {
+        0
+    } else if name.starts_with(&query) || short.starts_with(&query) {
+        1
+    } else if name.contains(&query) {
+        2
     } else {
-        do_job_upgrade_full(ctl, router)?;
+        3
+    }
+}
+
+fn do_job_search(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &SearchArgs,
+) -> anyhow::Result<()> {
+    let names = match &args.backend {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut pkgs: Vec<upm::rpc::SearchItem> = Vec::new();
+    let mut seen: std::collections::HashMap<(String, String), usize> =
+        std::collections::HashMap::new();
+    for name in names {
+        let info = match router.info(&name) {
+            Ok(upm::BackendSetup::NotInstalled) => continue,
+            Ok(upm::BackendSetup::Installed(v)) => v,
+            Err(e) => {
+                eprintln!("warning: skip backend '{}': {}", name, e.to_string().trim());
+                continue;
+            }
+        };
+
+        let params = upm::rpc::SearchParams {
+            backend_name: name.clone(),
+            query: args.query.clone(),
+        };
+        let rsp = if info.search {
+            ctl.root_worker.call::<upm::rpc::Search>(&params)
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Search>(&params)
+        };
+        let rsp = match rsp {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("warning: skip backend '{}': {}", name, e.to_string().trim());
+                continue;
+            }
+        };
+
+        // The same package may be listed once per repository.
+        for item in rsp.pkgs {
+            let key = (item.backend.clone(), item.name.clone());
+            match seen.get(&key) {
+                Some(&i) => {
+                    let x = &mut pkgs[i];
+                    if !item.vendor.is_empty() && !x.vendor.split(',').any(|v| v == item.vendor) {
+                        x.vendor = if x.vendor.is_empty() {
+                            item.vendor
+                        } else {
+                            format!("{},{}", x.vendor, item.vendor)
+                        };
+                    }
+                    x.installed |= item.installed;
+                }
+                None => {
+                    seen.insert(key, pkgs.len());
+                    pkgs.push(item);
+                }
+            }
+        }
+    }
+
+    pkgs.sort_by_key(|x| {
+        (
+            search_rank(&args.query, &x.name),
+            !x.installed,
+            ctl.config.backend_rank(&x.backend),
+            x.name.clone(),
+        )
+    });
+
+    for item in pkgs.iter() {
+        println!(
+            "{}:{} {}{}",
+            item.backend,
+            item.name,
+            item.version,
+            if item.installed { " [installed]" } else { "" }
+        );
+        if !item.vendor.is_empty() {
+            println!("  from {}", item.vendor);
+        }
+        println!("  {}", item.summary);
     }
 
     Ok(())
 }
 
-fn do_job(ctl: &mut Controller, args: &UpmArgs, mut router: WorkerRouter) -> anyhow::Result<()> {
-    let mode = args
-        .mode
-        .as_ref()
-        .unwrap_or(&ActionMode::Update(BackendName { name: None }));
-    let ret = match mode {
-        ActionMode::Update(v) => do_job_update(ctl, &mut router, &v.name),
-        ActionMode::Outdated(v) => do_job_outdated(ctl, &mut router, &v.name),
-        ActionMode::Upgrade(v) => do_job_upgrade(ctl, &mut router, &v.name),
-        _ => Err(anyhow::anyhow!("not implementation.")),
+/// Format a size in bytes for humans.
+///
+/// # Arguments
+/// + `size` - The size in bytes.
+///
+/// # Returns
+/// The formatted size.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn print_package_info(info: &upm::rpc::PackageInfo) {
+    let none = || "-".to_string();
+
+    println!("{}:{}", info.backend, info.name);
+    println!(
+        "  Installed: {}",
+        info.installed_version.clone().unwrap_or_else(none)
+    );
+    println!(
+        "  Candidate: {}",
+        info.candidate_version.clone().unwrap_or_else(none)
+    );
+    println!("  Origin: {}", info.origin.clone().unwrap_or_else(none));
+    println!("  Homepage: {}", info.homepage.clone().unwrap_or_else(none));
+    println!("  License: {}", info.license.clone().unwrap_or_else(none));
+    println!(
+        "  Download size: {}",
+        info.download_size.map(format_size).unwrap_or_else(none)
+    );
+    println!(
+        "  Installed size: {}",
+        info.installed_size.map(format_size).unwrap_or_else(none)
+    );
+    println!("  Depends: {}", info.dependencies.join(", "));
+    println!("  Description:");
+    for line in info.description.lines() {
+        println!("    {}", line);
+    }
+}
+
+fn do_job_info(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &InfoArgs,
+) -> anyhow::Result<()> {
+    let (names, name) = match split_backend(router, &args.name) {
+        (Some(backend), name) => (vec![backend.to_string()], name),
+        (None, name) => match &args.backend {
+            Some(backend) => (vec![backend.clone()], name),
+            None => (sorted_backends(ctl, router), name),
+        },
     };
 
-    ret
+    let mut pkgs = Vec::new();
+    for backend in names {
+        let info = match router.info(&backend)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::InfoParams {
+            backend_name: backend,
+            name: name.to_string(),
+        };
+        let rsp = if info.info {
+            ctl.root_worker.call::<upm::rpc::Info>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Info>(&params)?
+        };
+        pkgs.extend(rsp.pkg);
+    }
+
+    if pkgs.is_empty() {
+        return Err(anyhow::anyhow!("package '{}' not found.", name));
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&pkgs)?);
+        return Ok(());
+    }
+    for (i, pkg) in pkgs.iter().enumerate() {
+        if i != 0 {
+            println!();
+        }
+        print_package_info(pkg);
+    }
+
+    Ok(())
+}
+
+/// Get the changelog of a package from a backend.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `router` - The router that knows the backends.
+/// + `backend` - The name of backend.
+/// + `name` - The name of package.
+///
+/// # Returns
+/// The changelog, `None` if the backend is not installed or does not know
+/// the package.
+fn query_changelog(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    backend: &str,
+    name: &str,
+) -> anyhow::Result<Option<upm::rpc::PackageChangelog>> {
+    let info = match router.info(backend)? {
+        upm::BackendSetup::NotInstalled => return Ok(None),
+        upm::BackendSetup::Installed(v) => v,
+    };
+
+    let params = upm::rpc::ChangelogParams {
+        backend_name: backend.to_string(),
+        name: name.to_string(),
+    };
+    let rsp = if info.changelog {
+        ctl.root_worker.call::<upm::rpc::Changelog>(&params)?
+    } else {
+        ctl.normal_worker.call::<upm::rpc::Changelog>(&params)?
+    };
+
+    Ok(rsp.changelog)
+}
+
+fn print_changelog(backend: &str, changelog: &upm::rpc::PackageChangelog) {
+    match &changelog.current_version {
+        Some(v) => println!(
+            "{}:{} {} -> {}",
+            backend, changelog.name, v, changelog.target_version
+        ),
+        None => println!(
+            "{}:{} {}",
+            backend, changelog.name, changelog.target_version
+        ),
+    }
+    if changelog.entries.is_empty() {
+        println!("  No changelog available.");
+    }
+    for entry in changelog.entries.iter() {
+        if entry.date.is_empty() {
+            println!("  {}", entry.version);
+        } else {
+            println!("  {} ({})", entry.version, entry.date);
+        }
+        for line in entry.text.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+fn do_job_changelog(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &ChangelogArgs,
+) -> anyhow::Result<()> {
+    let (names, name) = match split_backend(router, &args.name) {
+        (Some(backend), name) => (vec![backend.to_string()], name),
+        (None, name) => match &args.backend {
+            Some(backend) => (vec![backend.clone()], name),
+            None => (sorted_backends(ctl, router), name),
+        },
+    };
+
+    let mut found = false;
+    for backend in names {
+        let Some(changelog) = query_changelog(ctl, router, &backend, name)? else {
+            continue;
+        };
+        if found {
+            println!();
+        }
+        print_changelog(&backend, &changelog);
+        found = true;
+    }
+
+    if !found {
+        return Err(anyhow::anyhow!("package '{}' not found.", name));
+    }
+
+    Ok(())
+}
+
+fn do_job_list(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &ListArgs,
+) -> anyhow::Result<()> {
+    let names = match &args.backend {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut pkgs = Vec::new();
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::ListParams { backend_name: name };
+        let rsp = if info.list_installed {
+            ctl.root_worker.call::<upm::rpc::List>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::List>(&params)?
+        };
+        pkgs.extend(rsp.pkgs);
+    }
+
+    pkgs.retain(|x| {
+        args.origin.as_ref().is_none_or(|v| *v == x.origin)
+            && (!args.manual || x.manual)
+            && (!args.auto || !x.manual)
+    });
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&pkgs)?);
+        return Ok(());
+    }
+    for item in pkgs.iter() {
+        println!(
+            "{}:{} {} {} {} [{}]",
+            item.backend,
+            item.name,
+            item.version,
+            item.arch,
+            item.origin,
+            if item.manual { "manual" } else { "auto" }
+        );
+    }
+
+    Ok(())
+}
+
+fn do_job_hold(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    pkg: &PackageName,
+    hold: bool,
+) -> anyhow::Result<()> {
+    for (name, pkgs) in group_by_backend(router, pkg)? {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => {
+                return Err(anyhow::anyhow!("backend '{}' is not installed.", name));
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::HoldParams {
+            backend_name: name,
+            pkgs: pkgs.into_iter().map(|x| x.name).collect(),
+        };
+        let worker = if info.hold {
+            &mut ctl.root_worker
+        } else {
+            &mut ctl.normal_worker
+        };
+        if hold {
+            worker.call::<upm::rpc::Hold>(&params)?;
+        } else {
+            worker.call::<upm::rpc::Unhold>(&params)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn do_job_holds(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    name: &Option<String>,
+) -> anyhow::Result<()> {
+    let names = match name {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::HoldsParams {
+            backend_name: name.clone(),
+        };
+        let rsp = if info.holds {
+            ctl.root_worker.call::<upm::rpc::Holds>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Holds>(&params)?
+        };
+        for pkg in rsp.pkgs.iter() {
+            println!("{}:{}", name, pkg);
+        }
+        for pkg in ctl.config.holds_for(&name) {
+            if !rsp.pkgs.contains(&pkg) {
+                println!("{}:{} (upm)", name, pkg);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn do_job_clean(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &CleanArgs,
+) -> anyhow::Result<()> {
+    let names = match &args.name {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut total = 0;
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::CleanParams {
+            backend_name: name.clone(),
+            options: upm::rpc::CleanOptions {
+                all: args.all,
+                dry_run: args.dry_run,
+            },
+        };
+        let rsp = if info.clean {
+            ctl.root_worker.call::<upm::rpc::Clean>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Clean>(&params)?
+        };
+        total += rsp.reclaimed;
+
+        if args.dry_run {
+            println!("{}: would reclaim {}", name, format_size(rsp.reclaimed));
+        } else {
+            println!("{}: reclaimed {}", name, format_size(rsp.reclaimed));
+        }
+    }
+
+    if args.dry_run {
+        println!("total: would reclaim {}", format_size(total));
+    } else {
+        println!("total: reclaimed {}", format_size(total));
+    }
+
+    Ok(())
+}
+
+fn do_job_autoremove(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &AutoremoveArgs,
+) -> anyhow::Result<()> {
+    let names = match &args.name {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut plans = Vec::new();
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::OrphansParams {
+            backend_name: name.clone(),
+        };
+        let mut rsp = if info.orphans {
+            ctl.root_worker.call::<upm::rpc::Orphans>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Orphans>(&params)?
+        };
+
+        let holds = ctl.config.holds_for(&name);
+        rsp.pkgs.retain(|x| !holds.contains(&x.name));
+        if !rsp.pkgs.is_empty() {
+            plans.push((name, info, rsp.pkgs));
+        }
+    }
+
+    if plans.is_empty() {
+        println!("No orphaned packages.");
+        return Ok(());
+    }
+
+    println!("The following packages are no longer required:");
+    for (name, _, pkgs) in plans.iter() {
+        for item in pkgs.iter() {
+            println!("  {}:{} {}", name, item.name, item.version);
+        }
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    if !args.yes && !confirm("Do you want to remove them?")? {
+        return Ok(());
+    }
+
+    // Remove exactly the packages listed, so held ones stay.
+    for (name, info, pkgs) in plans {
+        let params = upm::rpc::UninstallParams {
+            backend_name: name,
+            pkgs: pkgs
+                .into_iter()
+                .map(|x| upm::rpc::PackageSpec {
+                    name: x.name,
+                    version: None,
+                    vendor: None,
+                })
+                .collect(),
+            options: upm::rpc::UninstallOptions::default(),
+        };
+        let rsp = run_recorded::<upm::rpc::Uninstall>(ctl, &params.backend_name, &info, &params)?;
+        for item in rsp.pkgs.iter() {
+            println!(
+                "removed {}:{} {}",
+                params.backend_name, item.name, item.version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn do_job_history(args: &HistoryArgs) -> anyhow::Result<()> {
+    let path = std::path::Path::new(upm::history::DEFAULT_HISTORY_PATH);
+    let history = upm::history::History::new(path);
+
+    let Some(id) = args.id else {
+        let transactions = history.load()?;
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&transactions)?);
+            return Ok(());
+        }
+        for item in transactions.iter() {
+            println!(
+                "{:>4}  {}  {:<10} {:<10} {:<8} {} package(s){}",
+                item.id,
+                upm::history::format_timestamp(item.timestamp),
+                item.user,
+                item.method,
+                item.backend,
+                item.changes.len(),
+                if item.error.is_some() {
+                    " (failed)"
+                } else {
+                    ""
+                }
+            );
+        }
+        return Ok(());
+    };
+
+    let transaction = match history.find(id)? {
+        Some(v) => v,
+        None => return Err(anyhow::anyhow!("transaction {} not found.", id)),
+    };
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&transaction)?);
+        return Ok(());
+    }
+    println!("Transaction: {}", transaction.id);
+    println!(
+        "Date:        {}",
+        upm::history::format_timestamp(transaction.timestamp)
+    );
+    println!("User:        {}", transaction.user);
+    println!("Command:     {}", transaction.method);
+    println!("Backend:     {}", transaction.backend);
+    if let Some(error) = &transaction.error {
+        println!("Error:       {}", error.trim());
+    }
+    for change in transaction.changes.iter() {
+        print_change(change);
+    }
+
+    Ok(())
+}
+
+/// Print a change of a transaction.
+///
+/// # Arguments
+/// + `change` - The change to print.
+fn print_change(change: &upm::history::Change) {
+    match (&change.old_version, &change.new_version) {
+        (Some(old), Some(new)) => println!("  {}: {} -> {}", change.name, old, new),
+        (None, Some(new)) => println!("  {}: installed {}", change.name, new),
+        (Some(old), None) => println!("  {}: removed {}", change.name, old),
+        (None, None) => println!("  {}", change.name),
+    }
+}
+
+fn do_job_rollback(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &RollbackArgs,
+) -> anyhow::Result<()> {
+    let path = std::path::Path::new(upm::history::DEFAULT_HISTORY_PATH);
+    let transaction = match upm::history::History::new(path).find(args.id)? {
+        Some(v) => v,
+        None => return Err(anyhow::anyhow!("transaction {} not found.", args.id)),
+    };
+    if transaction.changes.is_empty() {
+        println!("Transaction {} changed nothing.", args.id);
+        return Ok(());
+    }
+
+    let info = match router.info(&transaction.backend)? {
+        upm::BackendSetup::NotInstalled => {
+            return Err(anyhow::anyhow!(
+                "backend '{}' is not installed.",
+                transaction.backend
+            ));
+        }
+        upm::BackendSetup::Installed(v) => v,
+    };
+
+    println!("The following changes will be undone:");
+    for change in transaction.changes.iter() {
+        print_change(change);
+    }
+    if !args.yes && !confirm("Do you want to continue?")? {
+        return Ok(());
+    }
+
+    let params = upm::rpc::RollbackParams { id: transaction.id };
+    run_recorded::<upm::rpc::Rollback>(ctl, &transaction.backend, &info, &params)?;
+
+    Ok(())
+}
+
+/// Describe a node of a dependency tree.
+///
+/// # Arguments
+/// + `dep` - The node.
+///
+/// # Returns
+/// The name with the constraint, the kind unless it is a plain dependency
+/// and whether the package is installed.
+fn format_dependency(dep: &upm::rpc::Dependency) -> String {
+    let mut ret = dep.name.clone();
+    if !dep.constraint.is_empty() {
+        ret.push_str(&format!(" ({})", dep.constraint));
+    }
+    let kind = match dep.kind {
+        upm::rpc::DependencyKind::Depends => "",
+        upm::rpc::DependencyKind::Recommends => " [recommends]",
+        upm::rpc::DependencyKind::Runtime => " [runtime]",
+        upm::rpc::DependencyKind::Build => " [build]",
+        upm::rpc::DependencyKind::Extension => " [extension]",
+    };
+    ret.push_str(kind);
+    if dep.installed {
+        ret.push_str(" [installed]");
+    }
+    ret
+}
+
+/// Print a dependency tree.
+///
+/// # Arguments
+/// + `deps` - The nodes below the current one.
+/// + `indent` - The lines drawn for the levels above.
+fn print_dependency_tree(deps: &[upm::rpc::Dependency], indent: &str) {
+    for (i, dep) in deps.iter().enumerate() {
+        let last = i + 1 == deps.len();
+        println!(
+            "{}{}{}",
+            indent,
+            if last { "└── " } else { "├── " },
+            format_dependency(dep)
+        );
+        let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+        print_dependency_tree(&dep.deps, &indent);
+    }
+}
+
+/// Collect every node of a dependency tree once, in the order they appear.
+///
+/// # Arguments
+/// + `deps` - The nodes below the current one.
+/// + `ret` - The nodes collected so far.
+fn flatten_dependencies<'a>(
+    deps: &'a [upm::rpc::Dependency],
+    ret: &mut Vec<&'a upm::rpc::Dependency>,
+) {
+    for dep in deps {
+        if !ret.iter().any(|x| x.name == dep.name) {
+            ret.push(dep);
+        }
+        flatten_dependencies(&dep.deps, ret);
+    }
+}
+
+fn do_job_deps(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &DepsArgs,
+    reverse: bool,
+) -> anyhow::Result<()> {
+    let (names, name) = match split_backend(router, &args.name) {
+        (Some(backend), name) => (vec![backend.to_string()], name),
+        (None, name) => match &args.backend {
+            Some(backend) => (vec![backend.clone()], name),
+            None => (sorted_backends(ctl, router), name),
+        },
+    };
+
+    let mut found = false;
+    for backend in names {
+        let info = match router.info(&backend)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::DepsParams {
+            backend_name: backend.clone(),
+            name: name.to_string(),
+            options: upm::rpc::DepsOptions {
+                reverse,
+                installed: args.installed,
+            },
+        };
+        let rsp = if info.deps {
+            ctl.root_worker.call::<upm::rpc::Deps>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Deps>(&params)?
+        };
+        let Some(deps) = rsp.deps else {
+            continue;
+        };
+
+        if found {
+            println!();
+        }
+        found = true;
+        println!("{}:{}", backend, name);
+        if args.flat {
+            let mut flat = Vec::new();
+            flatten_dependencies(&deps, &mut flat);
+            for dep in flat {
+                println!("  {}", format_dependency(dep));
+            }
+        } else {
+            print_dependency_tree(&deps, "");
+        }
+    }
+
+    if !found {
+        return Err(anyhow::anyhow!("package '{}' not found.", name));
+    }
+
+    Ok(())
+}
+
+/// Find the file a path given on the command line refers to.
+///
+/// A bare name that is not in the current directory is looked up in `PATH`,
+/// like a command.
+///
+/// # Arguments
+/// + `arg` - The path given on the command line.
+///
+/// # Returns
+/// The absolute path.
+fn locate_file(arg: &str) -> anyhow::Result<std::path::PathBuf> {
+    let path = std::path::Path::new(arg);
+    if !arg.contains('/') && !path.exists() {
+        let dirs = std::env::var_os("PATH").unwrap_or_default();
+        if let Some(v) = std::env::split_paths(&dirs)
+            .map(|dir| dir.join(arg))
+            .find(|v| v.is_file())
+        {
+            return Ok(v);
+        }
+    }
+
+    Ok(std::path::absolute(path)?)
+}
+
+fn do_job_owns(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &OwnsArgs,
+) -> anyhow::Result<()> {
+    let path = locate_file(&args.path)?;
+    let names = match &args.backend {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut found = false;
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::OwnsParams {
+            backend_name: name,
+            path: path.clone(),
+        };
+        let rsp = if info.owns {
+            ctl.root_worker.call::<upm::rpc::Owns>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Owns>(&params)?
+        };
+        for owner in rsp.owners.iter() {
+            println!("{}:{} {}", owner.backend, owner.name, owner.version);
+            found = true;
+        }
+    }
+
+    if !found {
+        return Err(anyhow::anyhow!("no package owns '{}'.", path.display()));
+    }
+
+    Ok(())
+}
+
+fn do_job_files(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &InfoArgs,
+) -> anyhow::Result<()> {
+    let (names, name) = match split_backend(router, &args.name) {
+        (Some(backend), name) => (vec![backend.to_string()], name),
+        (None, name) => match &args.backend {
+            Some(backend) => (vec![backend.clone()], name),
+            None => (sorted_backends(ctl, router), name),
+        },
+    };
+
+    let mut files = Vec::new();
+    let mut found = false;
+    for backend in names {
+        let info = match router.info(&backend)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::FilesParams {
+            backend_name: backend,
+            name: name.to_string(),
+        };
+        let rsp = if info.files {
+            ctl.root_worker.call::<upm::rpc::Files>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Files>(&params)?
+        };
+        if let Some(v) = rsp.files {
+            files.extend(v);
+            found = true;
+        }
+    }
+
+    if !found {
+        return Err(anyhow::anyhow!("package '{}' is not installed.", name));
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&files)?);
+        return Ok(());
+    }
+    for file in files.iter() {
+        println!("{}", file.display());
+    }
+
+    Ok(())
+}
+
+fn do_job_verify(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &VerifyArgs,
+) -> anyhow::Result<()> {
+    let groups = if args.name.is_empty() {
+        let names = match &args.backend {
+            Some(v) => vec![v.clone()],
+            None => sorted_backends(ctl, router),
+        };
+        names.into_iter().map(|v| (v, Vec::new())).collect()
+    } else {
+        let pkg = PackageName {
+            backend: args.backend.clone(),
+            name: args.name.clone(),
+        };
+        group_by_backend(router, &pkg)?
+            .into_iter()
+            .map(|(k, v)| (k, v.into_iter().map(|x| x.name).collect()))
+            .collect::<Vec<(String, Vec<String>)>>()
+    };
+
+    let mut report = serde_json::Map::new();
+    let mut count = 0;
+    for (name, pkgs) in groups {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled if pkgs.is_empty() && args.backend.is_none() => {
+                continue;
+            }
+            upm::BackendSetup::NotInstalled => {
+                return Err(anyhow::anyhow!("backend '{}' is not installed.", name));
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::VerifyParams {
+            backend_name: name.clone(),
+            pkgs,
+        };
+        let rsp = if info.verify {
+            ctl.root_worker.call::<upm::rpc::Verify>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::Verify>(&params)?
+        };
+        count += rsp.issues.len();
+
+        if args.json {
+            report.insert(name, serde_json::to_value(&rsp.issues)?);
+            continue;
+        }
+        if rsp.issues.is_empty() {
+            println!("{}: ok", name);
+            continue;
+        }
+
+        let statuses = [
+            (upm::rpc::FileStatus::Modified, "modified"),
+            (upm::rpc::FileStatus::Missing, "missing"),
+            (upm::rpc::FileStatus::Unexpected, "unexpected"),
+        ];
+        let summary: Vec<String> = statuses
+            .iter()
+            .filter_map(|(status, label)| {
+                let n = rsp.issues.iter().filter(|x| x.status == *status).count();
+                (n > 0).then(|| format!("{} {}", n, label))
+            })
+            .collect();
+        println!("{}: {}", name, summary.join(", "));
+        for issue in rsp.issues.iter() {
+            let label = statuses
+                .iter()
+                .find(|(status, _)| *status == issue.status)
+                .map_or("", |(_, label)| label);
+            let pkg = if issue.name.is_empty() {
+                "-"
+            } else {
+                issue.name.as_str()
+            };
+            println!("  {:<10} {} {}", label, pkg, issue.path);
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    // Fail so that monitoring notices.
+    if count > 0 {
+        return Err(anyhow::anyhow!("{} problems found.", count));
+    }
+
+    Ok(())
+}
+
+/// Find the backend of a repository given on the command line.
+///
+/// # Arguments
+/// + `router` - The router that knows the backends.
+/// + `backend` - The backend given with `--backend`.
+/// + `arg` - The repository given on the command line.
+///
+/// # Returns
+/// The name of backend, its privileges and the name of repository.
+fn repo_backend(
+    router: &mut WorkerRouter,
+    backend: &Option<String>,
+    arg: &str,
+) -> anyhow::Result<(String, upm::MethodPrivilege, String)> {
+    let (name, repo) = split_backend(router, arg);
+    let name = match name.or(backend.as_deref()) {
+        Some(v) => v.to_string(),
+        None => {
+            return Err(anyhow::anyhow!(
+                "please specify the backend of '{}' with --backend or as 'backend:{}'.",
+                arg,
+                arg
+            ));
+        }
+    };
+    let repo = repo.to_string();
+
+    match router.info(&name)? {
+        upm::BackendSetup::NotInstalled => {
+            Err(anyhow::anyhow!("backend '{}' is not installed.", name))
+        }
+        upm::BackendSetup::Installed(v) => Ok((name, v, repo)),
+    }
+}
+
+fn do_job_repo_list(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &RepoListArgs,
+) -> anyhow::Result<()> {
+    let names = match &args.name {
+        Some(v) => vec![v.clone()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut repos = Vec::new();
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let params = upm::rpc::RepoListParams { backend_name: name };
+        let rsp = if info.repos {
+            ctl.root_worker.call::<upm::rpc::RepoList>(&params)?
+        } else {
+            ctl.normal_worker.call::<upm::rpc::RepoList>(&params)?
+        };
+        repos.extend(rsp.repos);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&repos)?);
+        return Ok(());
+    }
+    for repo in repos.iter() {
+        println!(
+            "{}:{} {}{}",
+            repo.backend,
+            repo.name,
+            repo.url,
+            if repo.enabled { "" } else { " (disabled)" }
+        );
+    }
+
+    Ok(())
+}
+
+fn do_job_repo_add(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &RepoAddArgs,
+) -> anyhow::Result<()> {
+    let (backend, info, name) = repo_backend(router, &args.backend, &args.name)?;
+
+    let params = upm::rpc::RepoAddParams {
+        repo: upm::rpc::Repository {
+            name,
+            url: args.url.clone().unwrap_or_default(),
+            enabled: !args.disabled,
+            backend: backend.clone(),
+            suites: args.suites.clone(),
+            components: args.components.clone(),
+        },
+        backend_name: backend,
+        options: upm::rpc::RepoAddOptions {
+            key: args.key.clone(),
+        },
+    };
+    if info.repo_edit {
+        ctl.root_worker.call::<upm::rpc::RepoAdd>(&params)?;
+    } else {
+        ctl.normal_worker.call::<upm::rpc::RepoAdd>(&params)?;
+    }
+
+    Ok(())
+}
+
+fn do_job_repo_edit<R>(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &RepoName,
+) -> anyhow::Result<()>
+where
+    R: upm::rpc::Request<Params = upm::rpc::RepoParams>,
+{
+    let (backend, info, name) = repo_backend(router, &args.backend, &args.name)?;
+
+    let params = upm::rpc::RepoParams {
+        backend_name: backend,
+        name,
+    };
+    if info.requires_root(R::METHOD) {
+        ctl.root_worker.call::<R>(&params)?;
+    } else {
+        ctl.normal_worker.call::<R>(&params)?;
+    }
+
+    Ok(())
+}
+
+fn do_job_repo(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &RepoArgs,
+) -> anyhow::Result<()> {
+    match &args.action {
+        RepoAction::List(v) => do_job_repo_list(ctl, router, v),
+        RepoAction::Add(v) => do_job_repo_add(ctl, router, v),
+        RepoAction::Remove(v) => do_job_repo_edit::<upm::rpc::RepoRemove>(ctl, router, v),
+        RepoAction::Enable(v) => do_job_repo_edit::<upm::rpc::RepoEnable>(ctl, router, v),
+        RepoAction::Disable(v) => do_job_repo_edit::<upm::rpc::RepoDisable>(ctl, router, v),
+    }
+}
+
+/// Get the repositories of a backend.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `name` - The name of backend.
+/// + `info` - The privileges of the backend.
+///
+/// # Returns
+/// The repositories.
+fn query_repos(
+    ctl: &mut Controller,
+    name: &str,
+    info: &upm::MethodPrivilege,
+) -> anyhow::Result<Vec<upm::rpc::Repository>> {
+    let params = upm::rpc::RepoListParams {
+        backend_name: name.to_string(),
+    };
+    let rsp = if info.repos {
+        ctl.root_worker.call::<upm::rpc::RepoList>(&params)?
+    } else {
+        ctl.normal_worker.call::<upm::rpc::RepoList>(&params)?
+    };
+
+    Ok(rsp.repos)
+}
+
+fn do_job_export(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &ExportArgs,
+) -> anyhow::Result<()> {
+    let format = if args.json {
+        upm::manifest::ManifestFormat::Json
+    } else if args.brewfile {
+        upm::manifest::ManifestFormat::Brewfile
+    } else {
+        upm::manifest::ManifestFormat::Toml
+    };
+    let names = match &args.backend {
+        Some(v) => vec![v.clone()],
+        None if args.brewfile => vec!["brew".to_string()],
+        None => sorted_backends(ctl, router),
+    };
+
+    let mut manifest = upm::manifest::Manifest::default();
+    for name in names {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => continue,
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        let mut section = upm::manifest::BackendManifest::default();
+        for item in query_installed(ctl, &name, &info)? {
+            if !item.manual {
+                continue;
+            }
+            // brew installs casks and formulae alike, only Brewfiles care.
+            if name == "brew" && item.origin.starts_with("homebrew/cask") {
+                section.casks.push(item.name);
+            } else if args.pin {
+                section
+                    .packages
+                    .push(format!("{}={}", item.name, item.version));
+            } else {
+                section.packages.push(item.name);
+            }
+        }
+        for repo in query_repos(ctl, &name, &info)? {
+            section.repos.push(upm::manifest::ManifestRepo {
+                name: repo.name,
+                url: repo.url,
+                enabled: repo.enabled,
+                suites: repo.suites,
+                components: repo.components,
+                key: None,
+            });
+        }
+        // Multi-arch packages are listed once per architecture.
+        section.packages.dedup();
+        manifest.backends.insert(name, section);
+    }
+
+    let data = manifest.format(format)?;
+    match &args.path {
+        Some(path) => std::fs::write(path, data)?,
+        None => print!("{}", data),
+    }
+
+    Ok(())
+}
+
+fn do_job_import(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &ImportArgs,
+) -> anyhow::Result<()> {
+    let format = if args.json {
+        upm::manifest::ManifestFormat::Json
+    } else if args.brewfile {
+        upm::manifest::ManifestFormat::Brewfile
+    } else {
+        upm::manifest::ManifestFormat::from_path(&args.path)
+    };
+    let manifest = upm::manifest::Manifest::load(&args.path, format)?;
+
+    for (name, section) in manifest.backends {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => {
+                log::warn!("backend '{}' is not installed, skip it.", name);
+                continue;
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        // Add the repositories first, the packages may come from them.
+        let mut known: Vec<String> = query_repos(ctl, &name, &info)?
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        let mut added = false;
+        for repo in section.repos {
+            if known.contains(&repo.name) {
+                continue;
+            }
+            known.push(repo.name.clone());
+            println!("add repository {}:{}", name, repo.name);
+            if args.dry_run {
+                continue;
+            }
+
+            let params = upm::rpc::RepoAddParams {
+                backend_name: name.clone(),
+                repo: upm::rpc::Repository {
+                    name: repo.name,
+                    url: repo.url,
+                    enabled: repo.enabled,
+                    backend: name.clone(),
+                    suites: repo.suites,
+                    components: repo.components,
+                },
+                options: upm::rpc::RepoAddOptions { key: repo.key },
+            };
+            if info.repo_edit {
+                ctl.root_worker.call::<upm::rpc::RepoAdd>(&params)?;
+            } else {
+                ctl.normal_worker.call::<upm::rpc::RepoAdd>(&params)?;
+            }
+            added = true;
+        }
+        if added {
+            do_job_update_item(ctl, router, &name)?;
+        }
+
+        let installed = query_installed(ctl, &name, &info)?;
+        let pkgs: Vec<upm::rpc::PackageSpec> = section
+            .packages
+            .iter()
+            .chain(section.casks.iter())
+            .map(|v| upm::manifest::Requirement::parse(v))
+            .filter(|x| !installed.iter().any(|v| v.name == x.name))
+            .map(|x| upm::rpc::PackageSpec {
+                version: x.pinned().map(|v| v.to_string()),
+                name: x.name,
+                vendor: None,
+            })
+            .collect();
+        if pkgs.is_empty() {
+            continue;
+        }
+        for pkg in pkgs.iter() {
+            match &pkg.version {
+                Some(v) => println!("install {}:{}={}", name, pkg.name, v),
+                None => println!("install {}:{}", name, pkg.name),
+            }
+        }
+        if args.dry_run {
+            continue;
+        }
+
+        let params = upm::rpc::InstallParams {
+            backend_name: name.clone(),
+            pkgs,
+        };
+        run_recorded::<upm::rpc::Install>(ctl, &name, &info, &params)?;
+    }
+
+    Ok(())
+}
+
+/// Compare the installed packages with a manifest.
+///
+/// A backend that is not installed has nothing installed.
+///
+/// # Arguments
+/// + `ctl` - The controller.
+/// + `router` - The router that knows the backends.
+/// + `manifest` - The manifest of the desired packages.
+/// + `prune` - Also report explicitly installed packages that are not
+///   declared.
+///
+/// # Returns
+/// The packages that differ.
+fn manifest_drift(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    manifest: &upm::manifest::Manifest,
+    prune: bool,
+) -> anyhow::Result<Vec<upm::manifest::Drift>> {
+    let mut ret = Vec::new();
+    for (name, section) in manifest.backends.iter() {
+        let installed = match router.info(name)? {
+            upm::BackendSetup::NotInstalled => Vec::new(),
+            upm::BackendSetup::Installed(v) => query_installed(ctl, name, &v)?,
+        };
+        ret.extend(section.drift(name, &installed, prune));
+    }
+
+    Ok(ret)
+}
+
+fn do_job_diff(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &DiffArgs,
+) -> anyhow::Result<()> {
+    let format = upm::manifest::ManifestFormat::from_path(&args.path);
+    let manifest = upm::manifest::Manifest::load(&args.path, format)?;
+    let drift = manifest_drift(ctl, router, &manifest, args.prune)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&drift)?);
+    } else {
+        for item in drift.iter() {
+            let installed = item.installed.as_deref().unwrap_or_default();
+            match item.kind {
+                upm::manifest::DriftKind::Missing => {
+                    println!("missing        {}:{}", item.backend, item.wanted)
+                }
+                upm::manifest::DriftKind::Extra => {
+                    println!(
+                        "extra          {}:{} {}",
+                        item.backend, item.name, installed
+                    )
+                }
+                upm::manifest::DriftKind::WrongVersion => println!(
+                    "wrong version  {}:{} {}, want {}",
+                    item.backend, item.name, installed, item.wanted
+                ),
+            }
+        }
+    }
+
+    // Fail like diff(1) so that scripts notice.
+    if !drift.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} packages differ from '{}'.",
+            drift.len(),
+            args.path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// What `converge` changes on a backend.
+#[derive(Debug, Default)]
+struct ConvergePlan {
+    /// The packages to remove.
+    remove: Vec<upm::rpc::PackageSpec>,
+
+    /// The packages to install, or to install in another version.
+    install: Vec<upm::rpc::PackageSpec>,
+
+    /// The packages to upgrade to the newest version.
+    upgrade: Vec<String>,
+}
+
+fn do_job_converge(
+    ctl: &mut Controller,
+    router: &mut WorkerRouter,
+    args: &ConvergeArgs,
+) -> anyhow::Result<()> {
+    let format = upm::manifest::ManifestFormat::from_path(&args.path);
+    let manifest = upm::manifest::Manifest::load(&args.path, format)?;
+    let drift = manifest_drift(ctl, router, &manifest, args.prune)?;
+
+    // Plan what to do per backend: remove, install and upgrade.
+    let mut plans: Vec<(String, ConvergePlan)> = Vec::new();
+    let mut unresolved = Vec::new();
+    for backend in manifest.backends.keys() {
+        let mut plan = ConvergePlan::default();
+        for item in drift.iter().filter(|x| x.backend == *backend) {
+            let installed = item.installed.as_deref().unwrap_or_default();
+            let req = upm::manifest::Requirement::parse(&item.wanted);
+            match item.kind {
+                upm::manifest::DriftKind::Extra => {
+                    println!("remove   {}:{} {}", backend, item.name, installed);
+                    plan.remove.push(upm::rpc::PackageSpec {
+                        name: item.name.clone(),
+                        version: None,
+                        vendor: None,
+                    });
+                }
+                upm::manifest::DriftKind::Missing => {
+                    println!("install  {}:{}", backend, item.wanted);
+                    plan.install.push(upm::rpc::PackageSpec {
+                        name: item.name.clone(),
+                        version: req.pinned().map(|v| v.to_string()),
+                        vendor: None,
+                    });
+                }
+                upm::manifest::DriftKind::WrongVersion => {
+                    if let Some(version) = req.pinned() {
+                        println!(
+                            "install  {}:{} ({} installed)",
+                            backend, item.wanted, installed
+                        );
+                        plan.install.push(upm::rpc::PackageSpec {
+                            name: item.name.clone(),
+                            version: Some(version.to_string()),
+                            vendor: None,
+                        });
+                    } else if matches!(
+                        req.constraint,
+                        Some((
+                            upm::manifest::VersionOp::Ge | upm::manifest::VersionOp::Gt,
+                            _
+                        ))
+                    ) {
+                        println!(
+                            "upgrade  {}:{} ({} installed)",
+                            backend, item.wanted, installed
+                        );
+                        plan.upgrade.push(item.name.clone());
+                    } else {
+                        // Only the backend knows which older versions exist.
+                        println!(
+                            "cannot   {}:{} ({} installed), pin an exact version",
+                            backend, item.wanted, installed
+                        );
+                        unresolved.push(format!("{}:{}", backend, item.wanted));
+                    }
+                }
+            }
+        }
+        if !plan.remove.is_empty() || !plan.install.is_empty() || !plan.upgrade.is_empty() {
+            plans.push((backend.clone(), plan));
+        }
+    }
+
+    if !args.apply {
+        if !plans.is_empty() {
+            println!("dry run, pass --apply to make these changes.");
+        }
+        return Ok(());
+    }
+    if !unresolved.is_empty() {
+        return Err(anyhow::anyhow!(
+            "cannot converge {}, pin exact versions.",
+            unresolved.join(", ")
+        ));
+    }
+
+    for (name, plan) in plans {
+        let info = match router.info(&name)? {
+            upm::BackendSetup::NotInstalled => {
+                return Err(anyhow::anyhow!("backend '{}' is not installed.", name));
+            }
+            upm::BackendSetup::Installed(v) => v,
+        };
+
+        if !plan.remove.is_empty() {
+            let params = upm::rpc::UninstallParams {
+                backend_name: name.clone(),
+                pkgs: plan.remove,
+                options: upm::rpc::UninstallOptions {
+                    purge: false,
+                    force: false,
+                    autoremove: false,
+                    dry_run: false,
+                },
+            };
+            run_recorded::<upm::rpc::Uninstall>(ctl, &name, &info, &params)?;
+        }
+        if !plan.install.is_empty() {
+            let params = upm::rpc::InstallParams {
+                backend_name: name.clone(),
+                pkgs: plan.install,
+            };
+            run_recorded::<upm::rpc::Install>(ctl, &name, &info, &params)?;
+        }
+        if !plan.upgrade.is_empty() {
+            let params = upm::rpc::UpgradeParams {
+                backend_name: name.clone(),
+                options: upm::rpc::UpgradeOptions {
+                    pkgs: plan.upgrade,
+                    apt: ctl.config.upgrade.apt.clone(),
+                    flatpak: ctl.config.upgrade.flatpak.clone(),
+                    brew: ctl.config.upgrade.brew.clone(),
+                    ..Default::default()
+                },
+            };
+            run_recorded::<upm::rpc::Upgrade>(ctl, &name, &info, &params)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn do_job(ctl: &mut Controller, args: &UpmArgs, mut router: WorkerRouter) -> anyhow::Result<()> {
+    let mode = args
+        .mode
+        .as_ref()
+        .unwrap_or(&ActionMode::Update(BackendName { name: None }));
+    match mode {
+        ActionMode::Update(v) => do_job_update(ctl, &mut router, &v.name),
+        ActionMode::Outdated(v) => do_job_outdated(ctl, &mut router, v),
+        ActionMode::Upgrade(v) => do_job_upgrade(ctl, &mut router, v),
+        ActionMode::Install(v) => do_job_install(ctl, &mut router, v),
+        ActionMode::Uninstall(v) => do_job_uninstall(ctl, &mut router, v),
+        ActionMode::Search(v) => do_job_search(ctl, &mut router, v),
+        ActionMode::Info(v) => do_job_info(ctl, &mut router, v),
+        ActionMode::List(v) => do_job_list(ctl, &mut router, v),
+        ActionMode::Changelog(v) => do_job_changelog(ctl, &mut router, v),
+        ActionMode::Deps(v) => do_job_deps(ctl, &mut router, v, false),
+        ActionMode::Rdeps(v) => do_job_deps(ctl, &mut router, v, true),
+        ActionMode::Owns(v) => do_job_owns(ctl, &mut router, v),
+        ActionMode::Files(v) => do_job_files(ctl, &mut router, v),
+        ActionMode::Verify(v) => do_job_verify(ctl, &mut router, v),
+        ActionMode::Hold(v) => do_job_hold(ctl, &mut router, v, true),
+        ActionMode::Unhold(v) => do_job_hold(ctl, &mut router, v, false),
+        ActionMode::Holds(v) => do_job_holds(ctl, &mut router, &v.name),
+        ActionMode::Clean(v) => do_job_clean(ctl, &mut router, v),
+        ActionMode::Autoremove(v) => do_job_autoremove(ctl, &mut router, v),
+        ActionMode::History(v) => do_job_history(v),
+        ActionMode::Rollback(v) => do_job_rollback(ctl, &mut router, v),
+        ActionMode::Repo(v) => do_job_repo(ctl, &mut router, v),
+        ActionMode::Export(v) => do_job_export(ctl, &mut router, v),
+        ActionMode::Import(v) => do_job_import(ctl, &mut router, v),
+        ActionMode::Diff(v) => do_job_diff(ctl, &mut router, v),
+        ActionMode::Converge(v) => do_job_converge(ctl, &mut router, v),
+    }
 }
 
 fn main() {
@@ -0,0 +1,661 @@
+use serde::{Deserialize, Serialize};
+
+/// The formats a manifest can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Toml,
+    Json,
+    /// A Homebrew Brewfile, which only holds the brew backend.
+    Brewfile,
+}
+
+impl ManifestFormat {
+    /// Guess the format of a manifest from its file name.
+    ///
+    /// # Arguments
+    /// + `path` - The path of the manifest.
+    ///
+    /// # Returns
+    /// The format, TOML unless the name says otherwise.
+    pub fn from_path(path: &std::path::Path) -> ManifestFormat {
+        let name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("json") => ManifestFormat::Json,
+            Some("brewfile") => ManifestFormat::Brewfile,
+            _ if name == "Brewfile" => ManifestFormat::Brewfile,
+            _ => ManifestFormat::Toml,
+        }
+    }
+}
+
+/// A manifest of explicitly installed packages and repositories, grouped by
+/// backend.
+///
+/// ```toml
+/// [apt]
+/// packages = ["curl", "git=1:2.39.5-0+deb12u2"]
+///
+/// [[apt.repos]]
+/// name = "docker"
+/// url = "https://download.docker.com/linux/debian"
+/// suites = ["bookworm"]
+/// components = ["stable"]
+/// key = "https://download.docker.com/linux/debian/gpg"
+///
+/// [brew]
+/// packages = ["ripgrep"]
+/// casks = ["firefox"]
+///
+/// [flatpak]
+/// packages = ["org.gimp.GIMP"]
+/// absent = ["org.mozilla.firefox"]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Manifest {
+    /// The packages and repositories of each backend.
+    pub backends: std::collections::BTreeMap<String, BackendManifest>,
+}
+
+/// The packages and repositories of a backend in a manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendManifest {
+    /// The packages, as `name` or `name` followed by a version constraint,
+    /// e.g. `name=version` or `name>=version`.
+    #[serde(default)]
+    pub packages: Vec<String>,
+
+    /// The brew casks, kept apart from formulae so that Brewfiles can tell
+    /// them apart.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub casks: Vec<String>,
+
+    /// The repositories the packages come from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repos: Vec<ManifestRepo>,
+
+    /// The packages that must not be installed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub absent: Vec<String>,
+}
+
+/// A repository in a manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestRepo {
+    /// The name of repository.
+    pub name: String,
+
+    /// The URL of repository, empty for brew taps on GitHub.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+
+    /// Whether the backend uses the repository.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// The suites of an apt repository.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suites: Vec<String>,
+
+    /// The components of an apt repository.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<String>,
+
+    /// The signing key, as a local path or URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// How the installed version must compare to a declared one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+/// A package declared in a manifest, with an optional version constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// The name of package.
+    pub name: String,
+
+    /// The version constraint, if any.
+    pub constraint: Option<(VersionOp, String)>,
+}
+
+impl Requirement {
+    /// Parse a declared package.
+    ///
+    /// # Arguments
+    /// + `spec` - The package, as `name` or `name` followed by `=`, `>=`,
+    ///   `>`, `<=` or `<` and a version.
+    ///
+    /// # Returns
+    /// The requirement.
+    pub fn parse(spec: &str) -> Requirement {
+        let Some(i) = spec.find(['=', '<', '>']) else {
+            return Requirement {
+                name: spec.to_string(),
+                constraint: None,
+            };
+        };
+
+        let (name, rest) = spec.split_at(i);
+        let (op, version) = [
+            (">=", VersionOp::Ge),
+            ("<=", VersionOp::Le),
+            (">", VersionOp::Gt),
+            ("<", VersionOp::Lt),
+            ("=", VersionOp::Eq),
+        ]
+        .iter()
+        .find_map(|(k, op)| rest.strip_prefix(k).map(|v| (*op, v)))
+        .unwrap_or((VersionOp::Eq, rest));
+        Requirement {
+            name: name.to_string(),
+            constraint: Some((op, version.to_string())),
+        }
+    }
+
+    /// Check whether an installed version satisfies the requirement.
+    ///
+    /// # Arguments
+    /// + `version` - The installed version.
+    ///
+    /// # Returns
+    /// `true` if there is no constraint or the version meets it.
+    pub fn matches(&self, version: &str) -> bool {
+        use std::cmp::Ordering;
+
+        let Some((op, wanted)) = &self.constraint else {
+            return true;
+        };
+        let ord = crate::backend::compare_versions(version, wanted);
+        match op {
+            VersionOp::Eq => ord == Ordering::Equal,
+            VersionOp::Ge => ord != Ordering::Less,
+            VersionOp::Gt => ord == Ordering::Greater,
+            VersionOp::Le => ord != Ordering::Greater,
+            VersionOp::Lt => ord == Ordering::Less,
+        }
+    }
+
+    /// The version to install to meet the requirement.
+    ///
+    /// # Returns
+    /// The version of an `=` constraint, `None` if any newer one will do.
+    pub fn pinned(&self) -> Option<&str> {
+        match &self.constraint {
+            Some((VersionOp::Eq, v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((op, version)) = &self.constraint else {
+            return write!(f, "{}", self.name);
+        };
+        let op = match op {
+            VersionOp::Eq => "=",
+            VersionOp::Ge => ">=",
+            VersionOp::Gt => ">",
+            VersionOp::Le => "<=",
+            VersionOp::Lt => "<",
+        };
+        write!(f, "{}{}{}", self.name, op, version)
+    }
+}
+
+/// How installed packages differ from a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// A declared package is not installed.
+    Missing,
+    /// A package is installed that must not be.
+    Extra,
+    /// The installed version does not meet the declared constraint.
+    WrongVersion,
+}
+
+/// A package that differs from a manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Drift {
+    /// The name of backend.
+    pub backend: String,
+
+    /// The name of package.
+    pub name: String,
+
+    /// How the package differs.
+    pub kind: DriftKind,
+
+    /// The installed version, `None` if not installed.
+    pub installed: Option<String>,
+
+    /// The declared package, e.g. `git>=2.40`. Empty for extra packages.
+    pub wanted: String,
+}
+
+impl BackendManifest {
+    /// Compare the installed packages of the backend with the manifest.
+    ///
+    /// # Arguments
+    /// + `backend` - The name of backend.
+    /// + `installed` - The installed packages.
+    /// + `prune` - Also report explicitly installed packages the manifest
+    ///   does not declare as extra.
+    ///
+    /// # Returns
+    /// The packages that differ.
+    pub fn drift(
+        &self,
+        backend: &str,
+        installed: &[crate::rpc::InstalledItem],
+        prune: bool,
+    ) -> Vec<Drift> {
+        let find = |name: &str| installed.iter().find(|x| x.name == name);
+        let wanted: Vec<Requirement> = self
+            .packages
+            .iter()
+            .chain(self.casks.iter())
+            .map(|v| Requirement::parse(v))
+            .collect();
+
+        let mut ret = Vec::new();
+        for req in wanted.iter() {
+            let kind = match find(&req.name) {
+                None => DriftKind::Missing,
+                Some(item) if !req.matches(&item.version) => DriftKind::WrongVersion,
+                Some(_) => continue,
+            };
+            ret.push(Drift {
+                backend: backend.to_string(),
+                name: req.name.clone(),
+                kind,
+                installed: find(&req.name).map(|x| x.version.clone()),
+                wanted: req.to_string(),
+            });
+        }
+
+        let mut extra: Vec<&str> = self
+            .absent
+            .iter()
+            .filter(|v| find(v).is_some())
+            .map(|v| v.as_str())
+            .collect();
+        if prune {
+            for item in installed.iter() {
+                if item.manual
+                    && !wanted.iter().any(|x| x.name == item.name)
+                    && !extra.contains(&item.name.as_str())
+                {
+                    extra.push(&item.name);
+                }
+            }
+        }
+        for name in extra {
+            ret.push(Drift {
+                backend: backend.to_string(),
+                name: name.to_string(),
+                kind: DriftKind::Extra,
+                installed: find(name).map(|x| x.version.clone()),
+                wanted: String::new(),
+            });
+        }
+
+        ret
+    }
+}
+
+impl Manifest {
+    /// Load a manifest from a file.
+    ///
+    /// # Arguments
+    /// + `path` - The path of the manifest.
+    /// + `format` - The format of the manifest.
+    ///
+    /// # Returns
+    /// The manifest.
+    pub fn load(path: &std::path::Path, format: ManifestFormat) -> anyhow::Result<Manifest> {
+        let data = std::fs::read_to_string(path)?;
+        Manifest::parse(&data, format)
+            .map_err(|e| anyhow::anyhow!("invalid manifest '{}': {}", path.display(), e))
+    }
+
+    /// Parse a manifest.
+    ///
+    /// # Arguments
+    /// + `data` - The content of the manifest.
+    /// + `format` - The format of the manifest.
+    ///
+    /// # Returns
+    /// The manifest.
+    pub fn parse(data: &str, format: ManifestFormat) -> anyhow::Result<Manifest> {
+        match format {
+            ManifestFormat::Toml => Ok(toml::from_str(data)?),
+            ManifestFormat::Json => Ok(serde_json::from_str(data)?),
+            ManifestFormat::Brewfile => parse_brewfile(data),
+        }
+    }
+
+    /// Write the manifest out.
+    ///
+    /// # Arguments
+    /// + `format` - The format to write.
+    ///
+    /// # Returns
+    /// The content of the manifest.
+    pub fn format(&self, format: ManifestFormat) -> anyhow::Result<String> {
+        match format {
+            ManifestFormat::Toml => Ok(toml::to_string(self)?),
+            ManifestFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            ManifestFormat::Brewfile => Ok(format_brewfile(self)),
+        }
+    }
+}
+
+/// Parse a Brewfile into the brew section of a manifest.
+///
+/// Only `tap`, `brew` and `cask` lines are understood, the options after the
+/// name are ignored except the URL of a tap.
+///
+/// # Arguments
+/// + `data` - The content of the Brewfile.
+///
+/// # Returns
+/// The manifest.
+fn parse_brewfile(data: &str) -> anyhow::Result<Manifest> {
+    let re = regex::Regex::new(r#"^(\w+)\s+"([^"]+)"(?:\s*,\s*"([^"]+)")?"#).unwrap();
+
+    let mut brew = BackendManifest::default();
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(caps) = re.captures(line) else {
+            return Err(anyhow::anyhow!("line {}: cannot parse '{}'.", i + 1, line));
+        };
+
+        let name = caps[2].to_string();
+        match &caps[1] {
+            "tap" => brew.repos.push(ManifestRepo {
+                name,
+                url: caps
+                    .get(3)
+                    .map(|v| v.as_str().to_string())
+                    .unwrap_or_default(),
+                enabled: true,
+                suites: Vec::new(),
+                components: Vec::new(),
+                key: None,
+            }),
+            "brew" => brew.packages.push(name),
+            "cask" => brew.casks.push(name),
+            v => log::warn!("line {}: '{}' entries are not supported.", i + 1, v),
+        }
+    }
+
+    let mut ret = Manifest::default();
+    ret.backends.insert("brew".to_string(), brew);
+    Ok(ret)
+}
+
+/// Write the brew section of a manifest as a Brewfile.
+///
+/// # Arguments
+/// + `manifest` - The manifest.
+///
+/// # Returns
+/// The content of the Brewfile.
+fn format_brewfile(manifest: &Manifest) -> String {
+    let mut ret = String::new();
+    let Some(brew) = manifest.backends.get("brew") else {
+        return ret;
+    };
+
+    for repo in brew.repos.iter() {
+        if repo.url.is_empty() {
+            ret.push_str(&format!("tap \"{}\"\n", repo.name));
+        } else {
+            ret.push_str(&format!("tap \"{}\", \"{}\"\n", repo.name, repo.url));
+        }
+    }
+    // Brewfiles cannot pin versions.
+    for pkg in brew.packages.iter() {
+        ret.push_str(&format!("brew \"{}\"\n", Requirement::parse(pkg).name));
+    }
+    for cask in brew.casks.iter() {
+        ret.push_str(&format!("cask \"{}\"\n", cask));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str, version: &str, manual: bool) -> crate::rpc::InstalledItem {
+        crate::rpc::InstalledItem {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: String::new(),
+            backend: "apt".to_string(),
+            origin: String::new(),
+            manual,
+            revision: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_requirement() {
+        let cases = [
+            ("curl", "curl", None),
+            (
+                "git=1:2.39.5-0+deb12u2",
+                "git",
+                Some((VersionOp::Eq, "1:2.39.5-0+deb12u2")),
+            ),
+            ("git>=2.40", "git", Some((VersionOp::Ge, "2.40"))),
+            ("git>2.40", "git", Some((VersionOp::Gt, "2.40"))),
+            ("git<=2.40", "git", Some((VersionOp::Le, "2.40"))),
+            ("git<2.40", "git", Some((VersionOp::Lt, "2.40"))),
+            ("python@3.12", "python@3.12", None),
+        ];
+        for (spec, name, constraint) in cases {
+            let req = Requirement::parse(spec);
+            assert_eq!(req.name, name, "{}", spec);
+            assert_eq!(
+                req.constraint,
+                constraint.map(|(op, v)| (op, v.to_string())),
+                "{}",
+                spec
+            );
+            assert_eq!(req.to_string(), spec);
+        }
+
+        let cases = [
+            ("curl", "7.88.1-10", true),
+            ("git=1:2.39.5-0+deb12u2", "1:2.39.5-0+deb12u2", true),
+            ("git=1:2.39.5-0+deb12u2", "1:2.39.5-0+deb12u3", false),
+            ("git>=2.40", "2.40", true),
+            ("git>=2.40", "2.40~rc1", false),
+            ("git>2.40", "2.40", false),
+            ("git>2.40", "2.40.1", true),
+            ("git<=2.40", "2.40", true),
+            ("git<2.40", "2.40", false),
+            ("git<2.40", "2.9", true),
+        ];
+        for (spec, version, expected) in cases {
+            assert_eq!(
+                Requirement::parse(spec).matches(version),
+                expected,
+                "{} {}",
+                spec,
+                version
+            );
+        }
+
+        assert_eq!(Requirement::parse("git=2.40").pinned(), Some("2.40"));
+        assert_eq!(Requirement::parse("git>=2.40").pinned(), None);
+        assert_eq!(Requirement::parse("git").pinned(), None);
+    }
+
+    #[test]
+    fn test_drift() {
+        let manifest = BackendManifest {
+            packages: vec![
+                "curl".to_string(),
+                "git>=2.40".to_string(),
+                "htop".to_string(),
+            ],
+            absent: vec!["nano".to_string(), "emacs".to_string()],
+            ..Default::default()
+        };
+        let installed = [
+            installed("curl", "7.88.1-10", true),
+            installed("git", "2.39.5-0+deb12u2", true),
+            installed("nano", "7.2-1", false),
+            installed("vim", "2:9.0.1378-2", true),
+            installed("libc6", "2.36-9", false),
+        ];
+
+        let drift = |prune: bool| -> Vec<(String, DriftKind, Option<String>, String)> {
+            manifest
+                .drift("apt", &installed, prune)
+                .into_iter()
+                .map(|x| (x.name, x.kind, x.installed, x.wanted))
+                .collect()
+        };
+        let expected = vec![
+            (
+                "git".to_string(),
+                DriftKind::WrongVersion,
+                Some("2.39.5-0+deb12u2".to_string()),
+                "git>=2.40".to_string(),
+            ),
+            (
+                "htop".to_string(),
+                DriftKind::Missing,
+                None,
+                "htop".to_string(),
+            ),
+            (
+                "nano".to_string(),
+                DriftKind::Extra,
+                Some("7.2-1".to_string()),
+                String::new(),
+            ),
+        ];
+        assert_eq!(drift(false), expected);
+
+        let mut expected = expected;
+        expected.push((
+            "vim".to_string(),
+            DriftKind::Extra,
+            Some("2:9.0.1378-2".to_string()),
+            String::new(),
+        ));
+        assert_eq!(drift(true), expected);
+    }
+
+    #[test]
+    fn test_parse() {
+        let data = r#"
+[apt]
+packages = ["curl", "git=1:2.39.5-0+deb12u2"]
+
+[[apt.repos]]
+name = "docker"
+url = "https://download.docker.com/linux/debian"
+suites = ["bookworm"]
+components = ["stable"]
+
+[flatpak]
+packages = ["org.gimp.GIMP"]
+absent = ["org.mozilla.firefox"]
+"#;
+        let manifest = Manifest::parse(data, ManifestFormat::Toml).unwrap();
+        let apt = &manifest.backends["apt"];
+        assert_eq!(apt.packages, ["curl", "git=1:2.39.5-0+deb12u2"]);
+        assert_eq!(apt.repos[0].name, "docker");
+        assert!(apt.repos[0].enabled);
+        assert_eq!(apt.repos[0].key, None);
+        assert_eq!(manifest.backends["flatpak"].absent, ["org.mozilla.firefox"]);
+
+        // The formats can be read back.
+        for format in [ManifestFormat::Toml, ManifestFormat::Json] {
+            let data = manifest.format(format).unwrap();
+            let parsed = Manifest::parse(&data, format).unwrap();
+            assert_eq!(parsed.backends["apt"].packages, apt.packages);
+            assert_eq!(parsed.backends["apt"].repos[0].url, apt.repos[0].url);
+        }
+
+        assert!(Manifest::parse("[apt]\npackage = [\"curl\"]\n", ManifestFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn test_brewfile() {
+        let data = r#"
+# Development tools.
+tap "homebrew/bundle"
+tap "example/tools", "https://git.example.com/tools.git"
+brew "ripgrep"
+brew "postgresql@16", restart_service: true
+cask "firefox"
+mas "Xcode", id: 497799835
+"#;
+        let manifest = Manifest::parse(data, ManifestFormat::Brewfile).unwrap();
+        let brew = &manifest.backends["brew"];
+        assert_eq!(brew.packages, ["ripgrep", "postgresql@16"]);
+        assert_eq!(brew.casks, ["firefox"]);
+        assert_eq!(brew.repos.len(), 2);
+        assert_eq!(brew.repos[0].url, "");
+        assert_eq!(brew.repos[1].url, "https://git.example.com/tools.git");
+
+        let mut manifest = manifest;
+        if let Some(brew) = manifest.backends.get_mut("brew") {
+            brew.packages.push("wget>=1.24".to_string());
+            brew.packages.push("jq=1.7.1".to_string());
+        }
+        assert_eq!(
+            manifest.format(ManifestFormat::Brewfile).unwrap(),
+            "\
+tap \"homebrew/bundle\"
+tap \"example/tools\", \"https://git.example.com/tools.git\"
+brew \"ripgrep\"
+brew \"postgresql@16\"
+brew \"wget\"
+brew \"jq\"
+cask \"firefox\"
+"
+        );
+
+        assert!(Manifest::parse("brew ripgrep\n", ManifestFormat::Brewfile).is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        let format = |v: &str| ManifestFormat::from_path(std::path::Path::new(v));
+        assert_eq!(format("upm.toml"), ManifestFormat::Toml);
+        assert_eq!(format("upm.json"), ManifestFormat::Json);
+        assert_eq!(format("/home/me/Brewfile"), ManifestFormat::Brewfile);
+        assert_eq!(format("work.brewfile"), ManifestFormat::Brewfile);
+        assert_eq!(format("manifest"), ManifestFormat::Toml);
+    }
+}
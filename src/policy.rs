@@ -0,0 +1,272 @@
+use serde::Deserialize;
+
+/// The default location of the policy file.
+pub const DEFAULT_POLICY_PATH: &str = "/etc/upm/policy.toml";
+
+/// Wildcard that matches any user, group, method or backend.
+const WILDCARD: &str = "*";
+
+/// Grants passing arguments from the command line to a package manager run by
+/// the root worker. They can run arbitrary code, so `*` does not grant this,
+/// it has to be listed in `methods`.
+pub const NATIVE_ARGS: &str = "native-args";
+
+/// Authorization policy evaluated by the root worker.
+///
+/// A request is allowed if any rule matches the caller, the method and the
+/// backend. The policy file looks like:
+///
+/// ```toml
+/// [[rule]]
+/// groups = ["staff"]
+/// methods = ["update", "outdated"]
+///
+/// [[rule]]
+/// users = ["admin"]
+/// methods = ["*"]
+/// backends = ["apt", "flatpak"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// The grant rules.
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// A single grant rule.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    /// The users this rule applies to.
+    #[serde(default)]
+    pub users: Vec<String>,
+
+    /// The groups this rule applies to.
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// The methods granted by this rule.
+    pub methods: Vec<String>,
+
+    /// The backends granted by this rule. Empty means all backends.
+    #[serde(default)]
+    pub backends: Vec<String>,
+}
+
+/// The user who sent a request.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// The user id.
+    pub uid: u32,
+
+    /// The user name.
+    pub name: String,
+
+    /// The names of all groups the user belongs to.
+    pub groups: Vec<String>,
+}
+
+impl Policy {
+    /// Load the policy from a file.
+    ///
+    /// # Arguments
+    /// + `path` - The path of the policy file.
+    ///
+    /// # Returns
+    /// `Ok(None)` if the file does not exist, otherwise the parsed policy.
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Option<Policy>> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let policy: Policy = toml::from_str(&data)
+            .map_err(|e| anyhow::anyhow!("invalid policy '{}': {}", path.display(), e))?;
+        Ok(Some(policy))
+    }
+
+    /// Check whether the caller may call the method on the backend.
+    ///
+    /// # Arguments
+    /// + `caller` - The user who sent the request.
+    /// + `method` - The method of the request.
+    /// + `backend` - The name of backend.
+    ///
+    /// # Returns
+    /// `true` if the request is allowed.
+    pub fn allows(&self, caller: &Caller, method: &str, backend: &str) -> bool {
+        // root can always do what it wants.
+        if caller.uid == 0 {
+            return true;
+        }
+
+        self.rules
+            .iter()
+            .any(|rule| rule.matches(caller, method, backend))
+    }
+}
+
+impl PolicyRule {
+    fn matches(&self, caller: &Caller, method: &str, backend: &str) -> bool {
+        let user = self
+            .users
+            .iter()
+            .any(|v| v == WILDCARD || *v == caller.name);
+        let group = self
+            .groups
+            .iter()
+            .any(|v| v == WILDCARD || caller.groups.contains(v));
+        if !user && !group {
+            return false;
+        }
+
+        let wildcard = method != NATIVE_ARGS;
+        if !self
+            .methods
+            .iter()
+            .any(|v| (wildcard && v == WILDCARD) || v == method)
+        {
+            return false;
+        }
+
+        self.backends.is_empty() || self.backends.iter().any(|v| v == WILDCARD || v == backend)
+    }
+}
+
+impl Caller {
+    /// Resolve the user name and groups from a user id.
+    ///
+    /// # Arguments
+    /// + `uid` - The user id.
+    ///
+    /// # Returns
+    /// The caller.
+    pub fn from_uid(uid: u32) -> anyhow::Result<Caller> {
+        use nix::unistd::{Group, Uid, User};
+
+        let user = match User::from_uid(Uid::from_raw(uid))? {
+            Some(v) => v,
+            None => {
+                return Err(anyhow::anyhow!("uid {} not found.", uid));
+            }
+        };
+
+        let name = std::ffi::CString::new(user.name.as_str())?;
+        let gids = nix::unistd::getgrouplist(&name, user.gid)?;
+
+        let mut groups = Vec::new();
+        for gid in gids {
+            if let Some(group) = Group::from_gid(gid)? {
+                groups.push(group.name);
+            }
+        }
+
+        Ok(Caller {
+            uid,
+            name: user.name,
+            groups,
+        })
+    }
+
+    /// Resolve the user who started upm.
+    ///
+    /// The root worker is started through `sudo`, so the original user is
+    /// taken from `SUDO_UID` when present.
+    ///
+    /// # Returns
+    /// The caller.
+    pub fn current() -> anyhow::Result<Caller> {
+        let uid = match std::env::var("SUDO_UID") {
+            Ok(v) => v.parse::<u32>()?,
+            Err(_) => nix::unistd::getuid().as_raw(),
+        };
+
+        Caller::from_uid(uid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(uid: u32, name: &str, groups: &[&str]) -> Caller {
+        Caller {
+            uid,
+            name: name.to_string(),
+            groups: groups.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy: Policy = toml::from_str(
+            r#"
+[[rule]]
+groups = ["staff"]
+methods = ["update", "outdated"]
+
+[[rule]]
+users = ["admin"]
+methods = ["*"]
+backends = ["apt", "flatpak"]
+
+[[rule]]
+users = ["packager"]
+methods = ["upgrade", "native-args"]
+backends = ["apt"]
+
+[[rule]]
+users = ["*"]
+methods = ["history"]
+"#,
+        )
+        .unwrap();
+
+        let alice = caller(1000, "alice", &["alice", "staff"]);
+        let admin = caller(1001, "admin", &["admin"]);
+        let packager = caller(1002, "packager", &["packager"]);
+        let guest = caller(1003, "guest", &["guest"]);
+        let root = caller(0, "root", &["root"]);
+
+        let cases = [
+            // Groups match any backend when `backends` is empty.
+            (&alice, "update", "brew", true),
+            (&alice, "outdated", "apt", true),
+            (&alice, "upgrade", "apt", false),
+            // `*` matches every method, but only the listed backends.
+            (&admin, "upgrade", "apt", true),
+            (&admin, "remove", "flatpak", true),
+            (&admin, "upgrade", "brew", false),
+            // Native arguments have to be granted by name.
+            (&admin, NATIVE_ARGS, "apt", false),
+            (&packager, NATIVE_ARGS, "apt", true),
+            (&packager, NATIVE_ARGS, "flatpak", false),
+            (&packager, "remove", "apt", false),
+            (&guest, "history", "apt", true),
+            (&guest, "update", "apt", false),
+            (&root, NATIVE_ARGS, "brew", true),
+        ];
+        for (caller, method, backend, expected) in cases {
+            assert_eq!(
+                policy.allows(caller, method, backend),
+                expected,
+                "{} {} {}",
+                caller.name,
+                method,
+                backend
+            );
+        }
+
+        assert!(!Policy::default().allows(&alice, "update", "apt"));
+        assert!(Policy::default().allows(&root, "update", "apt"));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(toml::from_str::<Policy>("[[rule]]\nusers = [\"admin\"]\n").is_err());
+        assert!(toml::from_str::<Policy>("[[rules]]\nmethods = [\"*\"]\n").is_err());
+        assert!(toml::from_str::<Policy>("").unwrap().rules.is_empty());
+    }
+}
@@ -20,7 +20,8 @@ impl Client {
     /// + `req` - The request.
     ///
     /// # Returns
-    /// The result of the request.
+    /// The result of the request. If the worker reports a failure, the error
+    /// can be downcast to [`super::RpcError`].
     pub fn call<R>(&mut self, req: &R::Params) -> anyhow::Result<R::Result>
     where
         R: super::Request,
@@ -69,8 +70,11 @@ impl Client {
         let mut data = vec![0u8; payload_len];
         self.stream.read_exact(&mut data)?;
 
-        let rsp: R::Result = serde_json::from_slice(&data)?;
-        Ok(rsp)
+        let rsp: super::RpcResponse = serde_json::from_slice(&data)?;
+        match rsp.kind {
+            super::RpcResponseKind::Ok { result } => Ok(serde_json::from_value(result)?),
+            super::RpcResponseKind::Err { error } => Err(error.into()),
+        }
     }
 
     /// Shutdown the client.
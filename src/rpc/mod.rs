@@ -25,6 +25,60 @@ pub struct RpcError {
     data: Option<serde_json::Value>,
 }
 
+impl RpcError {
+    /// The request failed for a reason not covered by other codes.
+    pub const INTERNAL_ERROR: i32 = 1;
+
+    /// The request was rejected by the authorization policy.
+    pub const PERMISSION_DENIED: i32 = 2;
+
+    /// Get the error code.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Get the error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the additional error data.
+    pub fn data(&self) -> Option<&serde_json::Value> {
+        self.data.as_ref()
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// The request is not allowed by the authorization policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionDenied {
+    /// The name of the user who sent the request.
+    pub user: String,
+    /// The method of the request.
+    pub method: String,
+    /// The name of backend.
+    pub backend_name: String,
+}
+
+impl std::fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "permission denied: user '{}' may not call '{}' on backend '{}'.",
+            self.user, self.method, self.backend_name
+        )
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum RpcResponseKind {
@@ -117,6 +171,29 @@ pub struct OutdateItem {
     pub current_version: String,
     /// The target version of package.
     pub target_version: String,
+    /// Whether a plain upgrade will install the target version.
+    pub status: OutdateStatus,
+    /// The target version fixes a security issue.
+    pub security: bool,
+}
+
+/// Why an outdated package would or would not be upgraded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutdateStatus {
+    /// A plain upgrade installs the target version.
+    #[default]
+    Upgradable,
+    /// The upgrade needs to install or remove other packages, so only a full
+    /// upgrade installs it.
+    KeptBack,
+    /// The target version is rolled out gradually and this machine is not
+    /// included yet.
+    Phased {
+        /// The share of machines that get the update, in percent.
+        percentage: u8,
+    },
+    /// The package is held at its current version.
+    Held,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -131,7 +208,918 @@ impl Request for Upgrade {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpgradeParams {
     pub backend_name: String,
+    /// Which packages to upgrade.
+    pub options: UpgradeOptions,
+}
+
+/// Options for the upgrade request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpgradeOptions {
+    /// The packages to upgrade, empty for all.
+    pub pkgs: Vec<String>,
+    /// The packages to leave alone.
+    pub exclude: Vec<String>,
+    /// Only upgrade packages whose target version fixes a security issue.
+    pub security_only: bool,
+    /// Only download what the upgrade needs, without installing it.
+    pub download_only: bool,
+    /// Only install what was downloaded before, without using the network.
+    pub offline: bool,
+    /// How apt upgrades.
+    pub apt: AptUpgradeOptions,
+    /// How flatpak upgrades.
+    pub flatpak: FlatpakUpgradeOptions,
+    /// How brew upgrades.
+    pub brew: BrewUpgradeOptions,
+    /// Arguments passed to the package manager as they are.
+    pub extra_args: Vec<String>,
+}
+
+/// How apt upgrades everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AptStrategy {
+    /// `apt-get upgrade`, never installs or removes packages.
+    #[default]
+    Upgrade,
+    /// `apt-get full-upgrade`, installs or removes packages if needed.
+    FullUpgrade,
+    /// `apt-get dist-upgrade`, the older name of `full-upgrade`.
+    DistUpgrade,
+}
+
+/// Upgrade options of apt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AptUpgradeOptions {
+    /// The command that upgrades everything.
+    pub strategy: AptStrategy,
+    /// Let `apt-get upgrade` install new dependencies, only used by the
+    /// `upgrade` strategy.
+    pub with_new_pkgs: bool,
+    /// Arguments passed to apt-get as they are.
+    pub args: Vec<String>,
+}
+
+/// Which flatpak installations to upgrade.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlatpakInstallation {
+    /// Both the system and the user installation.
+    #[default]
+    Both,
+    /// Only the system installation.
+    System,
+    /// Only the user installation.
+    User,
+}
+
+/// Upgrade options of flatpak.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlatpakUpgradeOptions {
+    /// The installations to upgrade.
+    pub installation: FlatpakInstallation,
+    /// Arguments passed to flatpak as they are.
+    pub args: Vec<String>,
+}
+
+/// Which kinds of brew packages to upgrade.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrewKind {
+    /// Formulae and casks.
+    #[default]
+    All,
+    /// Only formulae.
+    Formula,
+    /// Only casks.
+    Cask,
+}
+
+/// Upgrade options of brew.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrewUpgradeOptions {
+    /// The kinds of packages to upgrade.
+    pub kind: BrewKind,
+    /// Also upgrade casks that update themselves.
+    pub greedy: bool,
+    /// Arguments passed to brew as they are.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpgradeResult {
+    /// The packages left out of an offline upgrade because they still need
+    /// a download.
+    pub pending: Vec<String>,
+}
+
+/// A package to operate on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSpec {
+    /// The name of package.
+    pub name: String,
+    /// The version to pin, if any.
+    pub version: Option<String>,
+    /// The repository or remote that provides the package, if any.
+    pub vendor: Option<String>,
+}
+
+/// The install request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Install {}
+
+impl Request for Install {
+    type Params = InstallParams;
+    type Result = InstallResult;
+    const METHOD: &'static str = "install";
+}
+
+/// Parameters for the install request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The packages to install.
+    pub pkgs: Vec<PackageSpec>,
+}
+
+/// Result for the install request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallResult {}
+
+/// The uninstall request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Uninstall {}
+
+impl Request for Uninstall {
+    type Params = UninstallParams;
+    type Result = UninstallResult;
+    const METHOD: &'static str = "uninstall";
+}
+
+/// Parameters for the uninstall request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UninstallParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The packages to uninstall.
+    pub pkgs: Vec<PackageSpec>,
+    /// How to uninstall.
+    pub options: UninstallOptions,
+}
+
+/// Options for the uninstall request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UninstallOptions {
+    /// Also remove configuration and data of the packages.
+    pub purge: bool,
+    /// Remove the packages even if others depend on them. apt removes the
+    /// dependent packages too, so it ignores this.
+    pub force: bool,
+    /// Also remove dependencies that are no longer needed.
+    pub autoremove: bool,
+    /// Only report what would be removed.
+    pub dry_run: bool,
+}
+
+/// Result for the uninstall request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UninstallResult {
+    /// The removed packages.
+    pub pkgs: Vec<RemoveItem>,
+}
+
+/// Removed item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveItem {
+    /// The name of package.
+    pub name: String,
+    /// The version of package, empty if unknown.
+    pub version: String,
+}
+
+/// The resolve request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Resolve {}
+
+impl Request for Resolve {
+    type Params = ResolveParams;
+    type Result = ResolveResult;
+    const METHOD: &'static str = "resolve";
+}
+
+/// Parameters for the resolve request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name given by the user.
+    pub name: String,
+}
+
+/// Result for the resolve request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveResult {
+    /// The matching packages.
+    pub pkgs: Vec<PackageSpec>,
+}
+
+/// The search request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Search {}
+
+impl Request for Search {
+    type Params = SearchParams;
+    type Result = SearchResult;
+    const METHOD: &'static str = "search";
+}
+
+/// Parameters for the search request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The search keyword.
+    pub query: String,
+}
+
+/// Result for the search request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// The matching packages.
+    pub pkgs: Vec<SearchItem>,
+}
+
+/// Search item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchItem {
+    /// The name of package.
+    pub name: String,
+    /// The name of backend.
+    pub backend: String,
+    /// The vendor of package.
+    pub vendor: String,
+    /// The available version of package.
+    pub version: String,
+    /// The one line description of package.
+    pub summary: String,
+    /// The package is installed.
+    pub installed: bool,
+}
+
+/// The info request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Info {}
+
+impl Request for Info {
+    type Params = InfoParams;
+    type Result = InfoResult;
+    const METHOD: &'static str = "info";
+}
+
+/// Parameters for the info request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfoParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name of package.
+    pub name: String,
+}
+
+/// Result for the info request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfoResult {
+    /// The details of the package, `None` if not found.
+    pub pkg: Option<PackageInfo>,
+}
+
+/// The details of a package.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageInfo {
+    /// The name of package.
+    pub name: String,
+    /// The name of backend.
+    pub backend: String,
+    /// The description of package.
+    pub description: String,
+    /// The homepage of package.
+    pub homepage: Option<String>,
+    /// The license of package.
+    pub license: Option<String>,
+    /// The installed version, `None` if not installed.
+    pub installed_version: Option<String>,
+    /// The version that would be installed.
+    pub candidate_version: Option<String>,
+    /// The download size in bytes.
+    pub download_size: Option<u64>,
+    /// The installed size in bytes.
+    pub installed_size: Option<u64>,
+    /// The repository or remote the package comes from.
+    pub origin: Option<String>,
+    /// The names of the packages this package depends on.
+    pub dependencies: Vec<String>,
+}
+
+/// The list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum List {}
+
+impl Request for List {
+    type Params = ListParams;
+    type Result = ListResult;
+    const METHOD: &'static str = "list";
+}
+
+/// Parameters for the list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListParams {
+    /// The name of backend.
+    pub backend_name: String,
+}
+
+/// Result for the list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListResult {
+    /// The installed packages.
+    pub pkgs: Vec<InstalledItem>,
+}
+
+/// Installed item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledItem {
+    /// The name of package.
+    pub name: String,
+    /// The installed version of package.
+    pub version: String,
+    /// The architecture of package, empty if not applicable.
+    pub arch: String,
+    /// The name of backend.
+    pub backend: String,
+    /// The repository or remote the package comes from, empty if unknown.
+    pub origin: String,
+    /// The package was installed explicitly rather than as a dependency.
+    pub manual: bool,
+    /// The backend specific revision, e.g. the flatpak commit. Empty if the
+    /// backend has none.
+    pub revision: String,
+}
+
+/// The simulate request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Simulate {}
+
+impl Request for Simulate {
+    type Params = SimulateParams;
+    type Result = SimulateResult;
+    const METHOD: &'static str = "simulate";
+}
+
+/// Parameters for the simulate request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulateParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// Which packages to upgrade.
+    pub options: UpgradeOptions,
+}
+
+/// Result for the simulate request, the planned transaction.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimulateResult {
+    /// Packages that would be upgraded.
+    pub upgrades: Vec<PlanItem>,
+    /// Packages that would be newly installed.
+    pub installs: Vec<PlanItem>,
+    /// Packages that would be removed.
+    pub removals: Vec<PlanItem>,
+    /// Packages with an upgrade that would not be applied.
+    pub held_back: Vec<PlanItem>,
+}
+
+/// An entry of a planned transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanItem {
+    /// The name of package.
+    pub name: String,
+    /// The installed version, empty if not installed.
+    pub current_version: String,
+    /// The version after the transaction, empty if removed or unknown.
+    pub target_version: String,
 }
 
+/// The hold request.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UpgradeResult {}
+pub enum Hold {}
+
+impl Request for Hold {
+    type Params = HoldParams;
+    type Result = HoldResult;
+    const METHOD: &'static str = "hold";
+}
+
+/// The unhold request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Unhold {}
+
+impl Request for Unhold {
+    type Params = HoldParams;
+    type Result = HoldResult;
+    const METHOD: &'static str = "unhold";
+}
+
+/// Parameters for the hold and unhold requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The names of packages.
+    pub pkgs: Vec<String>,
+}
+
+/// Result for the hold and unhold requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldResult {}
+
+/// The holds request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Holds {}
+
+impl Request for Holds {
+    type Params = HoldsParams;
+    type Result = HoldsResult;
+    const METHOD: &'static str = "holds";
+}
+
+/// Parameters for the holds request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldsParams {
+    /// The name of backend.
+    pub backend_name: String,
+}
+
+/// Result for the holds request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldsResult {
+    /// The names of held packages.
+    pub pkgs: Vec<String>,
+}
+
+/// The clean request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Clean {}
+
+impl Request for Clean {
+    type Params = CleanParams;
+    type Result = CleanResult;
+    const METHOD: &'static str = "clean";
+}
+
+/// Parameters for the clean request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// What to clean.
+    pub options: CleanOptions,
+}
+
+/// Options for cleaning the package cache.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanOptions {
+    /// Remove everything cached, not only what is outdated.
+    pub all: bool,
+    /// Only estimate the bytes that would be reclaimed.
+    pub dry_run: bool,
+}
+
+/// Result for the clean request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanResult {
+    /// The bytes reclaimed, or that would be reclaimed on a dry run.
+    pub reclaimed: u64,
+}
+
+/// The orphans request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Orphans {}
+
+impl Request for Orphans {
+    type Params = OrphansParams;
+    type Result = OrphansResult;
+    const METHOD: &'static str = "orphans";
+}
+
+/// Parameters for the orphans request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphansParams {
+    /// The name of backend.
+    pub backend_name: String,
+}
+
+/// Result for the orphans request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphansResult {
+    /// The orphaned packages.
+    pub pkgs: Vec<RemoveItem>,
+}
+
+/// The record request, which adds a transaction to the history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Record {}
+
+impl Request for Record {
+    type Params = RecordParams;
+    type Result = RecordResult;
+    const METHOD: &'static str = "record";
+}
+
+/// Parameters for the record request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordParams {
+    /// The transaction to record, run by the normal worker.
+    pub transaction: crate::history::Transaction,
+}
+
+/// Result for the record request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordResult {
+    /// The id of the recorded transaction.
+    pub id: u64,
+}
+
+/// The rollback request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Rollback {}
+
+impl Request for Rollback {
+    type Params = RollbackParams;
+    type Result = RollbackResult;
+    const METHOD: &'static str = "rollback";
+}
+
+/// Parameters for the rollback request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackParams {
+    /// The id of the transaction to undo. The worker reads its changes from
+    /// the history itself.
+    pub id: u64,
+}
+
+/// Result for the rollback request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackResult {}
+
+/// The changelog request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Changelog {}
+
+impl Request for Changelog {
+    type Params = ChangelogParams;
+    type Result = ChangelogResult;
+    const METHOD: &'static str = "changelog";
+}
+
+/// Parameters for the changelog request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangelogParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name of package.
+    pub name: String,
+}
+
+/// Result for the changelog request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangelogResult {
+    /// The changes, `None` if the backend does not know the package.
+    pub changelog: Option<PackageChangelog>,
+}
+
+/// The changes between the installed and the candidate version of a package.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageChangelog {
+    /// The name of package.
+    pub name: String,
+    /// The installed version, `None` if not installed.
+    pub current_version: Option<String>,
+    /// The candidate version.
+    pub target_version: String,
+    /// The entries, newest first.
+    pub entries: Vec<ChangelogEntry>,
+}
+
+/// An entry of a changelog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    /// The version the entry describes.
+    pub version: String,
+    /// The release date, empty if unknown.
+    pub date: String,
+    /// The changes.
+    pub text: String,
+}
+
+/// A package repository: an apt source, a flatpak remote or a brew tap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Repository {
+    /// The name of repository. For apt the name of its file in
+    /// `/etc/apt/sources.list.d` without extension.
+    pub name: String,
+    /// The URL of repository.
+    pub url: String,
+    /// Whether the backend uses the repository.
+    pub enabled: bool,
+    /// The name of backend.
+    pub backend: String,
+    /// The suites of an apt repository, e.g. `bookworm`. Empty for other
+    /// backends.
+    pub suites: Vec<String>,
+    /// The components of an apt repository, e.g. `main`. Empty for other
+    /// backends.
+    pub components: Vec<String>,
+}
+
+/// Options for adding a repository.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoAddOptions {
+    /// The signing key, as a local path or URL.
+    pub key: Option<String>,
+}
+
+/// The repo list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepoList {}
+
+impl Request for RepoList {
+    type Params = RepoListParams;
+    type Result = RepoListResult;
+    const METHOD: &'static str = "repo_list";
+}
+
+/// Parameters for the repo list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoListParams {
+    /// The name of backend.
+    pub backend_name: String,
+}
+
+/// Result for the repo list request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoListResult {
+    /// The configured repositories.
+    pub repos: Vec<Repository>,
+}
+
+/// The repo add request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepoAdd {}
+
+impl Request for RepoAdd {
+    type Params = RepoAddParams;
+    type Result = RepoAddResult;
+    const METHOD: &'static str = "repo_add";
+}
+
+/// Parameters for the repo add request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoAddParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The repository to add.
+    pub repo: Repository,
+    /// The backend specific details of the repository.
+    pub options: RepoAddOptions,
+}
+
+/// Result for the repo add request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoAddResult {}
+
+/// The repo remove request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepoRemove {}
+
+impl Request for RepoRemove {
+    type Params = RepoParams;
+    type Result = RepoResult;
+    const METHOD: &'static str = "repo_remove";
+}
+
+/// The repo enable request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepoEnable {}
+
+impl Request for RepoEnable {
+    type Params = RepoParams;
+    type Result = RepoResult;
+    const METHOD: &'static str = "repo_enable";
+}
+
+/// The repo disable request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepoDisable {}
+
+impl Request for RepoDisable {
+    type Params = RepoParams;
+    type Result = RepoResult;
+    const METHOD: &'static str = "repo_disable";
+}
+
+/// Parameters for the repo remove, enable and disable requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name of repository.
+    pub name: String,
+}
+
+/// Result for the repo remove, enable and disable requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoResult {}
+
+/// The deps request, which also answers reverse dependencies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Deps {}
+
+impl Request for Deps {
+    type Params = DepsParams;
+    type Result = DepsResult;
+    const METHOD: &'static str = "deps";
+}
+
+/// Parameters for the deps request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name of package.
+    pub name: String,
+    /// Which dependencies to find.
+    pub options: DepsOptions,
+}
+
+/// Options for finding dependencies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepsOptions {
+    /// Find the packages that depend on the package instead.
+    pub reverse: bool,
+    /// Only follow installed packages.
+    pub installed: bool,
+}
+
+/// Result for the deps request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsResult {
+    /// The dependency tree, `None` if the backend does not know the package.
+    pub deps: Option<Vec<Dependency>>,
+}
+
+/// How a package depends on another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// Required to install the package.
+    Depends,
+    /// Installed along by default, but not required.
+    Recommends,
+    /// The flatpak runtime an application or extension runs on.
+    Runtime,
+    /// Only required to build the package.
+    Build,
+    /// A flatpak extension of an application or runtime.
+    Extension,
+}
+
+/// A node of a dependency tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dependency {
+    /// The name of package.
+    pub name: String,
+    /// The version constraint, e.g. `>= 2.34`. Empty if there is none.
+    pub constraint: String,
+    /// How the parent depends on this package, or for reverse dependencies
+    /// how this package depends on the parent.
+    pub kind: DependencyKind,
+    /// Whether the package is installed.
+    pub installed: bool,
+    /// The dependencies of this package. Each package is only expanded the
+    /// first time it appears in the tree.
+    pub deps: Vec<Dependency>,
+}
+
+/// The owns request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Owns {}
+
+impl Request for Owns {
+    type Params = OwnsParams;
+    type Result = OwnsResult;
+    const METHOD: &'static str = "owns";
+}
+
+/// Parameters for the owns request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnsParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The absolute path of the file.
+    pub path: std::path::PathBuf,
+}
+
+/// Result for the owns request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnsResult {
+    /// The packages that installed the file, empty if none did.
+    pub owners: Vec<FileOwner>,
+}
+
+/// A package that installed a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileOwner {
+    /// The name of package.
+    pub name: String,
+    /// The installed version.
+    pub version: String,
+    /// The name of backend.
+    pub backend: String,
+}
+
+/// The files request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Files {}
+
+impl Request for Files {
+    type Params = FilesParams;
+    type Result = FilesResult;
+    const METHOD: &'static str = "files";
+}
+
+/// Parameters for the files request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The name of package.
+    pub name: String,
+}
+
+/// Result for the files request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesResult {
+    /// The files the package installed, `None` if it is not installed.
+    pub files: Option<Vec<std::path::PathBuf>>,
+}
+
+/// The verify request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Verify {}
+
+impl Request for Verify {
+    type Params = VerifyParams;
+    type Result = VerifyResult;
+    const METHOD: &'static str = "verify";
+}
+
+/// Parameters for the verify request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyParams {
+    /// The name of backend.
+    pub backend_name: String,
+    /// The packages to verify, empty for every installed package.
+    pub pkgs: Vec<String>,
+}
+
+/// Result for the verify request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResult {
+    /// The problems found, empty if everything is intact.
+    pub issues: Vec<FileIssue>,
+}
+
+/// A file that does not match the package metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileIssue {
+    /// The name of package, empty for files no package owns.
+    pub name: String,
+    /// The file, or the ref for flatpak.
+    pub path: String,
+    /// What is wrong with the file.
+    pub status: FileStatus,
+}
+
+/// What is wrong with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    /// The content differs from what the package installed.
+    Modified,
+    /// The package installed the file, but it is gone.
+    Missing,
+    /// The file is where the backend manages files, but no package owns it,
+    /// or something else replaced the file a package installed.
+    Unexpected,
+}
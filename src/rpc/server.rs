@@ -41,6 +41,222 @@ pub trait Router {
     /// # Returns
     /// The result of the upgrade request.
     fn upgrade(&self, params: super::UpgradeParams) -> anyhow::Result<super::UpgradeResult>;
+
+    /// Install packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the install request.
+    ///
+    /// # Returns
+    /// The result of the install request.
+    fn install(&self, params: super::InstallParams) -> anyhow::Result<super::InstallResult>;
+
+    /// Uninstall packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the uninstall request.
+    ///
+    /// # Returns
+    /// The result of the uninstall request.
+    fn uninstall(&self, params: super::UninstallParams) -> anyhow::Result<super::UninstallResult>;
+
+    /// Find the packages a backend provides for a name.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the resolve request.
+    ///
+    /// # Returns
+    /// The result of the resolve request.
+    fn resolve(&self, params: super::ResolveParams) -> anyhow::Result<super::ResolveResult>;
+
+    /// Search packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the search request.
+    ///
+    /// # Returns
+    /// The result of the search request.
+    fn search(&self, params: super::SearchParams) -> anyhow::Result<super::SearchResult>;
+
+    /// Get the details of a package.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the info request.
+    ///
+    /// # Returns
+    /// The result of the info request.
+    fn info(&self, params: super::InfoParams) -> anyhow::Result<super::InfoResult>;
+
+    /// List installed packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the list request.
+    ///
+    /// # Returns
+    /// The result of the list request.
+    fn list(&self, params: super::ListParams) -> anyhow::Result<super::ListResult>;
+
+    /// Simulate an upgrade.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the simulate request.
+    ///
+    /// # Returns
+    /// The result of the simulate request.
+    fn simulate(&self, params: super::SimulateParams) -> anyhow::Result<super::SimulateResult>;
+
+    /// Hold packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the hold request.
+    ///
+    /// # Returns
+    /// The result of the hold request.
+    fn hold(&self, params: super::HoldParams) -> anyhow::Result<super::HoldResult>;
+
+    /// Unhold packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the unhold request.
+    ///
+    /// # Returns
+    /// The result of the unhold request.
+    fn unhold(&self, params: super::HoldParams) -> anyhow::Result<super::HoldResult>;
+
+    /// List held packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the holds request.
+    ///
+    /// # Returns
+    /// The result of the holds request.
+    fn holds(&self, params: super::HoldsParams) -> anyhow::Result<super::HoldsResult>;
+
+    /// Clean the package cache.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the clean request.
+    ///
+    /// # Returns
+    /// The result of the clean request.
+    fn clean(&self, params: super::CleanParams) -> anyhow::Result<super::CleanResult>;
+
+    /// List orphaned packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the orphans request.
+    ///
+    /// # Returns
+    /// The result of the orphans request.
+    fn orphans(&self, params: super::OrphansParams) -> anyhow::Result<super::OrphansResult>;
+
+    /// Record a transaction in the history.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the record request.
+    ///
+    /// # Returns
+    /// The result of the record request.
+    fn record(&self, params: super::RecordParams) -> anyhow::Result<super::RecordResult>;
+
+    /// Undo the changes of a transaction.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the rollback request.
+    ///
+    /// # Returns
+    /// The result of the rollback request.
+    fn rollback(&self, params: super::RollbackParams) -> anyhow::Result<super::RollbackResult>;
+
+    /// Get the changelog of a package.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the changelog request.
+    ///
+    /// # Returns
+    /// The result of the changelog request.
+    fn changelog(&self, params: super::ChangelogParams) -> anyhow::Result<super::ChangelogResult>;
+
+    /// List repositories.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the repo list request.
+    ///
+    /// # Returns
+    /// The result of the repo list request.
+    fn repo_list(&self, params: super::RepoListParams) -> anyhow::Result<super::RepoListResult>;
+
+    /// Add a repository.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the repo add request.
+    ///
+    /// # Returns
+    /// The result of the repo add request.
+    fn repo_add(&self, params: super::RepoAddParams) -> anyhow::Result<super::RepoAddResult>;
+
+    /// Remove a repository.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the repo remove request.
+    ///
+    /// # Returns
+    /// The result of the repo remove request.
+    fn repo_remove(&self, params: super::RepoParams) -> anyhow::Result<super::RepoResult>;
+
+    /// Enable a repository.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the repo enable request.
+    ///
+    /// # Returns
+    /// The result of the repo enable request.
+    fn repo_enable(&self, params: super::RepoParams) -> anyhow::Result<super::RepoResult>;
+
+    /// Disable a repository.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the repo disable request.
+    ///
+    /// # Returns
+    /// The result of the repo disable request.
+    fn repo_disable(&self, params: super::RepoParams) -> anyhow::Result<super::RepoResult>;
+
+    /// Get the dependencies of a package.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the deps request.
+    ///
+    /// # Returns
+    /// The result of the deps request.
+    fn deps(&self, params: super::DepsParams) -> anyhow::Result<super::DepsResult>;
+
+    /// Find the packages that installed a file.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the owns request.
+    ///
+    /// # Returns
+    /// The result of the owns request.
+    fn owns(&self, params: super::OwnsParams) -> anyhow::Result<super::OwnsResult>;
+
+    /// List the files of a package.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the files request.
+    ///
+    /// # Returns
+    /// The result of the files request.
+    fn files(&self, params: super::FilesParams) -> anyhow::Result<super::FilesResult>;
+
+    /// Verify installed packages.
+    ///
+    /// # Arguments
+    /// + `params` - The parameters of the verify request.
+    ///
+    /// # Returns
+    /// The result of the verify request.
+    fn verify(&self, params: super::VerifyParams) -> anyhow::Result<super::VerifyResult>;
 }
 
 impl Server {
@@ -55,16 +271,28 @@ impl Server {
         Self { stream }
     }
 
-    /// Serve the request.
+    /// Serve requests until the peer closes the connection.
     pub fn serve(&mut self, router: &dyn Router) -> anyhow::Result<()> {
+        while self.serve_one(router)? {}
+        Ok(())
+    }
+
+    /// Serve a single request.
+    ///
+    /// # Returns
+    /// `Ok(false)` if the peer closed the connection, otherwise `Ok(true)`.
+    fn serve_one(&mut self, router: &dyn Router) -> anyhow::Result<bool> {
         use std::io::{Read, Write};
 
         // Receive 4 bytes magic header and verify.
         {
             let mut magic = [0; 4];
-            self.stream.read_exact(&mut magic)?;
-            let magic = std::str::from_utf8(&magic).unwrap();
-            if magic != "upm:" {
+            match self.stream.read_exact(&mut magic) {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e.into()),
+            }
+            if &magic != b"upm:" {
                 return Err(anyhow::anyhow!("invalid magic header."));
             }
         }
@@ -82,21 +310,52 @@ impl Server {
         self.stream.read_exact(&mut payload)?;
         let payload = std::str::from_utf8(&payload)?;
         let msg: super::RpcRequest = serde_json::from_str(payload)?;
+        let params = msg.params.unwrap_or(serde_json::Value::Null);
 
         // Call the request.
         let result = match msg.method.as_str() {
             super::Handshake::METHOD => {
-                let params: super::HandeshakeParams = serde_json::from_value(msg.params.unwrap())?;
-                let result = router.handshake(params);
-                let result = convert_result_to_response::<super::Handshake>(result);
-                serde_json::to_value(result)?
+                dispatch::<super::Handshake>(params, |p| router.handshake(p))?
+            }
+            super::Update::METHOD => dispatch::<super::Update>(params, |p| router.update(p))?,
+            super::Outdated::METHOD => dispatch::<super::Outdated>(params, |p| router.outdated(p))?,
+            super::Upgrade::METHOD => dispatch::<super::Upgrade>(params, |p| router.upgrade(p))?,
+            super::Install::METHOD => dispatch::<super::Install>(params, |p| router.install(p))?,
+            super::Uninstall::METHOD => {
+                dispatch::<super::Uninstall>(params, |p| router.uninstall(p))?
+            }
+            super::Resolve::METHOD => dispatch::<super::Resolve>(params, |p| router.resolve(p))?,
+            super::Search::METHOD => dispatch::<super::Search>(params, |p| router.search(p))?,
+            super::Info::METHOD => dispatch::<super::Info>(params, |p| router.info(p))?,
+            super::List::METHOD => dispatch::<super::List>(params, |p| router.list(p))?,
+            super::Simulate::METHOD => dispatch::<super::Simulate>(params, |p| router.simulate(p))?,
+            super::Hold::METHOD => dispatch::<super::Hold>(params, |p| router.hold(p))?,
+            super::Unhold::METHOD => dispatch::<super::Unhold>(params, |p| router.unhold(p))?,
+            super::Holds::METHOD => dispatch::<super::Holds>(params, |p| router.holds(p))?,
+            super::Clean::METHOD => dispatch::<super::Clean>(params, |p| router.clean(p))?,
+            super::Orphans::METHOD => dispatch::<super::Orphans>(params, |p| router.orphans(p))?,
+            super::Record::METHOD => dispatch::<super::Record>(params, |p| router.record(p))?,
+            super::Rollback::METHOD => dispatch::<super::Rollback>(params, |p| router.rollback(p))?,
+            super::Changelog::METHOD => {
+                dispatch::<super::Changelog>(params, |p| router.changelog(p))?
+            }
+            super::RepoList::METHOD => {
+                dispatch::<super::RepoList>(params, |p| router.repo_list(p))?
             }
-            super::Update::METHOD => {
-                let params: super::UpdateParams = serde_json::from_value(msg.params.unwrap())?;
-                let result = router.update(params);
-                let result = convert_result_to_response::<super::Update>(result);
-                serde_json::to_value(result)?
+            super::RepoAdd::METHOD => dispatch::<super::RepoAdd>(params, |p| router.repo_add(p))?,
+            super::RepoRemove::METHOD => {
+                dispatch::<super::RepoRemove>(params, |p| router.repo_remove(p))?
             }
+            super::RepoEnable::METHOD => {
+                dispatch::<super::RepoEnable>(params, |p| router.repo_enable(p))?
+            }
+            super::RepoDisable::METHOD => {
+                dispatch::<super::RepoDisable>(params, |p| router.repo_disable(p))?
+            }
+            super::Deps::METHOD => dispatch::<super::Deps>(params, |p| router.deps(p))?,
+            super::Owns::METHOD => dispatch::<super::Owns>(params, |p| router.owns(p))?,
+            super::Files::METHOD => dispatch::<super::Files>(params, |p| router.files(p))?,
+            super::Verify::METHOD => dispatch::<super::Verify>(params, |p| router.verify(p))?,
             _ => {
                 return Err(anyhow::anyhow!("unknown method '{}'.", msg.method));
             }
@@ -117,10 +376,31 @@ impl Server {
             self.stream.write_all(data.as_bytes())?;
         }
 
-        Ok(())
+        Ok(true)
     }
 }
 
+/// Decode the parameters, call the handler and encode the response.
+///
+/// # Arguments
+/// + `params` - The raw parameters of the request.
+/// + `handler` - The router method that handles the request.
+///
+/// # Returns
+/// The encoded response.
+fn dispatch<R>(
+    params: serde_json::Value,
+    handler: impl FnOnce(R::Params) -> anyhow::Result<R::Result>,
+) -> anyhow::Result<serde_json::Value>
+where
+    R: super::Request,
+{
+    let params: R::Params = serde_json::from_value(params)?;
+    let result = handler(params);
+    let result = convert_result_to_response::<R>(result);
+    Ok(serde_json::to_value(result)?)
+}
+
 fn convert_result_to_response<R>(result: anyhow::Result<R::Result>) -> super::RpcResponse
 where
     R: super::Request,
@@ -131,14 +411,23 @@ where
                 result: serde_json::to_value(result).unwrap(),
             },
         },
-        Err(err) => super::RpcResponse {
-            kind: super::RpcResponseKind::Err {
-                error: super::RpcError {
-                    code: 1,
-                    message: err.to_string(),
-                    data: None,
+        Err(err) => {
+            let (code, data) = match err.downcast_ref::<super::PermissionDenied>() {
+                Some(v) => (
+                    super::RpcError::PERMISSION_DENIED,
+                    serde_json::to_value(v).ok(),
+                ),
+                None => (super::RpcError::INTERNAL_ERROR, None),
+            };
+            super::RpcResponse {
+                kind: super::RpcResponseKind::Err {
+                    error: super::RpcError {
+                        code,
+                        message: err.to_string(),
+                        data,
+                    },
                 },
-            },
-        },
+            }
+        }
     }
 }